@@ -0,0 +1,96 @@
+use std::collections::BTreeSet;
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{database::DatabaseAccess, report::digest_of, DomainEntry};
+
+/// The user's own notes and tags on a domain, next to the scraped data.
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+pub struct Annotation {
+    #[serde(default)]
+    pub notes: Vec<Note>,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Note {
+    pub at: u64,
+    pub text: String,
+}
+
+impl Annotation {
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty() && self.tags.is_empty()
+    }
+
+    pub fn merge(self, other: Self) -> Self {
+        let mut notes = self.notes;
+        for note in other.notes {
+            if !notes.contains(&note) {
+                notes.push(note);
+            }
+        }
+        notes.sort_by_key(|n| n.at);
+
+        Self {
+            notes,
+            tags: self.tags.into_iter().chain(other.tags).collect(),
+        }
+    }
+}
+
+/// Adds a note and tags to a blocked domain, and removes tags from it.
+pub fn annotate(
+    db: &mut DatabaseAccess,
+    domain_or_digest: &str,
+    note: Option<String>,
+    add_tags: &[String],
+    remove_tags: &[String],
+) -> Result<()> {
+    let digest = digest_of(domain_or_digest);
+    let Some(mut entry) = db.get::<DomainEntry>(&digest)? else {
+        return Err(eyre!("No blocked domain with digest {digest}"));
+    };
+
+    if let Some(text) = note {
+        entry.annotation.notes.push(Note {
+            at: crate::unix_now(),
+            text,
+        });
+    }
+    entry.annotation.tags.extend(add_tags.iter().cloned());
+    entry.annotation.tags.retain(|t| !remove_tags.contains(t));
+
+    db.set(entry);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_keeps_notes_and_tags_of_both() {
+        let note = |at, text: &str| Note {
+            at,
+            text: text.to_string(),
+        };
+        let a = Annotation {
+            notes: vec![note(2, "still up"), note(1, "spam")],
+            tags: BTreeSet::from(["spam".to_string()]),
+        };
+        let b = Annotation {
+            notes: vec![note(1, "spam"), note(3, "gone")],
+            tags: BTreeSet::from(["verified-dead".to_string()]),
+        };
+
+        let merged = a.merge(b);
+        assert_eq!(
+            merged.notes,
+            vec![note(1, "spam"), note(2, "still up"), note(3, "gone")]
+        );
+        assert_eq!(merged.tags.len(), 2);
+    }
+}
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+/// https://api.fedidb.org/v1/servers
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FediDbServerPage {
+    pub data: Vec<FediDbServer>,
+    pub meta: FediDbPageMeta,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FediDbServer {
+    /// The domain name of the server.
+    pub domain: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FediDbPageMeta {
+    /// The cursor to pass to get the next page, if there is one.
+    pub next_cursor: Option<String>,
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+/// https://instances.social/api/doc/#api-Instances-listInstances
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InstancesSocialList {
+    pub instances: Vec<InstancesSocialInstance>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InstancesSocialInstance {
+    /// The domain name of the instance.
+    pub name: String,
+}
@@ -1,22 +1,90 @@
+use std::collections::BTreeSet;
+
 use serde::{Deserialize, Serialize};
 
 /// https://docs.joinmastodon.org/methods/instance/#domain_blocks
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct DomainBlock {
     /// The domain which is blocked. This may be obfuscated or partially censored.
     pub domain: String,
-    /// The SHA256 hash digest of the domain string.
+    /// The SHA256 hash digest of the domain string. Some servers leave this out.
+    #[serde(default)]
     pub digest: String,
-    /// The level to which the domain is blocked.
+    /// The level to which the domain is blocked, normalized by [`crate::severity`].
+    #[serde(default)]
     pub severity: DomainBlockSeverity,
+    /// The severity as the instance published it, when other software uses another term for it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_severity: Option<String>,
+    /// Restrictions on top of the severity. The public api doesn't show these, they come from
+    /// admin exports and other software.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub limitations: BTreeSet<Limitation>,
     /// An optional reason for the domain block.
     pub comment: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
 pub enum DomainBlockSeverity {
     #[serde(rename = "silence")]
     Silence,
     #[serde(rename = "suspend")]
     Suspend,
+    /// Federation is not limited, usually because the block only exists for its limitations.
+    #[serde(rename = "noop")]
+    Noop,
+    /// A severity this tool doesn't know about.
+    #[default]
+    #[serde(rename = "unknown", other)]
+    Unknown,
+}
+
+impl std::fmt::Display for DomainBlockSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Silence => "silence",
+            Self::Suspend => "suspend",
+            Self::Noop => "noop",
+            Self::Unknown => "unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A restriction of a domain block that works independently of its severity.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Limitation {
+    RejectMedia,
+    RejectReports,
+    MarkMediaSensitive,
+}
+
+impl std::fmt::Display for Limitation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::RejectMedia => "reject media",
+            Self::RejectReports => "reject reports",
+            Self::MarkMediaSensitive => "mark media sensitive",
+        };
+        f.write_str(name)
+    }
+}
+
+/// https://docs.joinmastodon.org/methods/instance/#v2
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InstanceV2 {
+    #[serde(default)]
+    pub usage: InstanceUsage,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct InstanceUsage {
+    #[serde(default)]
+    pub users: InstanceUsers,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct InstanceUsers {
+    pub active_month: Option<u64>,
 }
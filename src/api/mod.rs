@@ -1,3 +1,13 @@
+mod fedidb;
+mod instances_social;
 mod mastodon;
+mod nodeinfo;
+mod pagination;
+mod rdap;
 
+pub use fedidb::*;
+pub use instances_social::*;
 pub use mastodon::*;
+pub use nodeinfo::*;
+pub use pagination::*;
+pub use rdap::*;
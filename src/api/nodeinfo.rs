@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+/// https://github.com/jhass/nodeinfo/blob/main/PROTOCOL.md, served at `/.well-known/nodeinfo`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NodeInfoLinks {
+    pub links: Vec<NodeInfoLink>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NodeInfoLink {
+    /// The schema of the linked document, such as `http://nodeinfo.diaspora.software/ns/schema/2.0`.
+    pub rel: String,
+    pub href: String,
+}
+
+/// https://nodeinfo.diaspora.software/schema.html
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NodeInfo {
+    pub software: NodeInfoSoftware,
+    #[serde(default)]
+    pub usage: NodeInfoUsage,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NodeInfoSoftware {
+    /// Such as `mastodon` or `pleroma`.
+    pub name: String,
+    pub version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct NodeInfoUsage {
+    #[serde(default)]
+    pub users: NodeInfoUsers,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct NodeInfoUsers {
+    pub total: Option<u64>,
+    #[serde(rename = "activeMonth")]
+    pub active_month: Option<u64>,
+}
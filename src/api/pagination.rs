@@ -0,0 +1,47 @@
+use serde_json::value::RawValue;
+
+/// Gets the url of the next page from a `Link` header, as sent by paginated mastodon endpoints.
+///
+/// https://docs.joinmastodon.org/api/guidelines/#pagination
+pub fn next_page(link_header: &str) -> Option<&str> {
+    link_header.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        let url = url.trim().strip_prefix('<')?.strip_suffix('>')?;
+
+        let is_next = params.split(';').any(|param| {
+            param
+                .trim()
+                .strip_prefix("rel=")
+                .is_some_and(|rel| rel.trim_matches('"').split(' ').any(|r| r == "next"))
+        });
+        is_next.then_some(url)
+    })
+}
+
+/// Joins the json arrays of several pages into a single array.
+pub fn concat_pages(pages: &[Vec<u8>]) -> serde_json::Result<Vec<u8>> {
+    let mut items = vec![];
+    for page in pages {
+        items.extend(serde_json::from_slice::<Vec<&RawValue>>(page)?);
+    }
+
+    serde_json::to_vec(&items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_next_link() {
+        let header = r#"<https://a.social/api/v1/admin/domain_blocks?max_id=5>; rel="next", <https://a.social/api/v1/admin/domain_blocks?min_id=9>; rel="prev""#;
+        assert_eq!(
+            next_page(header),
+            Some("https://a.social/api/v1/admin/domain_blocks?max_id=5")
+        );
+        assert_eq!(next_page(r#"<https://a.social/x>; rel="prev""#), None);
+
+        let pages = vec![br#"[{"a":1}]"#.to_vec(), br#"[{"b":2},3]"#.to_vec()];
+        assert_eq!(concat_pages(&pages).unwrap(), br#"[{"a":1},{"b":2},3]"#);
+    }
+}
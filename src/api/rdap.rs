@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+/// https://www.rfc-editor.org/rfc/rfc9083#section-5.3
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RdapDomain {
+    /// Such as `active` or `client hold`.
+    #[serde(default)]
+    pub status: Vec<String>,
+    #[serde(default)]
+    pub events: Vec<RdapEvent>,
+    #[serde(default)]
+    pub entities: Vec<RdapEntity>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RdapEvent {
+    /// Such as `registration`, `expiration` or `last changed`.
+    #[serde(rename = "eventAction")]
+    pub event_action: String,
+    #[serde(rename = "eventDate")]
+    pub event_date: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RdapEntity {
+    /// Such as `registrar` or `registrant`.
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// A jCard, as `["vcard", [[name, params, type, value], ...]]`.
+    #[serde(rename = "vcardArray")]
+    pub vcard_array: Option<serde_json::Value>,
+}
+
+impl RdapEntity {
+    /// Gets the formatted name (`fn`) from the jCard.
+    pub fn name(&self) -> Option<&str> {
+        self.vcard_array
+            .as_ref()?
+            .get(1)?
+            .as_array()?
+            .iter()
+            .find(|property| property.get(0).and_then(|p| p.as_str()) == Some("fn"))?
+            .get(3)?
+            .as_str()
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+/// Gets the directory of the responses of a domain. Domains come from the command line and from
+/// the blocklists of other instances, so anything that could point outside of `dir` is rejected.
+fn domain_dir(dir: &str, domain: &str) -> Result<PathBuf> {
+    if domain.is_empty() || domain.contains(['/', '\\', ':']) || domain.contains("..") {
+        return Err(eyre!("Invalid domain {domain:?}"));
+    }
+
+    Ok(Path::new(dir).join(domain))
+}
+
+/// Stores a raw response body as `<dir>/<domain>/<timestamp>.json.gz`.
+pub fn store(dir: &str, domain: &str, timestamp: u64, body: &[u8]) -> Result<()> {
+    let dir = domain_dir(dir, domain)?;
+    std::fs::create_dir_all(&dir).context("create archive directory")?;
+
+    let file = File::create(dir.join(format!("{timestamp}.json.gz")))
+        .context("create archive file")?;
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    encoder.write_all(body).context("write archive file")?;
+    encoder.finish().context("finish archive file")?;
+
+    Ok(())
+}
+
+/// Gets the domains that have archived responses.
+pub fn domains(dir: &str) -> Result<Vec<String>> {
+    let mut domains = vec![];
+    for entry in std::fs::read_dir(dir).context("read archive directory")? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            domains.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+
+    domains.sort();
+    Ok(domains)
+}
+
+/// Gets the timestamps of all archived responses of a domain, oldest first.
+pub fn timestamps(dir: &str, domain: &str) -> Result<Vec<u64>> {
+    let mut timestamps = vec![];
+    for entry in std::fs::read_dir(domain_dir(dir, domain)?).context("read archive directory")? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if let Some(timestamp) = name
+            .strip_suffix(".json.gz")
+            .and_then(|t| t.parse::<u64>().ok())
+        {
+            timestamps.push(timestamp);
+        }
+    }
+
+    timestamps.sort();
+    Ok(timestamps)
+}
+
+pub fn load(dir: &str, domain: &str, timestamp: u64) -> Result<Vec<u8>> {
+    let path = domain_dir(dir, domain)?.join(format!("{timestamp}.json.gz"));
+    let file = File::open(path).context("open archive file")?;
+
+    let mut body = vec![];
+    GzDecoder::new(file)
+        .read_to_end(&mut body)
+        .context("decompress archive file")?;
+
+    Ok(body)
+}
+
+pub fn remove(dir: &str, domain: &str, timestamp: u64) -> Result<()> {
+    let path = domain_dir(dir, domain)?.join(format!("{timestamp}.json.gz"));
+    std::fs::remove_file(path).context("remove archive file")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_domains_outside_archive() {
+        assert!(domain_dir("archive", "example.com").is_ok());
+        assert!(domain_dir("archive", "..").is_err());
+        assert!(domain_dir("archive", "../etc").is_err());
+        assert!(domain_dir("archive", "/etc").is_err());
+        assert!(domain_dir("archive", "a\\b").is_err());
+        assert!(domain_dir("archive", "").is_err());
+    }
+}
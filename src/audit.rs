@@ -0,0 +1,239 @@
+use std::collections::HashSet;
+
+use color_eyre::{eyre::eyre, Result};
+
+use crate::{
+    api::{DomainBlock, DomainBlockSeverity, Limitation},
+    database::DatabaseAccess,
+    enrich::RdapInfo,
+    fetch,
+    output::{Color, Table},
+    report::severity_cell,
+    severity, DomainEntry,
+};
+
+/// Domains registered less than this many seconds ago are flagged as new.
+const NEW_DOMAIN_AGE: u64 = 90 * 24 * 60 * 60;
+
+/// Reads the blocklist of the user's own instance, either as the csv exported by the mastodon
+/// admin interface or as the json returned by the domain blocks api.
+pub fn parse_blocklist(content: &str) -> Result<Vec<DomainBlock>> {
+    if content.trim_start().starts_with('[') {
+        // an audit of part of the blocklist would report blocks as missing that aren't
+        let parsed = fetch::parse_blocklist(content.as_bytes())?;
+        if let Some(first) = parsed.skipped.first() {
+            return Err(eyre!(
+                "The blocklist has {} malformed items, such as: {}",
+                parsed.skipped.len(),
+                first.message
+            ));
+        }
+        return Ok(parsed.list);
+    }
+
+    let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+    let Some(header) = lines.next() else {
+        return Ok(vec![]);
+    };
+    let header = split_csv_line(header);
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim_start_matches('#') == name)
+            .ok_or_else(|| eyre!("csv has no {name} column"))
+    };
+    let domain_column = column("domain")?;
+    let severity_column = column("severity").ok();
+    let limitation_columns = [
+        ("reject_media", Limitation::RejectMedia),
+        ("reject_reports", Limitation::RejectReports),
+    ]
+    .into_iter()
+    .filter_map(|(name, limitation)| Some((column(name).ok()?, limitation)))
+    .collect::<Vec<_>>();
+
+    let mut blocks = vec![];
+    for line in lines {
+        let fields = split_csv_line(line);
+        let Some(domain) = fields.get(domain_column) else {
+            continue;
+        };
+        let raw_severity = severity_column
+            .and_then(|c| fields.get(c))
+            .map(|s| s.as_str())
+            .unwrap_or_default();
+        let severity = severity::normalize(raw_severity);
+        let limitations = limitation_columns
+            .iter()
+            .filter(|(c, _)| fields.get(*c).is_some_and(|v| v == "true"))
+            .map(|(_, limitation)| *limitation)
+            .collect();
+        blocks.push(DomainBlock {
+            domain: domain.clone(),
+            digest: hex::encode(crate::hash_domain(domain)),
+            severity,
+            original_severity: None,
+            limitations,
+            comment: None,
+        });
+    }
+
+    Ok(blocks)
+}
+
+/// Splits a csv line into its fields, handling quoted fields.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Compares the user's own blocklist against the fetched ones: which of their blocks nobody else
+/// has, and which domains at least `min_blockers` instances block but they don't.
+pub fn audit(db: &mut DatabaseAccess, own: Vec<DomainBlock>, min_blockers: usize) -> Result<()> {
+    let own_digests = own.iter().map(|b| b.digest.clone()).collect::<HashSet<_>>();
+
+    let mut unique = Table::new(&["Domain", "Severity"]);
+    for block in &own {
+        let blocked_elsewhere = db
+            .get::<DomainEntry>(&block.digest)?
+            .is_some_and(|e| !e.blocked_by.is_empty());
+        if !blocked_elsewhere {
+            unique.add_row(vec![
+                (block.domain.clone(), None),
+                severity_cell(block.severity),
+            ]);
+        }
+    }
+
+    let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
+    let mut missing = vec![];
+    for key in keys {
+        if own_digests.contains(&key) {
+            continue;
+        }
+        let entry = db.get::<DomainEntry>(&key)?.unwrap();
+        if entry.blocked_by.len() >= min_blockers {
+            missing.push(entry);
+        }
+    }
+    missing.sort_by(|a, b| {
+        b.blocked_by
+            .len()
+            .cmp(&a.blocked_by.len())
+            .then_with(|| a.display_domain().cmp(&b.display_domain()))
+    });
+
+    let now = crate::unix_now();
+    let mut missing_table = Table::new(&["Domain", "Blocked by", "Suspended by", "Registered"]);
+    for entry in &missing {
+        let suspended = entry
+            .blocked_by
+            .values()
+            .filter(|b| b.severity == DomainBlockSeverity::Suspend)
+            .count();
+        let registered_at = match &entry.known_domain {
+            Some(domain) => db
+                .get::<RdapInfo>(domain)?
+                .and_then(|rdap| rdap.registered_timestamp()),
+            None => None,
+        };
+        missing_table.add_row(vec![
+            (entry.display_domain().to_string(), None),
+            (entry.blocked_by.len().to_string(), None),
+            (suspended.to_string(), None),
+            registration_cell(registered_at, now),
+        ]);
+    }
+
+    for (title, table) in [
+        ("Blocked by nobody else".to_string(), unique),
+        (
+            format!("Not blocked, but blocked by at least {min_blockers} instances"),
+            missing_table,
+        ),
+    ] {
+        println!("{title} ({}):", table.len());
+        if !table.is_empty() {
+            table.print();
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+/// How long ago a domain was registered, flagging domains young enough to be throwaway spam
+/// domains.
+fn registration_cell(registered_at: Option<u64>, now: u64) -> (String, Option<Color>) {
+    let Some(registered_at) = registered_at else {
+        return (String::new(), None);
+    };
+
+    let age = now.saturating_sub(registered_at);
+    if age < NEW_DOMAIN_AGE {
+        (
+            format!("{} ago (new)", crate::format_age(age)),
+            Some(Color::Yellow),
+        )
+    } else {
+        (format!("{} ago", crate::format_age(age)), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    #[test]
+    fn parses_mastodon_csv_export() {
+        let csv = "#domain,#severity,#reject_media,#reject_reports,#public_comment,#obfuscate\n\
+                   spam.example,suspend,false,false,\"spam, lots of it\",false\n\
+                   loud.example,silence,true,false,,false\n";
+        let blocks = parse_blocklist(csv).unwrap();
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].domain, "spam.example");
+        assert_eq!(blocks[0].severity, DomainBlockSeverity::Suspend);
+        assert_eq!(
+            blocks[0].digest,
+            hex::encode(crate::hash_domain("spam.example"))
+        );
+        assert_eq!(blocks[1].severity, DomainBlockSeverity::Silence);
+        assert!(blocks[0].limitations.is_empty());
+        assert_eq!(
+            blocks[1].limitations,
+            BTreeSet::from([Limitation::RejectMedia])
+        );
+    }
+
+    #[test]
+    fn rejects_json_with_malformed_items() {
+        let json = r#"[{"domain":"spam.example","digest":"00","severity":"suspend"},{"domain":1}]"#;
+        assert!(parse_blocklist(json).is_err());
+    }
+
+    #[test]
+    fn splits_quoted_fields() {
+        assert_eq!(
+            split_csv_line(r#"a,"b, ""c""",d"#),
+            vec!["a", r#"b, "c""#, "d"]
+        );
+    }
+}
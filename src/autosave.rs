@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+use color_eyre::Result;
+
+use crate::{config::Config, database::DatabaseAccess};
+
+/// Saves the database during long operations once enough time has passed or enough changes were
+/// made since the last save.
+pub struct Autosave {
+    path: &'static str,
+    interval: Option<Duration>,
+    every_changes: Option<usize>,
+    last_save: Instant,
+    changes: usize,
+}
+
+impl Autosave {
+    pub fn new(config: &Config, path: &'static str) -> Self {
+        Self {
+            path,
+            interval: config.autosave_interval_secs.map(Duration::from_secs),
+            every_changes: config.autosave_every_changes,
+            last_save: Instant::now(),
+            changes: 0,
+        }
+    }
+
+    /// Records a change, then saves if needed.
+    pub fn changed(&mut self, db: &DatabaseAccess) -> Result<()> {
+        self.changes += 1;
+        self.check(db)
+    }
+
+    /// Saves if enough time has passed or enough changes were made.
+    pub fn check(&mut self, db: &DatabaseAccess) -> Result<()> {
+        let time_due = self
+            .interval
+            .is_some_and(|interval| self.changes > 0 && self.last_save.elapsed() >= interval);
+        let changes_due = self
+            .every_changes
+            .is_some_and(|every| every > 0 && self.changes >= every);
+
+        if time_due || changes_due {
+            db.save(self.path)?;
+            self.last_save = Instant::now();
+            self.changes = 0;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{
+    fetch::{BlocklistFetcher, FetchError, FetchStatus, Obfuscation},
+    output::{Color, Table},
+    source,
+};
+
+/// Checks whether instances publish their blocklist and how much of it is obfuscated, without
+/// storing anything, to vet candidate seeds.
+pub async fn probe(fetcher: &impl BlocklistFetcher, domains: &[String]) {
+    let mut table = Table::new(&["Domain", "Status", "Blocks", "Obfuscation", "Details"]);
+    for domain in domains {
+        let mut first_error = None;
+        let mut found = None;
+        for source in source::SOURCES {
+            let attempt = fetcher
+                .fetch_blocklist(domain, source.path())
+                .await
+                .and_then(|fetched| {
+                    source
+                        .parse(&fetched.body)
+                        .map_err(|e| FetchError::new(FetchStatus::ParseError, e))
+                });
+            match attempt {
+                Ok(parsed) => {
+                    found = Some((*source, parsed));
+                    break;
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        let row = match found {
+            Some((source, parsed)) => {
+                let obfuscated = parsed
+                    .list
+                    .iter()
+                    .filter(|b| b.domain.contains('*'))
+                    .count();
+                vec![
+                    (domain.clone(), None),
+                    (FetchStatus::Ok.to_string(), Some(Color::Green)),
+                    (parsed.list.len().to_string(), None),
+                    (
+                        format!("{} ({obfuscated})", Obfuscation::of(&parsed.list)),
+                        None,
+                    ),
+                    (format!("{} api", source.name()), None),
+                ]
+            }
+            None => {
+                let error = first_error.expect("there is at least one block source");
+                vec![
+                    (domain.clone(), None),
+                    (error.status.to_string(), Some(Color::Red)),
+                    (String::new(), None),
+                    (String::new(), None),
+                    (error.message, None),
+                ]
+            }
+        };
+        table.add_row(row);
+    }
+
+    table.print();
+}
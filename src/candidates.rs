@@ -0,0 +1,223 @@
+use std::time::Instant;
+
+use color_eyre::{eyre::Context, Result};
+use rayon::prelude::*;
+
+use crate::{hash_domain, keyspace::Shard, plausible};
+
+/// A partial domain being cracked.
+pub struct Target<'a> {
+    pub pattern: &'a str,
+    /// The possible characters of each wildcard in `pattern`, in order.
+    pub charsets: &'a [Vec<u8>],
+    pub digest: [u8; 32],
+    /// Sources that may take long stop at this time.
+    pub deadline: Option<Instant>,
+    /// The slice of the keyspace to brute-force. Other sources are cheap and always run in full.
+    pub shard: Shard,
+    /// Where brute-forcing starts, as an index into the keyspace. The candidates of `shard`
+    /// before it were tried by an earlier run.
+    pub resume_at: usize,
+}
+
+/// The outcome of running a source on a target.
+pub struct Search {
+    pub found: Option<String>,
+    /// How many candidates were hashed. Sources that stop at the first match count the candidates
+    /// they would have tried.
+    pub tried: u64,
+    /// Where a search that was stopped early can continue, for sources that support that.
+    pub resume_at: Option<usize>,
+}
+
+/// A strategy for finding the domain behind a partial domain. `crack` runs the enabled sources
+/// of a job in order until one finds the domain, so a new strategy only needs an implementation
+/// of this and a place in the pipeline. Hosting suffixes are not a source, they get their own
+/// jobs instead (see [`crate::suffix`]).
+pub trait CandidateSource: Sync {
+    /// Recorded as the source of the resolutions this finds.
+    fn name(&self) -> &'static str;
+
+    fn find(&self, target: &Target) -> Search;
+}
+
+/// Finds the first candidate that hashes to the digest, for sources that produce a list of
+/// candidates ordered from most to least likely.
+fn first_match(candidates: Vec<String>, digest: [u8; 32]) -> Search {
+    let tried = candidates.len() as u64;
+    let found = candidates
+        .into_par_iter()
+        .find_first(|c| hash_domain(c) == digest);
+
+    Search {
+        found,
+        tried,
+        resume_at: None,
+    }
+}
+
+/// Whether a candidate has the same length and visible characters as the pattern, and only
+/// allowed characters in place of the wildcards.
+fn fits(candidate: &str, target: &Target) -> bool {
+    if candidate.len() != target.pattern.len() {
+        return false;
+    }
+
+    let mut charsets = target.charsets.iter();
+    candidate
+        .bytes()
+        .zip(target.pattern.bytes())
+        .all(|(c, p)| match p {
+            b'*' => charsets.next().is_some_and(|set| set.contains(&c)),
+            p => c == p,
+        })
+}
+
+/// Candidates made of common domain words.
+pub struct Plausible;
+
+impl CandidateSource for Plausible {
+    fn name(&self) -> &'static str {
+        "plausible"
+    }
+
+    fn find(&self, target: &Target) -> Search {
+        first_match(
+            plausible::ranked(target.pattern, target.charsets),
+            target.digest,
+        )
+    }
+}
+
+/// Candidates from a user-provided list of domains, one per line.
+pub struct Wordlist {
+    words: Vec<String>,
+}
+
+impl Wordlist {
+    pub fn load(path: &str) -> Result<Self> {
+        let content = std::fs::read_to_string(path).context("read wordlist")?;
+        let words = content
+            .lines()
+            .map(|line| line.trim().to_lowercase())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+
+        Ok(Self { words })
+    }
+}
+
+impl CandidateSource for Wordlist {
+    fn name(&self) -> &'static str {
+        "wordlist"
+    }
+
+    fn find(&self, target: &Target) -> Search {
+        let candidates = self
+            .words
+            .iter()
+            .filter(|w| fits(w, target))
+            .cloned()
+            .collect();
+        first_match(candidates, target.digest)
+    }
+}
+
+/// Every combination of the wildcard characters. Always finds the domain when given the time.
+pub struct BruteForce;
+
+impl CandidateSource for BruteForce {
+    fn name(&self) -> &'static str {
+        "brute-force"
+    }
+
+    fn find(&self, target: &Target) -> Search {
+        let (found, tried, resume_at) = crate::brute_force(
+            target.pattern,
+            target.charsets,
+            target.digest,
+            target.shard,
+            target.resume_at,
+            target.deadline,
+        );
+        Search {
+            found,
+            tried,
+            resume_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyspace::ALPHABET;
+
+    #[test]
+    fn wordlist_only_tries_fitting_words() {
+        let wordlist = Wordlist {
+            words: vec![
+                "example.org".into(),
+                "exbmple.com".into(),
+                "example.com".into(),
+            ],
+        };
+        let charsets = vec![ALPHABET.to_vec()];
+        let target = Target {
+            pattern: "ex*mple.com",
+            charsets: &charsets,
+            digest: hash_domain("example.com"),
+            deadline: None,
+            shard: Shard::default(),
+            resume_at: 0,
+        };
+
+        assert!(!fits("example.org", &target));
+        assert!(fits("exbmple.com", &target));
+        let search = wordlist.find(&target);
+        assert_eq!(search.found.as_deref(), Some("example.com"));
+        assert_eq!(search.tried, 2);
+
+        let charsets = vec![b"b".to_vec()];
+        let target = Target {
+            charsets: &charsets,
+            ..target
+        };
+        assert!(!fits("example.com", &target));
+    }
+
+    #[test]
+    fn brute_force_resumes_after_tried_candidates() {
+        let charsets = vec![ALPHABET.to_vec()];
+        let target = Target {
+            pattern: "ex*mple.com",
+            charsets: &charsets,
+            digest: hash_domain("example.com"),
+            deadline: None,
+            shard: Shard::default(),
+            resume_at: 0,
+        };
+
+        let search = BruteForce.find(&target);
+        assert_eq!(search.found.as_deref(), Some("example.com"));
+        assert_eq!(search.resume_at, None);
+
+        // 'a' is the first candidate, so it was tried before the checkpoint
+        let resumed = Target {
+            resume_at: 1,
+            ..target
+        };
+        let search = BruteForce.find(&resumed);
+        assert_eq!(search.found, None);
+        assert_eq!(search.tried, ALPHABET.len() as u64 - 1);
+
+        let late = Target {
+            deadline: Some(Instant::now()),
+            resume_at: 5,
+            ..resumed
+        };
+        let search = BruteForce.find(&late);
+        assert_eq!(search.found, None);
+        assert_eq!(search.resume_at, Some(5));
+    }
+}
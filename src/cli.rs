@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use color_eyre::{eyre::eyre, Result};
+
+/// The arguments passed after a verb. Flags and options are taken out first, whatever remains
+/// are positional arguments.
+pub struct Args(Vec<String>);
+
+impl Args {
+    pub fn new(args: impl IntoIterator<Item = String>) -> Self {
+        Self(args.into_iter().collect())
+    }
+
+    /// Takes a flag such as `--discovered`, returning whether it was present.
+    pub fn flag(&mut self, name: &str) -> bool {
+        let len_before = self.0.len();
+        self.0.retain(|a| a != name);
+        self.0.len() != len_before
+    }
+
+    /// Takes an option with a value, either as `--name value` or `--name=value`.
+    pub fn option(&mut self, name: &str) -> Result<Option<String>> {
+        let prefix = format!("{name}=");
+        if let Some(idx) = self.0.iter().position(|a| a.starts_with(&prefix)) {
+            let arg = self.0.remove(idx);
+            return Ok(Some(arg[prefix.len()..].to_string()));
+        }
+
+        let Some(idx) = self.0.iter().position(|a| a == name) else {
+            return Ok(None);
+        };
+
+        if idx + 1 >= self.0.len() {
+            return Err(eyre!("missing value for {name}"));
+        }
+
+        let value = self.0.remove(idx + 1);
+        self.0.remove(idx);
+        Ok(Some(value))
+    }
+
+    /// Returns the remaining positional arguments, failing if any unknown flags are left.
+    pub fn positional(self) -> Result<Vec<String>> {
+        if let Some(unknown) = self.0.iter().find(|a| a.starts_with("--")) {
+            return Err(eyre!("unknown option {unknown}"));
+        }
+
+        Ok(self.0)
+    }
+}
+
+/// Parses a duration such as `90s`, `30m`, `2h` or `1d`.
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| eyre!("missing unit in duration {value}"))?;
+    let (amount, unit) = value.split_at(split);
+    let amount = amount
+        .parse::<u64>()
+        .map_err(|e| eyre!("invalid duration {value}: {e}"))?;
+
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        unit => return Err(eyre!("unknown duration unit {unit}")),
+    };
+    let seconds = amount
+        .checked_mul(seconds)
+        .ok_or_else(|| eyre!("duration {value} is too long"))?;
+    Ok(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Args {
+        Args::new(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn flags_and_positional() {
+        let mut args = args(&["a.social", "--discovered", "b.social"]);
+
+        assert!(args.flag("--discovered"));
+        assert!(!args.flag("--other"));
+        assert_eq!(args.positional().unwrap(), vec!["a.social", "b.social"]);
+    }
+
+    #[test]
+    fn options() {
+        let mut args = args(&["--seeds-file", "seeds.txt", "--other=value", "a.social"]);
+
+        assert_eq!(
+            args.option("--seeds-file").unwrap().as_deref(),
+            Some("seeds.txt")
+        );
+        assert_eq!(args.option("--other").unwrap().as_deref(), Some("value"));
+        assert_eq!(args.option("--missing").unwrap(), None);
+        assert_eq!(args.positional().unwrap(), vec!["a.social"]);
+    }
+
+    #[test]
+    fn option_without_value() {
+        let mut args = args(&["--seeds-file"]);
+
+        assert!(args.option("--seeds-file").is_err());
+    }
+
+    #[test]
+    fn unknown_option() {
+        let args = args(&["--what"]);
+
+        assert!(args.positional().is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("2w").is_err());
+        assert!(parse_duration("99999999999999999d").is_err());
+    }
+}
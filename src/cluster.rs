@@ -0,0 +1,243 @@
+use std::collections::{HashMap, HashSet};
+
+use color_eyre::Result;
+
+use crate::{database::DatabaseAccess, MastodonBlockList};
+
+pub const DEFAULT_MAX_DISTANCE: f64 = 0.5;
+
+// a list this much contained in another, with this many identical comments, was likely copied
+const COPY_MIN_COVERAGE: f64 = 0.9;
+const COPY_MIN_MATCHING_COMMENTS: f64 = 0.8;
+// small lists overlap by chance
+const COPY_MIN_BLOCKS: usize = 10;
+
+/// Groups fetched instances by how similar their blocklists are and prints every cluster with
+/// more than one member, followed by the blocklists that were likely copied from another.
+pub fn print_clusters(db: &mut DatabaseAccess, max_distance: f64) -> Result<()> {
+    let keys = db.iter_keys::<MastodonBlockList>().collect::<Vec<_>>();
+
+    let mut instances = vec![];
+    let mut comments = vec![];
+    for key in keys {
+        let blocklist = db.get::<MastodonBlockList>(&key)?.unwrap();
+        if blocklist.list.is_empty() {
+            continue;
+        }
+
+        comments.push(
+            blocklist
+                .list
+                .into_iter()
+                .map(|b| (b.digest, b.comment))
+                .collect::<HashMap<_, _>>(),
+        );
+        instances.push(blocklist.domain);
+    }
+    let sets = comments
+        .iter()
+        .map(|c| c.keys().cloned().collect())
+        .collect::<Vec<_>>();
+
+    let clusters = cluster(&sets, max_distance);
+    let (clusters, singletons): (Vec<_>, Vec<_>) = clusters.into_iter().partition(|c| c.len() > 1);
+
+    println!(
+        "Found {} clusters of instances with similar blocklists, {} instances are not in a cluster",
+        clusters.len(),
+        singletons.len()
+    );
+    for (i, cluster) in clusters.iter().enumerate() {
+        println!();
+        println!("Cluster {} ({} instances):", i + 1, cluster.len());
+        for member in cluster {
+            println!("- {} ({} blocks)", instances[*member], sets[*member].len());
+        }
+    }
+
+    let copies = likely_copies(&comments);
+    println!();
+    println!("Found {} likely copied blocklists", copies.len());
+    for copy in &copies {
+        println!(
+            "- {} includes {:.0}% of the blocklist of {}, with {:.0}% identical comments",
+            instances[copy.consumer],
+            copy.coverage * 100.0,
+            instances[copy.source],
+            copy.matching_comments * 100.0
+        );
+    }
+
+    let consumers = copies.iter().map(|c| c.consumer).collect::<HashSet<_>>();
+    let mut creators = copies
+        .iter()
+        .filter(|c| !consumers.contains(&c.source))
+        .map(|c| instances[c.source].as_str())
+        .collect::<Vec<_>>();
+    creators.sort();
+    creators.dedup();
+    if !creators.is_empty() {
+        println!(
+            "Blocklists that are copied but not copies themselves: {}",
+            creators.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+struct LikelyCopy {
+    consumer: usize,
+    source: usize,
+    /// The part of the source blocklist that is in the consumer blocklist.
+    coverage: f64,
+    /// The part of the commented shared blocks that have the exact same comment.
+    matching_comments: f64,
+}
+
+/// Finds blocklists that are a near-superset of another blocklist with the same comments, which
+/// happens when an instance imports a shared blocklist.
+fn likely_copies(lists: &[HashMap<String, Option<String>>]) -> Vec<LikelyCopy> {
+    let mut copies = vec![];
+    for (consumer, consumer_list) in lists.iter().enumerate() {
+        for (source, source_list) in lists.iter().enumerate() {
+            if consumer == source
+                || source_list.len() < COPY_MIN_BLOCKS
+                || consumer_list.len() < source_list.len()
+            {
+                continue;
+            }
+
+            let mut shared = 0;
+            let mut commented = 0;
+            let mut matching = 0;
+            for (digest, comment) in source_list {
+                let Some(consumer_comment) = consumer_list.get(digest) else {
+                    continue;
+                };
+                shared += 1;
+
+                if comment.as_deref().is_some_and(|c| !c.is_empty()) {
+                    commented += 1;
+                    if consumer_comment == comment {
+                        matching += 1;
+                    }
+                }
+            }
+
+            // without comments a large overlap can just be consensus
+            if commented == 0 {
+                continue;
+            }
+
+            let coverage = shared as f64 / source_list.len() as f64;
+            let matching_comments = matching as f64 / commented as f64;
+            // identical lists are both reported, in both directions
+            if coverage >= COPY_MIN_COVERAGE && matching_comments >= COPY_MIN_MATCHING_COMMENTS {
+                copies.push(LikelyCopy {
+                    consumer,
+                    source,
+                    coverage,
+                    matching_comments,
+                });
+            }
+        }
+    }
+
+    copies
+}
+
+fn jaccard_distance(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+
+    1.0 - a.intersection(b).count() as f64 / union as f64
+}
+
+/// Agglomerative clustering with average linkage: the two closest clusters are merged until no
+/// two clusters are closer than `max_distance`. Returns the indices of the members of each
+/// cluster, largest first.
+fn cluster(sets: &[HashSet<String>], max_distance: f64) -> Vec<Vec<usize>> {
+    let distances = sets
+        .iter()
+        .map(|a| {
+            sets.iter()
+                .map(|b| jaccard_distance(a, b))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut clusters = (0..sets.len()).map(|i| vec![i]).collect::<Vec<_>>();
+    loop {
+        let mut closest: Option<(usize, usize, f64)> = None;
+        for i in 0..clusters.len() {
+            for j in i + 1..clusters.len() {
+                let total = clusters[i]
+                    .iter()
+                    .flat_map(|a| clusters[j].iter().map(|b| distances[*a][*b]))
+                    .sum::<f64>();
+                let distance = total / (clusters[i].len() * clusters[j].len()) as f64;
+
+                if distance <= max_distance && closest.is_none_or(|(_, _, d)| distance < d) {
+                    closest = Some((i, j, distance));
+                }
+            }
+        }
+
+        let Some((i, j, _)) = closest else {
+            break;
+        };
+        let merged = clusters.remove(j);
+        clusters[i].extend(merged);
+    }
+
+    clusters.sort_by_key(|c| std::cmp::Reverse(c.len()));
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(digests: &[&str]) -> HashSet<String> {
+        digests.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn detects_copies() {
+        let source = (0..20)
+            .map(|i| (i.to_string(), Some(format!("reason {i}"))))
+            .collect::<HashMap<_, _>>();
+        let mut consumer = source.clone();
+        consumer.insert("extra".to_string(), None);
+        let mut unrelated = source.clone();
+        for comment in unrelated.values_mut() {
+            *comment = Some("spam".to_string());
+        }
+
+        let copies = likely_copies(&[source, consumer, unrelated]);
+
+        assert_eq!(copies.len(), 1);
+        assert_eq!((copies[0].consumer, copies[0].source), (1, 0));
+    }
+
+    #[test]
+    fn clusters_similar_sets() {
+        let sets = vec![
+            set(&["a", "b", "c", "d"]),
+            set(&["x", "y", "z"]),
+            set(&["a", "b", "c"]),
+            set(&["x", "y", "z", "w"]),
+            set(&["q"]),
+        ];
+
+        let clusters = cluster(&sets, DEFAULT_MAX_DISTANCE);
+
+        assert_eq!(clusters.len(), 3);
+        assert!(clusters.contains(&vec![0, 2]));
+        assert!(clusters.contains(&vec![1, 3]));
+        assert!(clusters.contains(&vec![4]));
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::{HashMap, HashSet};
+
+use color_eyre::eyre::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{api::DomainBlockSeverity, filter::DomainFilter, report::digest_of};
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// API token for instances.social, get one at https://instances.social/api/token
+    pub instances_social_token: Option<String>,
+    /// Queue cracked domains that serve nodeinfo for fetching so their own blocklists get
+    /// collected.
+    pub crawl_resolved: bool,
+    /// How many blocklists away from a seed a cracked domain may be found to still get queued.
+    pub crawl_max_depth: u32,
+    /// Decides which instances get fetched or queued for fetching.
+    pub fetch_filter: DomainFilter,
+    /// If set, raw blocklist responses are stored gzipped in this directory for `replay`.
+    pub archive_dir: Option<String>,
+    /// Save the database during `fetch` and `crack` after this many seconds of unsaved work.
+    pub autosave_interval_secs: Option<u64>,
+    /// Save the database during `fetch` and `crack` after this many fetched instances or
+    /// resolved digests. Every save writes the whole database, so set this to 1 only when losing
+    /// any work to a crash is worse than the time spent saving.
+    pub autosave_every_changes: Option<usize>,
+    /// How much history `prune` keeps.
+    pub retention: RetentionConfig,
+    /// If set, http responses are cached in this directory, honoring their cache headers.
+    /// Can be overridden with `--cache-dir`.
+    pub cache_dir: Option<String>,
+    /// OAuth tokens by instance domain, for instances that require authorized fetch or for
+    /// admin endpoints. A token is only sent in requests to that exact host.
+    pub instance_tokens: HashMap<String, String>,
+    /// Shell commands run when something happens, with a json payload on stdin.
+    pub hooks: HooksConfig,
+    /// If set, `fetch`, `process` and `crack` write a json and markdown report of every run
+    /// to this directory.
+    pub reports_dir: Option<String>,
+    /// Words masked in block comments by `export` and `publish`.
+    pub redact_keywords: Vec<String>,
+    /// How much effort `crack` spends on a digest. The policy with the highest `min_blockers`
+    /// that the digest reaches is used, digests matching none get the default policy.
+    pub crack_policies: Vec<CrackPolicy>,
+    /// A file with one domain per line, tried on every partial domain it fits before
+    /// brute-forcing.
+    pub crack_wordlist: Option<String>,
+    /// How the consensus score of a domain is calculated.
+    pub scoring: ScoringConfig,
+    /// Domains or digests that `process` skips and `show`, `publish` and `export` leave out.
+    pub ignore: Vec<String>,
+    /// If set, `crack` rewrites this file with the metrics before and after every job, so a
+    /// textfile collector can graph its progress while it runs.
+    pub metrics_file: Option<String>,
+    /// How many queued domains of the crawl frontier a single `fetch` visits, closest to the
+    /// seeds first. The rest stay queued for the next run.
+    pub crawl_batch_size: Option<usize>,
+    /// Minimum number of seconds between two requests to the same host.
+    pub host_interval_secs: Option<u64>,
+    /// The largest blocklist `fetch` accepts from an instance, in MiB over all of its pages.
+    /// Blocklists are read into memory in full before they are parsed.
+    pub max_response_mb: usize,
+    /// Path that instance admins can serve to opt out of being crawled. Checked before fetching
+    /// an instance queued by the crawl, an instance serving it is never fetched again.
+    pub opt_out_path: Option<String>,
+    /// Maps severity terms of other software to a common severity, on top of the built-in
+    /// mapping. For example `{ "quarantine": "silence" }`.
+    pub severity_map: HashMap<String, DomainBlockSeverity>,
+    /// If set, `translate` runs this shell command for every block comment in another language
+    /// than `translate_language`, with the comment on stdin. Its output is stored as the
+    /// translation. Off by default, as it usually sends the comments to an external service.
+    pub translate_command: Option<String>,
+    /// The language `translate` translates comments to, as an ISO 639-1 code.
+    pub translate_language: String,
+    /// Hex-encoded ed25519 public keys whose datasets `import dataset` accepts. When set,
+    /// unsigned datasets and datasets signed by another key are rejected.
+    pub trusted_signers: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoringConfig {
+    /// What a suspension adds to the score of a domain.
+    pub suspend: f64,
+    /// What a silence adds to the score of a domain.
+    pub silence: f64,
+    /// What a block that only limits media or reports adds to the score of a domain.
+    pub noop: f64,
+    /// What a block with a severity this tool doesn't know adds to the score of a domain.
+    pub unknown: f64,
+    /// Count blocks of larger instances more, by their monthly active users.
+    pub weight_by_size: bool,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            suspend: 2.,
+            silence: 1.,
+            noop: 0.5,
+            unknown: 1.,
+            weight_by_size: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct CrackPolicy {
+    /// Applies to digests blocked by at least this many instances.
+    pub min_blockers: usize,
+    /// Skip patterns with more censored characters than this.
+    pub max_wildcards: Option<usize>,
+    /// Look the digest up in the domains known through discovery and in `crack_wordlist`.
+    pub dictionary: bool,
+    /// Try candidates made of common domain words.
+    pub plausible: bool,
+    pub brute_force: bool,
+    /// Give up brute-forcing a pattern after this many seconds. It is tried again next run.
+    pub max_seconds: Option<u64>,
+}
+
+impl Default for CrackPolicy {
+    fn default() -> Self {
+        Self {
+            min_blockers: 0,
+            max_wildcards: None,
+            dictionary: true,
+            plausible: true,
+            brute_force: true,
+            max_seconds: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// Run for every new block of an already known instance, with the journal event.
+    pub on_new_block: Option<String>,
+    /// Run for every digest whose domain became known, with the journal event.
+    pub on_resolved: Option<String>,
+    /// Run after `fetch`, with the instances that were updated, unchanged or failed.
+    pub on_fetch_complete: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// How many archived responses to keep per instance.
+    pub keep_snapshots: Option<usize>,
+    /// Remove archived responses older than this. The latest response of an instance is kept.
+    pub archive_max_age_days: Option<u64>,
+    /// Remove journal events older than this, except for the newest one. By default the journal
+    /// is kept forever.
+    pub journal_max_age_days: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            instances_social_token: None,
+            crawl_resolved: false,
+            crawl_max_depth: 1,
+            fetch_filter: DomainFilter::default(),
+            archive_dir: None,
+            autosave_interval_secs: Some(5 * 60),
+            autosave_every_changes: Some(100),
+            retention: RetentionConfig::default(),
+            cache_dir: None,
+            instance_tokens: HashMap::new(),
+            hooks: HooksConfig::default(),
+            reports_dir: None,
+            redact_keywords: vec![],
+            crack_policies: vec![],
+            crack_wordlist: None,
+            scoring: ScoringConfig::default(),
+            ignore: vec![],
+            metrics_file: None,
+            crawl_batch_size: None,
+            host_interval_secs: Some(1),
+            max_response_mb: 64,
+            opt_out_path: Some("/.well-known/mastodon-block-enum-opt-out".to_string()),
+            severity_map: HashMap::new(),
+            translate_command: None,
+            translate_language: "en".to_string(),
+            trusted_signers: vec![],
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file, falling back to the default config if it doesn't exist.
+    pub fn load(path: &str) -> color_eyre::Result<Self> {
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).context("read config file"),
+        };
+
+        serde_json::from_slice(&content).context("deserialize config file")
+    }
+
+    /// Gets the digests of the ignored domains.
+    pub fn ignored_digests(&self) -> HashSet<String> {
+        self.ignore.iter().map(|d| digest_of(d)).collect()
+    }
+
+    /// Gets the crack policy for a digest blocked by `blockers` instances.
+    pub fn crack_policy(&self, blockers: usize) -> CrackPolicy {
+        self.crack_policies
+            .iter()
+            .filter(|p| p.min_blockers <= blockers)
+            .max_by_key(|p| p.min_blockers)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,346 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    database::{DatabaseAccess, DatabaseObject},
+    enrich,
+    fetch::{self, HttpFetcher},
+    interrupt, journal, throttle, MastodonBlockList, DEFAULT_SEEDS,
+};
+
+/// A resolved domain that was queued to have its own blocklist fetched. Together these form the
+/// crawl frontier, which is worked through over as many runs as it takes.
+#[derive(Serialize, Deserialize)]
+pub struct FetchCandidate {
+    pub domain: String,
+    /// How many blocklists away from a seed this domain was found. Seeds have a depth of 0.
+    pub depth: u32,
+    pub added_at: u64,
+    pub fetched_at: Option<u64>,
+    /// When the fetch failed. Failed candidates are not visited again by the crawl, transient
+    /// failures are retried through the fetch status of the instance instead.
+    #[serde(default)]
+    pub failed_at: Option<u64>,
+    /// When the instance was found to publish the opt-out marker. It is never fetched again.
+    #[serde(default)]
+    pub opted_out_at: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum FrontierState {
+    Queued,
+    Visited,
+    Failed,
+    OptedOut,
+}
+
+impl std::fmt::Display for FrontierState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Queued => "queued",
+            Self::Visited => "visited",
+            Self::Failed => "failed",
+            Self::OptedOut => "opted out",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FetchCandidate {
+    pub fn state(&self) -> FrontierState {
+        if self.opted_out_at.is_some() {
+            return FrontierState::OptedOut;
+        }
+
+        match (self.fetched_at, self.failed_at) {
+            (Some(_), _) => FrontierState::Visited,
+            (None, Some(_)) => FrontierState::Failed,
+            (None, None) => FrontierState::Queued,
+        }
+    }
+}
+
+impl DatabaseObject for FetchCandidate {
+    const KEY_NAME: &'static str = "fetch_candidate";
+
+    fn get_id(&self) -> Cow<str> {
+        (&self.domain).into()
+    }
+}
+
+/// Queues the domains resolved since the journal event `since` for fetching, if they are within
+/// the configured depth. Each domain is probed for nodeinfo first, so domains that don't run
+/// fediverse software or aren't up anymore don't end up in the frontier.
+pub async fn enqueue_resolved(db: &mut DatabaseAccess, config: &Config, since: u64) -> Result<()> {
+    let mut resolved = vec![];
+    for entry in journal::events_since(db, since)? {
+        if let journal::Event::Resolved { digest, domain, .. } = entry.event {
+            resolved.push((digest, domain));
+        }
+    }
+    if resolved.is_empty() {
+        return Ok(());
+    }
+
+    let fetcher = HttpFetcher::new(config)?;
+    for (digest, domain) in resolved {
+        let Some(depth) = candidate_depth(db, config, &digest, &domain)? else {
+            continue;
+        };
+
+        if let Err(e) = enrich::probe_nodeinfo(&fetcher, &domain).await {
+            println!("Not queueing {domain}, its nodeinfo can't be read: {e}");
+            continue;
+        }
+
+        println!("Queueing {domain} for fetching at depth {depth}");
+        db.set(FetchCandidate {
+            domain,
+            depth,
+            added_at: crate::unix_now(),
+            fetched_at: None,
+            failed_at: None,
+            opted_out_at: None,
+        });
+
+        if interrupt::is_interrupted() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Gets the depth a resolved domain would be queued at, or `None` if it shouldn't be queued.
+fn candidate_depth(
+    db: &mut DatabaseAccess,
+    config: &Config,
+    digest: &str,
+    domain: &str,
+) -> Result<Option<u32>> {
+    // instances whose blocklist was fetched in another way are visited already
+    if DEFAULT_SEEDS.contains(&domain)
+        || !config.fetch_filter.allows(domain)
+        || db.get::<FetchCandidate>(domain)?.is_some()
+        || db.get::<MastodonBlockList>(domain)?.is_some()
+    {
+        return Ok(None);
+    }
+
+    // the domain is one step further from the seeds than the closest blocklist that contains it
+    let keys = db.iter_keys::<MastodonBlockList>().collect::<Vec<_>>();
+    let mut depth: Option<u32> = None;
+    for key in keys {
+        let blocklist = db.get::<MastodonBlockList>(&key)?.unwrap();
+        if blocklist.list.iter().any(|b| b.digest == digest) {
+            let found_depth = source_depth(db, &blocklist.domain)? + 1;
+            depth = Some(depth.map_or(found_depth, |d| d.min(found_depth)));
+        }
+    }
+
+    Ok(depth.filter(|&d| d <= config.crawl_max_depth))
+}
+
+/// Gets the domains of the candidates that were not visited yet, closest to the seeds and oldest
+/// first, up to `limit` of them.
+pub fn queued_domains(db: &mut DatabaseAccess, limit: Option<usize>) -> Result<Vec<String>> {
+    let keys = db.iter_keys::<FetchCandidate>().collect::<Vec<_>>();
+
+    let mut queued = vec![];
+    for key in keys {
+        let candidate = db.get::<FetchCandidate>(&key)?.unwrap();
+        if candidate.state() == FrontierState::Queued {
+            queued.push(candidate);
+        }
+    }
+    queued.sort_by(|a, b| {
+        a.depth
+            .cmp(&b.depth)
+            .then(a.added_at.cmp(&b.added_at))
+            .then_with(|| a.domain.cmp(&b.domain))
+    });
+    if let Some(limit) = limit {
+        if queued.len() > limit {
+            println!(
+                "Visiting {limit} of {} queued crawl candidates, the rest stay queued",
+                queued.len()
+            );
+            queued.truncate(limit);
+        }
+    }
+
+    Ok(queued.into_iter().map(|c| c.domain).collect())
+}
+
+/// Records that a candidate was visited, so later runs don't fetch it again.
+pub fn mark_visited(db: &mut DatabaseAccess, domain: &str, succeeded: bool) -> Result<()> {
+    if let Some(mut candidate) = db.get::<FetchCandidate>(domain)? {
+        let now = Some(crate::unix_now());
+        if succeeded {
+            candidate.fetched_at = now;
+        } else {
+            candidate.failed_at = now;
+        }
+        db.set(candidate);
+    }
+
+    Ok(())
+}
+
+/// What the opt-out marker has to contain. Catch-all pages answer every path with a success, so
+/// a successful response alone doesn't mean anything.
+pub const OPT_OUT_TOKEN: &str = "mastodon-block-enum-opt-out";
+
+// the marker is a short file, a catch-all page may be anything
+const MAX_OPT_OUT_SIZE: usize = 4096;
+
+/// Checks whether a queued candidate serves the opt-out marker at `opt_out_path`, recording it so
+/// the instance is skipped from then on. Only a successful non-HTML response that contains
+/// [`OPT_OUT_TOKEN`] counts, an unreachable instance will fail its fetch anyway.
+pub async fn check_opt_out(db: &mut DatabaseAccess, config: &Config, domain: &str) -> Result<bool> {
+    let Some(path) = &config.opt_out_path else {
+        return Ok(false);
+    };
+    let Some(mut candidate) = db
+        .get::<FetchCandidate>(domain)?
+        .filter(|c| c.state() == FrontierState::Queued)
+    else {
+        return Ok(false);
+    };
+
+    let client = fetch::client();
+    let url = format!("https://{domain}/{}", path.trim_start_matches('/'));
+    let opted_out = match throttle::send(&url, || client.get(&url)).await {
+        Ok(mut response) if response.status().is_success() => {
+            let content_type = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let mut body = vec![];
+            while let Ok(Some(chunk)) = response.chunk().await {
+                body.extend_from_slice(&chunk);
+                if body.len() >= MAX_OPT_OUT_SIZE {
+                    break;
+                }
+            }
+            is_opt_out_marker(content_type.as_deref(), &body)
+        }
+        _ => false,
+    };
+    if opted_out {
+        println!("{domain} opted out of crawling, skipping it from now on");
+        candidate.opted_out_at = Some(crate::unix_now());
+        db.set(candidate);
+    }
+
+    Ok(opted_out)
+}
+
+fn is_opt_out_marker(content_type: Option<&str>, body: &[u8]) -> bool {
+    let is_html = content_type.is_some_and(|t| t.to_ascii_lowercase().contains("html"));
+    !is_html && String::from_utf8_lossy(body).contains(OPT_OUT_TOKEN)
+}
+
+/// Gets the instances that opted out of crawling.
+pub fn opted_out_domains(db: &mut DatabaseAccess) -> Result<HashSet<String>> {
+    let keys = db.iter_keys::<FetchCandidate>().collect::<Vec<_>>();
+
+    let mut opted_out = HashSet::new();
+    for key in keys {
+        let candidate = db.get::<FetchCandidate>(&key)?.unwrap();
+        if candidate.state() == FrontierState::OptedOut {
+            opted_out.insert(candidate.domain);
+        }
+    }
+
+    Ok(opted_out)
+}
+
+/// Counts the candidates of the crawl frontier by their state.
+pub fn frontier_counts(db: &mut DatabaseAccess) -> Result<BTreeMap<FrontierState, usize>> {
+    let keys = db.iter_keys::<FetchCandidate>().collect::<Vec<_>>();
+
+    let mut counts = BTreeMap::new();
+    for key in keys {
+        let candidate = db.get::<FetchCandidate>(&key)?.unwrap();
+        *counts.entry(candidate.state()).or_default() += 1;
+    }
+
+    Ok(counts)
+}
+
+fn source_depth(db: &DatabaseAccess, domain: &str) -> Result<u32> {
+    Ok(db
+        .get::<FetchCandidate>(domain)?
+        .map(|c| c.depth)
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseInstance;
+
+    #[test]
+    fn works_through_frontier() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        for (domain, depth, added_at) in
+            [("c.social", 2, 1), ("b.social", 1, 5), ("a.social", 1, 9)]
+        {
+            db.set(FetchCandidate {
+                domain: domain.to_string(),
+                depth,
+                added_at,
+                fetched_at: None,
+                failed_at: None,
+                opted_out_at: None,
+            });
+        }
+
+        assert_eq!(
+            queued_domains(&mut db, Some(2)).unwrap(),
+            vec!["b.social", "a.social"]
+        );
+
+        mark_visited(&mut db, "b.social", true).unwrap();
+        mark_visited(&mut db, "a.social", false).unwrap();
+        assert_eq!(queued_domains(&mut db, None).unwrap(), vec!["c.social"]);
+
+        let counts = frontier_counts(&mut db).unwrap();
+        assert_eq!(counts[&FrontierState::Queued], 1);
+        assert_eq!(counts[&FrontierState::Visited], 1);
+        assert_eq!(counts[&FrontierState::Failed], 1);
+
+        let mut candidate = db.get::<FetchCandidate>("c.social").unwrap().unwrap();
+        candidate.opted_out_at = Some(10);
+        db.set(candidate);
+        assert!(queued_domains(&mut db, None).unwrap().is_empty());
+        assert_eq!(
+            opted_out_domains(&mut db).unwrap(),
+            HashSet::from(["c.social".to_string()])
+        );
+    }
+
+    #[test]
+    fn only_a_marker_file_opts_out() {
+        let marker = format!("{OPT_OUT_TOKEN}\n");
+        assert!(is_opt_out_marker(Some("text/plain"), marker.as_bytes()));
+        assert!(is_opt_out_marker(None, marker.as_bytes()));
+
+        // a catch-all page of a frontend that answers every path
+        let page = format!("<html><body>Not found: /.well-known/{OPT_OUT_TOKEN}</body></html>");
+        assert!(!is_opt_out_marker(
+            Some("text/html; charset=utf-8"),
+            page.as_bytes()
+        ));
+        assert!(!is_opt_out_marker(Some("text/html"), b""));
+        assert!(!is_opt_out_marker(Some("application/json"), b"{}"));
+    }
+}
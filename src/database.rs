@@ -11,6 +11,12 @@ pub struct DatabaseInstance {
 }
 
 impl DatabaseInstance {
+    /// A database that only lives in memory, for tests. Nothing is written to disk unless it is
+    /// saved.
+    pub fn in_memory() -> DatabaseAccess {
+        Self::default().use_namespace("memory")
+    }
+
     pub fn load(path: &str) -> color_eyre::Result<Self> {
         let content = std::fs::read(path).context("read database file")?;
         let content = serde_json::from_slice(&content).context("deserialize database file")?;
@@ -59,6 +65,51 @@ impl DatabaseAccess {
         self.db.content.set(self.namespace, value)
     }
 
+    /// Removes an object, returning whether it existed.
+    pub fn remove<T: DatabaseObject>(&mut self, object_id: &str) -> bool {
+        self.db.content.remove::<T>(self.namespace, object_id)
+    }
+
+    /// Gets every key in the namespace, including the type prefix, for checking its integrity.
+    pub fn raw_keys(&self) -> Vec<String> {
+        self.db.content.0[self.namespace].keys().cloned().collect()
+    }
+
+    /// Removes an object by its full key, returning whether it existed.
+    pub fn remove_raw(&mut self, key: &str) -> bool {
+        self.db
+            .content
+            .0
+            .get_mut(self.namespace)
+            .expect("get namespace after check")
+            .remove(key)
+            .is_some()
+    }
+
+    /// Renders every object in the namespace as one `key = json` line, ordered by key, so tests
+    /// can compare the state of the database to an expected snapshot and get a readable diff.
+    pub fn snapshot(&self) -> String {
+        self.snapshot_of(&[])
+    }
+
+    /// Like `snapshot`, but only includes objects of these types. All objects are included when
+    /// `key_names` is empty. Useful to leave out objects with timestamps, such as the journal.
+    pub fn snapshot_of(&self, key_names: &[&str]) -> String {
+        let mut snapshot = String::new();
+        for (key, value) in &self.db.content.0[self.namespace] {
+            let key_name = key.split_once(':').map_or(key.as_str(), |(left, _)| left);
+            if key_names.is_empty() || key_names.contains(&key_name) {
+                snapshot += &format!("{key} = {value}\n");
+            }
+        }
+        snapshot
+    }
+
+    /// Saves the whole database, so long-running operations can persist their progress.
+    pub fn save(&self, path: &str) -> color_eyre::Result<()> {
+        self.db.save(path)
+    }
+
     pub fn pop_namespace(self) -> DatabaseInstance {
         self.db
     }
@@ -111,6 +162,14 @@ impl DatabaseContent {
 
         namespace.insert(object_id, json_value).is_some()
     }
+
+    fn remove<T: DatabaseObject>(&mut self, namespace: &str, id: &str) -> bool {
+        self.0
+            .get_mut(namespace)
+            .expect("get namespace after check")
+            .remove(&get_object_id::<T>(id))
+            .is_some()
+    }
 }
 
 fn get_object_id<T: DatabaseObject>(id: &str) -> String {
@@ -160,6 +219,28 @@ mod tests {
         assert!(dba.get::<MyDbItem1>("123").unwrap().is_some());
     }
 
+    #[test]
+    fn snapshots() {
+        let mut dba = DatabaseInstance::in_memory();
+        dba.set(MyDbItem2 {
+            id: "456".to_string(),
+        });
+        dba.set(MyDbItem1 {
+            id: "123".to_string(),
+            name: "Jeffrey".into(),
+        });
+
+        assert_eq!(
+            dba.snapshot(),
+            "my_db_item:123 = {\"id\":\"123\",\"name\":\"Jeffrey\"}\n\
+             my_db_item_2:456 = {\"id\":\"456\"}\n"
+        );
+        assert_eq!(
+            dba.snapshot_of(&[MyDbItem2::KEY_NAME]),
+            "my_db_item_2:456 = {\"id\":\"456\"}\n"
+        );
+    }
+
     #[test]
     fn read_no_object() {
         let db = DatabaseInstance::default();
@@ -168,6 +249,23 @@ mod tests {
         assert!(dba.get::<MyDbItem1>("123").unwrap().is_none());
     }
 
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("db_save_test_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let db = DatabaseInstance::default();
+        let mut dba = db.use_namespace("test_db");
+        dba.set(MyDbItem2 {
+            id: "123".to_string(),
+        });
+        dba.save(path).unwrap();
+
+        let loaded = DatabaseInstance::load(path).unwrap().use_namespace("test_db");
+        std::fs::remove_file(path).unwrap();
+        assert!(loaded.get::<MyDbItem2>("123").unwrap().is_some());
+    }
+
     #[test]
     fn get_keys() {
         let db = DatabaseInstance::default();
@@ -188,4 +286,17 @@ mod tests {
         let items = dba.iter_keys::<MyDbItem1>().collect::<Vec<_>>();
         assert_eq!(items, vec![123.to_string(), 456.to_string()]);
     }
+
+    #[test]
+    fn remove() {
+        let db = DatabaseInstance::default();
+        let mut dba = db.use_namespace("test_db");
+        dba.set(MyDbItem2 {
+            id: "123".to_string(),
+        });
+
+        assert!(dba.remove::<MyDbItem2>("123"));
+        assert!(!dba.remove::<MyDbItem2>("123"));
+        assert!(dba.get::<MyDbItem2>("123").unwrap().is_none());
+    }
 }
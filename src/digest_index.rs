@@ -0,0 +1,70 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{hash_domain, DomainEntry};
+
+/// Unresolved digests grouped by the length of their domain, which censoring keeps. Candidates
+/// are only hashed when some digest has their length, and then only compared against that
+/// bucket, which keeps lookups small.
+pub struct DigestIndex {
+    by_length: HashMap<usize, HashSet<[u8; 32]>>,
+    /// Digests without any partial domain, which could have any length.
+    unknown_length: HashSet<[u8; 32]>,
+}
+
+impl DigestIndex {
+    pub fn new<'a>(entries: impl IntoIterator<Item = &'a DomainEntry>) -> Self {
+        let mut index = Self {
+            by_length: HashMap::new(),
+            unknown_length: HashSet::new(),
+        };
+
+        for entry in entries {
+            if entry.partial_domains.is_empty() {
+                index.unknown_length.insert(entry.digest);
+            }
+            for partial in &entry.partial_domains {
+                index
+                    .by_length
+                    .entry(partial.len())
+                    .or_default()
+                    .insert(entry.digest);
+            }
+        }
+
+        index
+    }
+
+    /// Gets the digest `candidate` hashes to, if it is one of the indexed digests.
+    pub fn lookup(&self, candidate: &str) -> Option<[u8; 32]> {
+        let bucket = self.by_length.get(&candidate.len());
+        if bucket.is_none() && self.unknown_length.is_empty() {
+            return None;
+        }
+
+        let digest = hash_domain(candidate);
+        (bucket.is_some_and(|b| b.contains(&digest)) || self.unknown_length.contains(&digest))
+            .then_some(digest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    #[test]
+    fn looks_up_by_length() {
+        let entry = DomainEntry {
+            digest: hash_domain("example.com"),
+            known_domain: None,
+            partial_domains: BTreeSet::from(["exa*ple.com".to_string()]),
+            blocked_by: Default::default(),
+            annotation: Default::default(),
+        };
+        let index = DigestIndex::new([&entry]);
+
+        assert_eq!(index.lookup("example.com"), Some(entry.digest));
+        assert_eq!(index.lookup("examples.com"), None);
+    }
+}
@@ -0,0 +1,131 @@
+use std::{borrow::Cow, collections::BTreeSet};
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{FediDbServerPage, InstancesSocialList},
+    config::Config,
+    database::{DatabaseAccess, DatabaseObject},
+    fetch::{self, PROJECT_USER_AGENT},
+    throttle,
+};
+
+// fedidb returns 40 servers per page, this keeps a refresh at a reasonable amount of requests
+const FEDIDB_MAX_PAGES: usize = 50;
+
+/// A list of instance domains discovered through an external instance directory.
+#[derive(Serialize, Deserialize)]
+pub struct DiscoveredInstances {
+    pub source: String,
+    /// Unix timestamp of the last refresh.
+    pub fetched_at: u64,
+    pub domains: BTreeSet<String>,
+}
+
+impl DatabaseObject for DiscoveredInstances {
+    const KEY_NAME: &'static str = "discovered";
+
+    fn get_id(&self) -> Cow<str> {
+        (&self.source).into()
+    }
+}
+
+pub async fn discover(db: &mut DatabaseAccess, config: &Config) {
+    if let Some(token) = &config.instances_social_token {
+        try_store(db, "instances.social", load_instances_social(token).await);
+    } else {
+        println!("No instances.social token configured, skipping");
+    }
+
+    try_store(db, "fedidb", load_fedidb().await);
+}
+
+fn try_store(db: &mut DatabaseAccess, source: &str, domains: Result<BTreeSet<String>>) {
+    match domains {
+        Ok(domains) => {
+            println!("Discovered {} instances through {source}", domains.len());
+            db.set(DiscoveredInstances {
+                source: source.to_string(),
+                fetched_at: crate::unix_now(),
+                domains,
+            });
+        }
+        Err(e) => println!("Error while trying to discover instances through {source}: {e}"),
+    }
+}
+
+async fn load_instances_social(token: &str) -> Result<BTreeSet<String>> {
+    let client = fetch::client();
+
+    let url = "https://instances.social/api/1.0/instances/list?count=0&include_dead=false";
+    let list: InstancesSocialList = throttle::send(url, || {
+        client
+            .get(url)
+            .header("user-agent", PROJECT_USER_AGENT)
+            .bearer_auth(token)
+    })
+    .await?
+    .error_for_status()?
+    .json()
+    .await?;
+
+    Ok(list
+        .instances
+        .into_iter()
+        .map(|i| normalize_domain(&i.name))
+        .collect())
+}
+
+async fn load_fedidb() -> Result<BTreeSet<String>> {
+    let client = fetch::client();
+    let mut domains = BTreeSet::new();
+    let mut cursor = None;
+
+    for _ in 0..FEDIDB_MAX_PAGES {
+        let url = "https://api.fedidb.org/v1/servers?limit=40";
+        let request = || {
+            let request = client.get(url).header("user-agent", PROJECT_USER_AGENT);
+            match &cursor {
+                Some(cursor) => request.query(&[("cursor", cursor)]),
+                None => request,
+            }
+        };
+
+        let page: FediDbServerPage = throttle::send(url, request)
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        domains.extend(page.data.into_iter().map(|s| normalize_domain(&s.domain)));
+
+        cursor = page.meta.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+
+    if domains.is_empty() {
+        return Err(eyre!("fedidb returned no servers"));
+    }
+
+    Ok(domains)
+}
+
+fn normalize_domain(domain: &str) -> String {
+    domain.trim().trim_end_matches('.').to_lowercase()
+}
+
+/// Gets every domain known through discovery, for use as seeds or as a cracking dictionary.
+pub fn domain_corpus(db: &mut DatabaseAccess) -> Result<BTreeSet<String>> {
+    let keys = db.iter_keys::<DiscoveredInstances>().collect::<Vec<_>>();
+
+    let mut corpus = BTreeSet::new();
+    for key in keys {
+        if let Some(discovered) = db.get::<DiscoveredInstances>(&key)? {
+            corpus.extend(discovered.domains);
+        }
+    }
+
+    Ok(corpus)
+}
@@ -0,0 +1,309 @@
+use std::borrow::Cow;
+
+use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{InstanceV2, NodeInfo, NodeInfoLinks, RdapDomain},
+    autosave::Autosave,
+    config::Config,
+    database::{DatabaseAccess, DatabaseObject},
+    fetch::{self, BlocklistFetcher, HttpFetcher, PROJECT_USER_AGENT},
+    interrupt, throttle, DomainEntry, MastodonBlockList,
+};
+
+/// Registration data of a resolved domain, to tell throwaway domains from established ones.
+#[derive(Serialize, Deserialize)]
+pub struct RdapInfo {
+    pub domain: String,
+    pub fetched_at: u64,
+    /// The registered domain the data is about, which is a parent of subdomains.
+    pub registered_domain: Option<String>,
+    pub registrar: Option<String>,
+    /// When the domain was registered, as an RFC 3339 date.
+    pub registered_at: Option<String>,
+    pub status: Vec<String>,
+    /// Why the lookup failed, if it did.
+    pub error: Option<String>,
+}
+
+impl RdapInfo {
+    /// When the domain was registered, as a unix timestamp. Only the date is used, which is
+    /// precise enough to tell its age.
+    pub fn registered_timestamp(&self) -> Option<u64> {
+        parse_date(self.registered_at.as_deref()?)
+    }
+}
+
+/// Parses the `YYYY-MM-DD` start of an RFC 3339 date into a unix timestamp.
+fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.get(..10)?.split('-');
+    let year = parts.next()?.parse::<i64>().ok()?;
+    let month = parts.next()?.parse::<i64>().ok()?;
+    let day = parts.next()?.parse::<i64>().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // days since the epoch in the proleptic gregorian calendar, with years starting in march so
+    // the leap day comes last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    u64::try_from(days * 86400).ok()
+}
+
+impl DatabaseObject for RdapInfo {
+    const KEY_NAME: &'static str = "rdap";
+
+    fn get_id(&self) -> Cow<str> {
+        (&self.domain).into()
+    }
+}
+
+/// Whether a resolved domain runs fediverse software, and which.
+#[derive(Serialize, Deserialize)]
+pub struct ProbeResult {
+    pub domain: String,
+    pub probed_at: u64,
+    pub alive: bool,
+    pub last_alive_at: Option<u64>,
+    pub software: Option<String>,
+    pub version: Option<String>,
+    pub users: Option<u64>,
+    pub active_month: Option<u64>,
+    /// Why the probe failed, if it did.
+    pub error: Option<String>,
+}
+
+impl DatabaseObject for ProbeResult {
+    const KEY_NAME: &'static str = "probe";
+
+    fn get_id(&self) -> Cow<str> {
+        (&self.domain).into()
+    }
+}
+
+impl ProbeResult {
+    /// A short description for listings, such as `pleroma, 12 users` or `dead for 90d`.
+    pub fn summary(&self, now: u64) -> String {
+        if !self.alive {
+            return match self.last_alive_at {
+                Some(last_alive_at) => format!(
+                    "dead for {}",
+                    crate::format_age(now.saturating_sub(last_alive_at))
+                ),
+                None => "dead".to_string(),
+            };
+        }
+
+        match (&self.software, self.users) {
+            (Some(software), Some(users)) => format!("{software}, {users} users"),
+            (Some(software), None) => software.clone(),
+            _ => "alive".to_string(),
+        }
+    }
+}
+
+/// Looks up registration data for every resolved domain that doesn't have it yet, or all of
+/// them when `refresh` is set. With `probe`, every resolved domain is also checked for
+/// fediverse software through nodeinfo. With `instances`, the instances whose blocklist was
+/// fetched are probed too, which gives the sizes used to weight their blocks.
+pub async fn enrich(
+    db: &mut DatabaseAccess,
+    config: &Config,
+    refresh: bool,
+    probe: bool,
+    instances: bool,
+) -> Result<()> {
+    let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
+    let mut resolved = vec![];
+    for key in keys {
+        if let Some(domain) = db.get::<DomainEntry>(&key)?.unwrap().known_domain {
+            resolved.push(domain);
+        }
+    }
+
+    let mut domains = vec![];
+    for domain in &resolved {
+        if refresh || db.get::<RdapInfo>(domain)?.is_none() {
+            domains.push(domain.clone());
+        }
+    }
+    println!("Looking up registration data of {} domains", domains.len());
+
+    let client = fetch::client();
+    let mut autosave = Autosave::new(config, crate::DATABASE_FILE);
+    for domain in domains {
+        let info = match lookup(&client, &domain).await {
+            Ok((registered_domain, rdap)) => RdapInfo {
+                domain: domain.clone(),
+                fetched_at: crate::unix_now(),
+                registered_domain: Some(registered_domain),
+                registrar: rdap
+                    .entities
+                    .iter()
+                    .find(|e| e.roles.iter().any(|r| r == "registrar"))
+                    .and_then(|e| e.name())
+                    .map(|n| n.to_string()),
+                registered_at: rdap
+                    .events
+                    .iter()
+                    .find(|e| e.event_action == "registration")
+                    .map(|e| e.event_date.clone()),
+                status: rdap.status,
+                error: None,
+            },
+            Err(e) => {
+                println!("Error while looking up {domain}: {e}");
+                RdapInfo {
+                    domain: domain.clone(),
+                    fetched_at: crate::unix_now(),
+                    registered_domain: None,
+                    registrar: None,
+                    registered_at: None,
+                    status: vec![],
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+
+        db.set(info);
+        autosave.changed(db)?;
+        if interrupt::is_interrupted() {
+            return Ok(());
+        }
+    }
+
+    let mut to_probe = vec![];
+    if probe {
+        to_probe.extend(resolved);
+    }
+    if instances {
+        to_probe.extend(db.iter_keys::<MastodonBlockList>());
+    }
+    to_probe.sort();
+    to_probe.dedup();
+
+    if !to_probe.is_empty() {
+        println!("Probing {} domains", to_probe.len());
+        let fetcher = HttpFetcher::new(config)?;
+        for domain in to_probe {
+            let previous = db.get::<ProbeResult>(&domain)?;
+            let now = crate::unix_now();
+            let mut result = ProbeResult {
+                domain: domain.clone(),
+                probed_at: now,
+                alive: false,
+                last_alive_at: previous.and_then(|p| p.last_alive_at),
+                software: None,
+                version: None,
+                users: None,
+                active_month: None,
+                error: None,
+            };
+
+            match probe_nodeinfo(&fetcher, &domain).await {
+                Ok(nodeinfo) => {
+                    result.alive = true;
+                    result.last_alive_at = Some(now);
+                    result.software = Some(nodeinfo.software.name);
+                    result.version = nodeinfo.software.version;
+                    result.users = nodeinfo.usage.users.total;
+                    result.active_month = nodeinfo.usage.users.active_month;
+
+                    // not every mastodon version reports active users in its nodeinfo
+                    if result.active_month.is_none() {
+                        if let Ok(instance) = fetch_instance(&fetcher, &domain).await {
+                            result.active_month = instance.usage.users.active_month;
+                        }
+                    }
+                }
+                Err(e) => result.error = Some(e.to_string()),
+            }
+
+            db.set(result);
+            autosave.changed(db)?;
+            if interrupt::is_interrupted() {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the nodeinfo of a domain, which tells whether it runs fediverse software.
+pub async fn probe_nodeinfo(fetcher: &impl BlocklistFetcher, domain: &str) -> Result<NodeInfo> {
+    let links = fetcher.fetch_page(domain, ".well-known/nodeinfo").await?;
+    let links: NodeInfoLinks = serde_json::from_slice(&links)?;
+
+    // prefer the newest schema, the documents are compatible for the fields used here
+    let link = links
+        .links
+        .iter()
+        .filter(|l| {
+            l.rel
+                .starts_with("http://nodeinfo.diaspora.software/ns/schema/")
+        })
+        .max_by(|a, b| a.rel.cmp(&b.rel))
+        .ok_or_else(|| eyre!("no nodeinfo link"))?;
+    let path = link
+        .href
+        .strip_prefix(&format!("https://{domain}/"))
+        .ok_or_else(|| eyre!("nodeinfo is hosted elsewhere: {}", link.href))?;
+
+    let nodeinfo = fetcher.fetch_page(domain, path).await?;
+    Ok(serde_json::from_slice(&nodeinfo)?)
+}
+
+async fn fetch_instance(fetcher: &impl BlocklistFetcher, domain: &str) -> Result<InstanceV2> {
+    let instance = fetcher.fetch_page(domain, "api/v2/instance").await?;
+    Ok(serde_json::from_slice(&instance)?)
+}
+
+/// Queries RDAP for the domain, walking up to parent domains since registries only know about
+/// registered domains and not their subdomains.
+async fn lookup(client: &reqwest::Client, domain: &str) -> Result<(String, RdapDomain)> {
+    let mut candidate = domain;
+    loop {
+        // rdap.org redirects to the RDAP server of the registry of the TLD
+        let url = format!("https://rdap.org/domain/{candidate}");
+        let response = throttle::send(&url, || {
+            client.get(&url).header("user-agent", PROJECT_USER_AGENT)
+        })
+        .await?;
+
+        if response.status() != reqwest::StatusCode::NOT_FOUND {
+            let rdap = response.error_for_status()?.json().await?;
+            return Ok((candidate.to_string(), rdap));
+        }
+
+        match candidate.split_once('.') {
+            Some((_, parent)) if parent.contains('.') => candidate = parent,
+            _ => return Err(eyre!("no registration data found")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_registration_dates() {
+        assert_eq!(parse_date("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_date("2000-03-01"), Some(951868800));
+        assert_eq!(
+            parse_date("2024-02-29T12:34:56.000+00:00"),
+            Some(1709164800)
+        );
+        assert_eq!(parse_date("1969-12-31"), None);
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("yesterday"), None);
+    }
+}
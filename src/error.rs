@@ -0,0 +1,54 @@
+use std::fmt::Debug;
+
+use color_eyre::Report;
+
+/// An error that ends the program. The stage it happened in decides the exit code, so scripts
+/// can react to specific failures.
+pub enum CliError {
+    Usage(String),
+    Database(Report),
+    Fetch(Report),
+    Process(Report),
+    Crack(Report),
+    Other(Report),
+    /// Stopped by Ctrl-C, after saving progress.
+    Interrupted,
+}
+
+impl CliError {
+    pub fn usage(report: Report) -> Self {
+        Self::Usage(report.to_string())
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Other(_) => 1,
+            Self::Usage(_) => 2,
+            Self::Database(_) => 3,
+            Self::Fetch(_) => 4,
+            Self::Process(_) => 5,
+            Self::Crack(_) => 6,
+            Self::Interrupted => 130,
+        }
+    }
+}
+
+impl From<Report> for CliError {
+    fn from(report: Report) -> Self {
+        Self::Other(report)
+    }
+}
+
+impl Debug for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Usage(message) => write!(f, "{message}"),
+            Self::Database(report) => write!(f, "Database error: {report:?}"),
+            Self::Fetch(report) => write!(f, "Fetch failed: {report:?}"),
+            Self::Process(report) => write!(f, "Processing failed: {report:?}"),
+            Self::Crack(report) => write!(f, "Cracking failed: {report:?}"),
+            Self::Other(report) => write!(f, "{report:?}"),
+            Self::Interrupted => write!(f, "Interrupted"),
+        }
+    }
+}
@@ -0,0 +1,744 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::Limitation,
+    database::{DatabaseAccess, DatabaseObject},
+    fetch::InstanceStatus,
+    filter::EntryFilter,
+    journal::{Event, JournalEntry},
+    publish,
+    redact::Redaction,
+    share::{self, ShareBundle, SharedDomain},
+    DomainEntry, MastodonBlockList,
+};
+
+/// Version of the exported dataset format. Bump this whenever a field is removed, renamed or
+/// changes meaning. Adding fields is not a breaking change.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Copy)]
+pub enum ExportFormat {
+    Json,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "json" => Some(Self::Json),
+            "ndjson" => Some(Self::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+// these types are the public schema of the dataset, keep them separate from the database types
+// so internal changes don't break consumers
+
+#[derive(Serialize)]
+struct Dataset {
+    schema_version: u32,
+    generated_at: u64,
+    instances: Vec<ExportedInstance>,
+    domains: Vec<ExportedDomain>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum Record<'a> {
+    Header {
+        schema_version: u32,
+        generated_at: u64,
+    },
+    Instance(&'a ExportedInstance),
+    Domain(&'a ExportedDomain),
+}
+
+/// An instance whose blocklist was fetched.
+#[derive(Serialize)]
+struct ExportedInstance {
+    domain: String,
+    block_count: usize,
+    /// Result of the last fetch, such as `ok` or `unreachable`.
+    status: Option<String>,
+    checked_at: Option<u64>,
+    last_ok_at: Option<u64>,
+    /// Whether the blocked domains are obfuscated: `none`, `partial` or `full`.
+    obfuscation: Option<String>,
+    /// SHA-256 digest of the last fetched response, which only changes when the blocklist does.
+    content_hash: Option<String>,
+}
+
+/// A blocked domain, identified by the SHA-256 digest of its name. Domains are ordered by digest
+/// and their blockers by instance, so exports only differ when the data does.
+#[derive(Serialize)]
+struct ExportedDomain {
+    digest: String,
+    domain: Option<String>,
+    partial_domains: Vec<String>,
+    /// How the domain was resolved, if it wasn't published in plain text.
+    resolution: Option<ExportedResolution>,
+    blocked_by: Vec<ExportedBlock>,
+}
+
+#[derive(Serialize)]
+struct ExportedResolution {
+    source: String,
+    at: u64,
+}
+
+#[derive(Serialize)]
+struct ExportedBlock {
+    instance: String,
+    severity: String,
+    /// The severity as the instance published it, such as pleroma's `reject`.
+    original_severity: Option<String>,
+    /// Restrictions on top of the severity, such as `reject_media`.
+    limitations: Vec<Limitation>,
+    comment: Option<String>,
+}
+
+// the part of a dataset that `import dataset` reads
+
+#[derive(Deserialize)]
+struct ImportedDataset {
+    schema_version: u32,
+    generated_at: u64,
+    domains: Vec<ImportedDomain>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum ImportedRecord {
+    Header {
+        schema_version: u32,
+        generated_at: u64,
+    },
+    Instance {},
+    Domain(ImportedDomain),
+}
+
+#[derive(Deserialize)]
+struct ImportedDomain {
+    digest: String,
+    domain: Option<String>,
+    #[serde(default)]
+    partial_domains: Vec<String>,
+}
+
+/// Writes every instance and domain in the versioned dataset format, to a file or stdout.
+pub fn export_dataset(
+    db: &mut DatabaseAccess,
+    format: ExportFormat,
+    output: Option<&str>,
+    redaction: &Redaction,
+    filter: &EntryFilter,
+) -> Result<()> {
+    let dataset = collect(db, redaction, filter)?;
+
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).context("create export file")?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+
+    match format {
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &dataset)?;
+            writeln!(writer)?;
+        }
+        ExportFormat::Ndjson => {
+            let header = Record::Header {
+                schema_version: dataset.schema_version,
+                generated_at: dataset.generated_at,
+            };
+            let records = std::iter::once(header)
+                .chain(dataset.instances.iter().map(Record::Instance))
+                .chain(dataset.domains.iter().map(Record::Domain));
+            for record in records {
+                serde_json::to_writer(&mut writer, &record)?;
+                writeln!(writer)?;
+            }
+        }
+    }
+
+    writer.flush().context("write export")?;
+    Ok(())
+}
+
+/// Reads the domains of a dataset written by `export dataset`, in either format, so they can be
+/// imported like a share bundle.
+pub fn parse_dataset(content: &[u8]) -> Result<ShareBundle> {
+    let lines = content
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.trim_ascii().is_empty());
+    let is_ndjson = lines
+        .clone()
+        .next()
+        .is_some_and(|line| serde_json::from_slice::<ImportedRecord>(line).is_ok());
+
+    let dataset = if is_ndjson {
+        let mut header = None;
+        let mut domains = vec![];
+        for line in lines {
+            match serde_json::from_slice(line).context("deserialize dataset record")? {
+                ImportedRecord::Header {
+                    schema_version,
+                    generated_at,
+                } => header = Some((schema_version, generated_at)),
+                ImportedRecord::Instance {} => {}
+                ImportedRecord::Domain(domain) => domains.push(domain),
+            }
+        }
+        let (schema_version, generated_at) =
+            header.ok_or_else(|| eyre!("The dataset has no header record"))?;
+        ImportedDataset {
+            schema_version,
+            generated_at,
+            domains,
+        }
+    } else {
+        serde_json::from_slice::<ImportedDataset>(content).context("deserialize dataset")?
+    };
+
+    if dataset.schema_version > SCHEMA_VERSION {
+        return Err(eyre!(
+            "Dataset schema version {} is newer than the supported version {SCHEMA_VERSION}",
+            dataset.schema_version
+        ));
+    }
+
+    Ok(ShareBundle {
+        version: share::SHARE_VERSION,
+        generated_at: dataset.generated_at,
+        domains: dataset
+            .domains
+            .into_iter()
+            .map(|d| SharedDomain {
+                digest: d.digest,
+                partial_domains: d.partial_domains,
+                domain: d.domain,
+            })
+            .collect(),
+    })
+}
+
+/// The tables of `export sqlite`. Blocks reference instances by domain and domains by digest.
+const SQLITE_SCHEMA: &str = "\
+CREATE TABLE metadata (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+CREATE TABLE instances (
+    domain TEXT PRIMARY KEY,
+    block_count INTEGER NOT NULL,
+    status TEXT,
+    checked_at INTEGER,
+    last_ok_at INTEGER,
+    obfuscation TEXT,
+    content_hash TEXT
+);
+CREATE TABLE domains (
+    digest TEXT PRIMARY KEY,
+    domain TEXT,
+    resolution_source TEXT,
+    resolved_at INTEGER
+);
+CREATE TABLE partial_domains (
+    digest TEXT NOT NULL REFERENCES domains (digest),
+    partial_domain TEXT NOT NULL,
+    PRIMARY KEY (digest, partial_domain)
+);
+CREATE TABLE blocks (
+    instance TEXT NOT NULL REFERENCES instances (domain),
+    digest TEXT NOT NULL REFERENCES domains (digest),
+    severity TEXT NOT NULL,
+    original_severity TEXT,
+    comment TEXT,
+    PRIMARY KEY (instance, digest)
+);
+CREATE TABLE block_limitations (
+    instance TEXT NOT NULL,
+    digest TEXT NOT NULL,
+    limitation TEXT NOT NULL,
+    PRIMARY KEY (instance, digest, limitation),
+    FOREIGN KEY (instance, digest) REFERENCES blocks (instance, digest)
+);
+CREATE INDEX blocks_digest ON blocks (digest);
+";
+
+const SQLITE_TABLES: &[&str] = &[
+    "block_limitations",
+    "blocks",
+    "partial_domains",
+    "domains",
+    "instances",
+    "metadata",
+];
+
+/// Writes the dataset as tables of a SQLite database, replacing the tables of an earlier export
+/// in the same file, with the `sqlite3` command line shell.
+pub fn export_sqlite(
+    db: &mut DatabaseAccess,
+    path: &str,
+    redaction: &Redaction,
+    filter: &EntryFilter,
+) -> Result<()> {
+    let dataset = collect(db, redaction, filter)?;
+    run_script("sqlite3", &["-bail", path], &sql_script(&dataset))
+}
+
+/// Writes the blocks and domains of the dataset as `blocks.parquet` and `domains.parquet` in
+/// `out_dir`, one row per block and per domain. The `duckdb` command line shell loads the
+/// dataset with the same statements as `export sqlite` and writes the files.
+pub fn export_parquet(
+    db: &mut DatabaseAccess,
+    out_dir: &Path,
+    redaction: &Redaction,
+    filter: &EntryFilter,
+) -> Result<()> {
+    let dataset = collect(db, redaction, filter)?;
+    std::fs::create_dir_all(out_dir).context("create output directory")?;
+
+    let mut script = sql_script(&dataset);
+    script += &parquet_statements(out_dir);
+    run_script("duckdb", &["-bail"], &script)
+}
+
+/// Copies the tables made by `sql_script` to parquet files. Limitations and partial domains
+/// become list columns, and blocks get the domain name so they can be analyzed on their own.
+fn parquet_statements(out_dir: &Path) -> String {
+    let path = |name: &str| {
+        let path = out_dir.join(name);
+        sql_text(Some(&path.to_string_lossy()))
+    };
+
+    format!(
+        "\
+COPY (
+    SELECT b.instance, b.digest, d.domain, b.severity, b.original_severity,
+        coalesce((SELECT list(l.limitation ORDER BY l.limitation) FROM block_limitations l
+            WHERE l.instance = b.instance AND l.digest = b.digest), []) AS limitations,
+        b.comment
+    FROM blocks b JOIN domains d USING (digest)
+    ORDER BY b.digest, b.instance
+) TO {} (FORMAT parquet);
+COPY (
+    SELECT d.digest, d.domain,
+        coalesce((SELECT list(p.partial_domain ORDER BY p.partial_domain) FROM partial_domains p
+            WHERE p.digest = d.digest), []) AS partial_domains,
+        d.resolution_source, d.resolved_at,
+        (SELECT count(*) FROM blocks b WHERE b.digest = d.digest) AS blocked_by
+    FROM domains d
+    ORDER BY d.digest
+) TO {} (FORMAT parquet);
+",
+        path("blocks.parquet"),
+        path("domains.parquet"),
+    )
+}
+
+/// Runs a database shell with the script on stdin. There is no SQLite or parquet library in this
+/// tool, so these shells write the files.
+fn run_script(program: &str, args: &[&str], script: &str) -> Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("run {program}, is it installed?"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(script.as_bytes())
+            .with_context(|| format!("write to {program}"))?;
+    }
+
+    let status = child
+        .wait()
+        .with_context(|| format!("wait for {program}"))?;
+    if !status.success() {
+        return Err(eyre!("{program} exited with {status}"));
+    }
+    Ok(())
+}
+
+/// The statements that create the tables and insert the dataset, in a single transaction.
+fn sql_script(dataset: &Dataset) -> String {
+    let mut script = String::from("BEGIN;\n");
+    for table in SQLITE_TABLES {
+        writeln!(script, "DROP TABLE IF EXISTS {table};").unwrap();
+    }
+    script += SQLITE_SCHEMA;
+
+    writeln!(
+        script,
+        "INSERT INTO metadata VALUES ('schema_version', '{}'), ('generated_at', '{}');",
+        dataset.schema_version, dataset.generated_at
+    )
+    .unwrap();
+
+    for instance in &dataset.instances {
+        writeln!(
+            script,
+            "INSERT INTO instances VALUES ({}, {}, {}, {}, {}, {}, {});",
+            sql_text(Some(&instance.domain)),
+            instance.block_count,
+            sql_text(instance.status.as_deref()),
+            sql_integer(instance.checked_at),
+            sql_integer(instance.last_ok_at),
+            sql_text(instance.obfuscation.as_deref()),
+            sql_text(instance.content_hash.as_deref()),
+        )
+        .unwrap();
+    }
+
+    for domain in &dataset.domains {
+        let digest = sql_text(Some(&domain.digest));
+        writeln!(
+            script,
+            "INSERT INTO domains VALUES ({digest}, {}, {}, {});",
+            sql_text(domain.domain.as_deref()),
+            sql_text(domain.resolution.as_ref().map(|r| r.source.as_str())),
+            sql_integer(domain.resolution.as_ref().map(|r| r.at)),
+        )
+        .unwrap();
+
+        for partial in &domain.partial_domains {
+            writeln!(
+                script,
+                "INSERT INTO partial_domains VALUES ({digest}, {});",
+                sql_text(Some(partial))
+            )
+            .unwrap();
+        }
+
+        for block in &domain.blocked_by {
+            let instance = sql_text(Some(&block.instance));
+            writeln!(
+                script,
+                "INSERT INTO blocks VALUES ({instance}, {digest}, {}, {}, {});",
+                sql_text(Some(&block.severity)),
+                sql_text(block.original_severity.as_deref()),
+                sql_text(block.comment.as_deref()),
+            )
+            .unwrap();
+
+            for limitation in &block.limitations {
+                // the same snake_case name as in the json dataset
+                let name = serde_json::to_value(limitation).unwrap();
+                writeln!(
+                    script,
+                    "INSERT INTO block_limitations VALUES ({instance}, {digest}, {});",
+                    sql_text(name.as_str())
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    script += "COMMIT;\n";
+    script
+}
+
+fn sql_text(value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!("'{}'", value.replace('\'', "''")),
+        None => "NULL".to_string(),
+    }
+}
+
+fn sql_integer(value: Option<u64>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "NULL".to_string(),
+    }
+}
+
+fn collect(
+    db: &mut DatabaseAccess,
+    redaction: &Redaction,
+    filter: &EntryFilter,
+) -> Result<Dataset> {
+    let mut blockers = publish::blockers_by_digest(db)?;
+    redaction.apply_to_blockers(&mut blockers);
+
+    let mut resolutions = HashMap::new();
+    let keys = db.iter_keys::<JournalEntry>().collect::<Vec<_>>();
+    for key in keys {
+        let entry = db.get::<JournalEntry>(&key)?.unwrap();
+        if let Event::Resolved { digest, source, .. } = entry.event {
+            resolutions.insert(
+                digest,
+                ExportedResolution {
+                    source,
+                    at: entry.at,
+                },
+            );
+        }
+    }
+
+    let keys = db.iter_keys::<MastodonBlockList>().collect::<Vec<_>>();
+    let mut instances = vec![];
+    for key in keys {
+        let blocklist = db.get::<MastodonBlockList>(&key)?.unwrap();
+        let status = db.get::<InstanceStatus>(&blocklist.domain)?;
+        instances.push(ExportedInstance {
+            block_count: blocklist.list.len(),
+            status: status.as_ref().map(|s| s.status.to_string()),
+            checked_at: status.as_ref().map(|s| s.checked_at),
+            last_ok_at: status.and_then(|s| s.last_ok_at),
+            obfuscation: blocklist
+                .metadata
+                .as_ref()
+                .and_then(|m| m.obfuscation)
+                .map(|o| o.to_string()),
+            content_hash: blocklist.metadata.and_then(|m| m.content_hash),
+            domain: blocklist.domain,
+        });
+    }
+
+    let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
+    let mut domains = vec![];
+    for key in keys {
+        let entry = db.get::<DomainEntry>(&key)?.unwrap();
+        if !filter.matches(&entry) {
+            continue;
+        }
+        let digest = entry.get_id().to_string();
+        domains.push(ExportedDomain {
+            resolution: resolutions.remove(&digest),
+            blocked_by: blockers
+                .remove(&digest)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|b| filter.instance_matches(&entry, &b.instance))
+                .map(|b| ExportedBlock {
+                    instance: b.instance,
+                    severity: b.severity.to_string(),
+                    original_severity: b.original_severity,
+                    limitations: b.limitations.into_iter().collect(),
+                    comment: b.comment,
+                })
+                .collect(),
+            digest,
+            domain: entry.known_domain,
+            partial_domains: entry.partial_domains.into_iter().collect(),
+        });
+    }
+
+    Ok(Dataset {
+        schema_version: SCHEMA_VERSION,
+        generated_at: generated_at(),
+        instances,
+        domains,
+    })
+}
+
+/// The time the dataset was generated. Setting `SOURCE_DATE_EPOCH` overrides it, so exporting the
+/// same data twice gives identical files.
+pub fn generated_at() -> u64 {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(crate::unix_now)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use super::*;
+    use crate::{
+        api::{DomainBlock, DomainBlockSeverity},
+        database::DatabaseInstance,
+    };
+
+    #[test]
+    fn collects_dataset() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        let digest = hex::encode(crate::hash_domain("example.com"));
+        db.set(MastodonBlockList {
+            domain: "a.social".to_string(),
+            list: vec![DomainBlock {
+                domain: "exa*ple.com".to_string(),
+                digest: digest.clone(),
+                severity: DomainBlockSeverity::Suspend,
+                original_severity: None,
+                limitations: Default::default(),
+                comment: Some("spam".to_string()),
+            }],
+            processed: true,
+            metadata: None,
+            removed: Default::default(),
+        });
+        db.set(DomainEntry {
+            digest: crate::hash_domain("example.com"),
+            known_domain: None,
+            partial_domains: BTreeSet::from(["exa*ple.com".to_string()]),
+            blocked_by: Default::default(),
+            annotation: Default::default(),
+        });
+
+        let dataset = collect(&mut db, &Redaction::default(), &EntryFilter::default()).unwrap();
+
+        assert_eq!(dataset.schema_version, SCHEMA_VERSION);
+        assert_eq!(dataset.instances.len(), 1);
+        assert_eq!(dataset.instances[0].block_count, 1);
+        assert_eq!(dataset.instances[0].status, None);
+        assert_eq!(dataset.domains.len(), 1);
+        assert_eq!(dataset.domains[0].digest, digest);
+        assert_eq!(dataset.domains[0].blocked_by[0].severity, "suspend");
+        assert_eq!(dataset.domains[0].blocked_by[0].original_severity, None);
+    }
+
+    #[test]
+    fn leaves_out_comments_in_other_languages() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        let digest = hex::encode(crate::hash_domain("example.com"));
+        let mut blocked_by = BTreeMap::new();
+        for (instance, language) in [("a.social", "en"), ("b.social", "de")] {
+            db.set(MastodonBlockList {
+                domain: instance.to_string(),
+                list: vec![DomainBlock {
+                    domain: "exa*ple.com".to_string(),
+                    digest: digest.clone(),
+                    severity: DomainBlockSeverity::Suspend,
+                    original_severity: None,
+                    limitations: Default::default(),
+                    comment: Some("spam".to_string()),
+                }],
+                processed: true,
+                metadata: None,
+                removed: Default::default(),
+            });
+            blocked_by.insert(
+                instance.to_string(),
+                crate::BlockedBy {
+                    severity: DomainBlockSeverity::Suspend,
+                    original_severity: None,
+                    limitations: Default::default(),
+                    comment: Some("spam".to_string()),
+                    comment_language: Some(language.to_string()),
+                },
+            );
+        }
+        db.set(DomainEntry {
+            digest: crate::hash_domain("example.com"),
+            known_domain: None,
+            partial_domains: BTreeSet::from(["exa*ple.com".to_string()]),
+            blocked_by,
+            annotation: Default::default(),
+        });
+
+        let filter = EntryFilter {
+            comment_languages: vec!["en".to_string()],
+            ..Default::default()
+        };
+        let dataset = collect(&mut db, &Redaction::default(), &filter).unwrap();
+
+        assert_eq!(dataset.domains[0].blocked_by.len(), 1);
+        assert_eq!(dataset.domains[0].blocked_by[0].instance, "a.social");
+    }
+
+    #[test]
+    fn writes_sql_script() {
+        let dataset = Dataset {
+            schema_version: SCHEMA_VERSION,
+            generated_at: 0,
+            instances: vec![],
+            domains: vec![ExportedDomain {
+                digest: "abc".to_string(),
+                domain: None,
+                partial_domains: vec!["exa*ple.com".to_string()],
+                resolution: None,
+                blocked_by: vec![ExportedBlock {
+                    instance: "a.social".to_string(),
+                    severity: "suspend".to_string(),
+                    original_severity: None,
+                    limitations: vec![Limitation::RejectMedia],
+                    comment: Some("don't federate".to_string()),
+                }],
+            }],
+        };
+
+        let script = sql_script(&dataset);
+
+        assert!(script.starts_with("BEGIN;\nDROP TABLE IF EXISTS block_limitations;\n"));
+        assert!(script.contains("INSERT INTO domains VALUES ('abc', NULL, NULL, NULL);\n"));
+        assert!(script.contains(
+            "INSERT INTO blocks VALUES ('a.social', 'abc', 'suspend', NULL, 'don''t federate');\n"
+        ));
+        assert!(script.contains(
+            "INSERT INTO block_limitations VALUES ('a.social', 'abc', 'reject_media');\n"
+        ));
+        assert!(script.ends_with("COMMIT;\n"));
+    }
+
+    #[test]
+    fn writes_parquet_statements() {
+        let out_dir = Path::new("it's out");
+        let statements = parquet_statements(out_dir);
+
+        let target = |name: &str| {
+            let path = out_dir.join(name);
+            let path = sql_text(Some(&path.to_string_lossy()));
+            format!("TO {path} (FORMAT parquet);\n")
+        };
+        assert!(statements.starts_with("COPY (\n"));
+        assert!(statements.contains(&target("blocks.parquet")));
+        assert!(statements.ends_with(&target("domains.parquet")));
+        assert!(statements.contains("'it''s out"));
+    }
+
+    #[test]
+    fn parses_exported_dataset() {
+        let domain = |domain: Option<&str>| ExportedDomain {
+            digest: "abc".to_string(),
+            domain: domain.map(str::to_string),
+            partial_domains: vec!["exa*ple.com".to_string()],
+            resolution: None,
+            blocked_by: vec![],
+        };
+        let dataset = Dataset {
+            schema_version: SCHEMA_VERSION,
+            generated_at: 5,
+            instances: vec![],
+            domains: vec![domain(Some("example.com")), domain(None)],
+        };
+
+        let json = serde_json::to_vec_pretty(&dataset).unwrap();
+        let bundle = parse_dataset(&json).unwrap();
+        assert_eq!(bundle.generated_at, 5);
+        assert_eq!(bundle.domains.len(), 2);
+        assert_eq!(bundle.domains[0].domain.as_deref(), Some("example.com"));
+
+        let mut ndjson = vec![];
+        let header = Record::Header {
+            schema_version: SCHEMA_VERSION,
+            generated_at: 5,
+        };
+        for record in [header, Record::Domain(&dataset.domains[0])] {
+            serde_json::to_writer(&mut ndjson, &record).unwrap();
+            ndjson.push(b'\n');
+        }
+        let bundle = parse_dataset(&ndjson).unwrap();
+        assert_eq!(bundle.domains.len(), 1);
+        assert_eq!(bundle.domains[0].partial_domains, vec!["exa*ple.com"]);
+
+        let newer = Dataset {
+            schema_version: SCHEMA_VERSION + 1,
+            ..dataset
+        };
+        assert!(parse_dataset(&serde_json::to_vec(&newer).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,1419 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    future::Future,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use color_eyre::Result;
+use reqwest::StatusCode;
+use serde::{
+    de::{Deserializer as _, SeqAccess, Visitor},
+    Deserialize, Serialize,
+};
+use serde_json::value::RawValue;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    api::{self, DomainBlock, Limitation},
+    archive,
+    config::Config,
+    database::{DatabaseAccess, DatabaseObject},
+    http_cache::HttpCache,
+    journal::{self, Event},
+    output::{Color, Table},
+    scrape, severity, source, throttle, MastodonBlockList,
+};
+
+const RETRY_BASE_DELAY: u64 = 60 * 60;
+const RETRY_MAX_DELAY: u64 = 7 * 24 * 60 * 60;
+// guards against endpoints that keep linking to a next page
+const MAX_PAGES: usize = 100;
+
+// mstdn.jp requires a user agent or will serve a 404
+pub const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/112.0.0.0 Safari/537.36";
+
+/// Sent to third-party APIs such as fedidb, instances.social and RDAP, which get to know who is
+/// querying them.
+pub const PROJECT_USER_AGENT: &str = concat!(
+    "mastodon-block-enum/",
+    env!("CARGO_PKG_VERSION"),
+    " (+https://github.com/holly-hacker/mastodon-block-enum)"
+);
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .gzip(true)
+        .brotli(true)
+        .http2_adaptive_window(true)
+        .pool_idle_timeout(Duration::from_secs(90))
+        .tcp_keepalive(Duration::from_secs(60))
+        .connect_timeout(Duration::from_secs(30))
+        .build()
+        .expect("build http client")
+});
+
+/// Gets the http client shared by every request of a run, so connections (and http/2 sessions)
+/// to a host are reused across endpoints and pages.
+pub fn client() -> reqwest::Client {
+    // clones share the connection pool
+    CLIENT.clone()
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum FetchStatus {
+    Ok,
+    /// The blocklist is only shown to logged in users.
+    RequiresAuth,
+    /// The instance does not publish its blocklist.
+    Disabled,
+    /// The instance no longer exists.
+    Gone,
+    /// The response could not be parsed as a blocklist.
+    ParseError,
+    /// The instance could not be reached or had a server error.
+    Unreachable,
+    /// Cloudflare answered with a bot challenge instead of the instance.
+    Cloudflare,
+    /// The instance answered with an html page, such as an error or maintenance page.
+    HtmlError,
+    /// The response is neither json nor html.
+    WrongContentType,
+}
+
+impl FetchStatus {
+    fn from_http_status(status: StatusCode) -> Self {
+        match status.as_u16() {
+            401 | 403 => Self::RequiresAuth,
+            404 => Self::Disabled,
+            410 => Self::Gone,
+            _ => Self::Unreachable,
+        }
+    }
+
+    /// Whether fetching again later may give a different result.
+    pub fn is_transient(self) -> bool {
+        matches!(self, Self::Unreachable | Self::Cloudflare | Self::HtmlError)
+    }
+}
+
+impl Display for FetchStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Ok => "ok",
+            Self::RequiresAuth => "requires-auth",
+            Self::Disabled => "disabled",
+            Self::Gone => "gone",
+            Self::ParseError => "parse-error",
+            Self::Unreachable => "unreachable",
+            Self::Cloudflare => "cloudflare",
+            Self::HtmlError => "html-error",
+            Self::WrongContentType => "wrong-content-type",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The result of the last attempt to fetch the blocklist of an instance.
+#[derive(Serialize, Deserialize)]
+pub struct InstanceStatus {
+    pub domain: String,
+    pub status: FetchStatus,
+    pub message: Option<String>,
+    pub checked_at: u64,
+    pub last_ok_at: Option<u64>,
+    #[serde(default)]
+    pub consecutive_failures: u32,
+    /// When a transient failure should be retried, with exponential backoff.
+    #[serde(default)]
+    pub retry_at: Option<u64>,
+}
+
+impl DatabaseObject for InstanceStatus {
+    const KEY_NAME: &'static str = "instance_status";
+
+    fn get_id(&self) -> Cow<str> {
+        (&self.domain).into()
+    }
+}
+
+/// Why fetching from an instance failed. `status` tells callers what kind of failure it was,
+/// such as [`FetchStatus::RequiresAuth`] or [`FetchStatus::ParseError`].
+#[derive(Debug)]
+pub struct FetchError {
+    pub status: FetchStatus,
+    pub message: String,
+}
+
+/// A successful response, with the http details worth keeping.
+pub struct FetchedBody {
+    pub body: Vec<u8>,
+    pub http_status: u16,
+    pub etag: Option<String>,
+}
+
+/// Details about how a stored blocklist was fetched.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FetchMetadata {
+    pub fetched_at: u64,
+    /// Unknown for blocklists replayed from the archive.
+    pub http_status: Option<u16>,
+    pub etag: Option<String>,
+    pub duration_ms: Option<u64>,
+    pub item_count: usize,
+    /// How many malformed items were left out.
+    pub skipped_count: usize,
+    /// How many items were left out for each [`SkipReason`].
+    #[serde(default)]
+    pub skip_reasons: BTreeMap<SkipReason, usize>,
+    /// How many of the blocked domains are obfuscated. Missing for lists fetched by older
+    /// versions.
+    #[serde(default)]
+    pub obfuscation: Option<Obfuscation>,
+    /// Name of the [`source::BlockSource`] the blocklist came from.
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Hex SHA-256 digest of the response body. Missing for lists fetched by older versions.
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    /// The [`severity::fingerprint`] of the mapping the list was parsed with. Missing for lists
+    /// fetched by older versions.
+    #[serde(default)]
+    pub severity_mapping: Option<String>,
+}
+
+/// The digest stored as [`FetchMetadata::content_hash`].
+pub fn content_hash(body: &[u8]) -> String {
+    hex::encode(Sha256::digest(body))
+}
+
+impl FetchError {
+    pub fn new(status: FetchStatus, message: impl Display) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.status, self.message)
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+/// The transport used to get data from instances, so the fetch pipeline can run against canned
+/// responses or something other than plain http.
+pub trait BlocklistFetcher {
+    /// Gets the raw response of a blocklist on an instance, following pagination.
+    fn fetch_blocklist(
+        &self,
+        domain: &str,
+        path: &str,
+    ) -> impl Future<Output = Result<FetchedBody, FetchError>>;
+
+    /// Gets the raw content of a page on an instance, such as `about`.
+    fn fetch_page(
+        &self,
+        domain: &str,
+        path: &str,
+    ) -> impl Future<Output = Result<Vec<u8>, FetchError>>;
+}
+
+pub struct HttpFetcher {
+    client: reqwest::Client,
+    cache: Option<HttpCache>,
+    /// Bearer tokens by lowercase host.
+    tokens: HashMap<String, String>,
+    /// The most bytes a blocklist may take over all of its pages. Responses are read into
+    /// memory in full before they are parsed, so this bounds what a single instance can take.
+    max_response_size: usize,
+}
+
+impl HttpFetcher {
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            client: client(),
+            cache: config
+                .cache_dir
+                .as_deref()
+                .map(HttpCache::new)
+                .transpose()?,
+            tokens: config
+                .instance_tokens
+                .iter()
+                .map(|(domain, token)| (domain.to_lowercase(), token.clone()))
+                .collect(),
+            max_response_size: config.max_response_mb.saturating_mul(1024 * 1024),
+        })
+    }
+
+    fn token_for(&self, url: &str) -> Option<&str> {
+        let url = reqwest::Url::parse(url).ok()?;
+        let host = url.host_str()?.to_lowercase();
+        self.tokens.get(&host).map(|t| t.as_str())
+    }
+
+    /// Gets a single response, along with its `Link` header. With `json`, responses that can't
+    /// be json are rejected with a status that says what they are instead, such as the html
+    /// error page of a frontend. Pages such as the about page are html and are taken as is.
+    async fn get(
+        &self,
+        url: &str,
+        json: bool,
+    ) -> Result<(FetchedBody, Option<String>), FetchError> {
+        let cached = self.cache.as_ref().and_then(|c| c.lookup(url));
+        if let Some((metadata, body)) = &cached {
+            if metadata.is_fresh(crate::unix_now()) {
+                let fetched = FetchedBody {
+                    body: body.clone(),
+                    http_status: metadata.http_status,
+                    etag: metadata.etag.clone(),
+                };
+                return Ok((fetched, metadata.link.clone()));
+            }
+        }
+
+        let mut response = throttle::send(url, || {
+            let mut request = self.client.get(url).header("user-agent", USER_AGENT);
+            if let Some(etag) = cached.as_ref().and_then(|(m, _)| m.etag.as_ref()) {
+                request = request.header("if-none-match", etag);
+            }
+            if let Some(token) = self.token_for(url) {
+                request = request.bearer_auth(token);
+            }
+            request
+        })
+        .await
+        .map_err(|e| FetchError::new(FetchStatus::Unreachable, e))?;
+
+        let status = response.status();
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|h| h.to_str().ok())
+                .map(|h| h.to_string())
+        };
+        let cache_control = header("cache-control");
+        let etag = header("etag");
+        let link = header("link");
+        let content_type = header("content-type");
+
+        if header("cf-mitigated").as_deref() == Some("challenge") {
+            return Err(FetchError::new(
+                FetchStatus::Cloudflare,
+                format!("HTTP {status}, challenge"),
+            ));
+        }
+
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some((metadata, body)) = cached {
+                let fetched = FetchedBody {
+                    body,
+                    http_status: metadata.http_status,
+                    etag: metadata.etag,
+                };
+                self.store_cached(url, cache_control.as_deref(), &fetched, &metadata.link);
+                return Ok((fetched, metadata.link));
+            }
+        }
+
+        if !status.is_success() {
+            return Err(FetchError::new(
+                FetchStatus::from_http_status(status),
+                format!("HTTP {status}"),
+            ));
+        }
+
+        // the body is buffered in full and only parsed once it is complete: pages get
+        // concatenated, and the cache, the archive and the content hash need the raw response.
+        // Nothing is parsed while it downloads, so the size cap is what bounds the memory an
+        // instance can take
+        let mut body = vec![];
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| FetchError::new(FetchStatus::Unreachable, e))?
+        {
+            if body.len() + chunk.len() > self.max_response_size {
+                return Err(FetchError::new(
+                    FetchStatus::ParseError,
+                    format!("response larger than {} bytes", self.max_response_size),
+                ));
+            }
+
+            body.extend_from_slice(&chunk);
+        }
+
+        if let Some(error) = json
+            .then(|| classify_non_json(content_type.as_deref(), &body))
+            .flatten()
+        {
+            return Err(error);
+        }
+
+        let fetched = FetchedBody {
+            body,
+            http_status: status.as_u16(),
+            etag,
+        };
+        self.store_cached(url, cache_control.as_deref(), &fetched, &link);
+        Ok((fetched, link))
+    }
+
+    /// Gets a json array from a paginated endpoint, following `Link: rel="next"` headers until
+    /// the last page. The status and ETag of the first page are kept.
+    async fn get_all_pages(&self, url: &str) -> Result<FetchedBody, FetchError> {
+        let (mut first, mut link) = self.get(url, true).await?;
+
+        let mut pages = vec![];
+        let mut size = first.body.len();
+        while let Some(next) = link.as_deref().and_then(api::next_page) {
+            if pages.len() + 1 >= MAX_PAGES {
+                return Err(FetchError::new(
+                    FetchStatus::ParseError,
+                    format!("more than {MAX_PAGES} pages"),
+                ));
+            }
+
+            let (page, next_link) = self.get(next, true).await?;
+            size += page.body.len();
+            if size > self.max_response_size {
+                return Err(FetchError::new(
+                    FetchStatus::ParseError,
+                    format!("response larger than {} bytes", self.max_response_size),
+                ));
+            }
+
+            pages.push(page.body);
+            link = next_link;
+        }
+
+        if !pages.is_empty() {
+            pages.insert(0, first.body);
+            first.body = api::concat_pages(&pages)
+                .map_err(|e| FetchError::new(FetchStatus::ParseError, e))?;
+        }
+
+        Ok(first)
+    }
+
+    fn store_cached(
+        &self,
+        url: &str,
+        cache_control: Option<&str>,
+        fetched: &FetchedBody,
+        link: &Option<String>,
+    ) {
+        if let Some(cache) = &self.cache {
+            let stored = cache.store(
+                url,
+                cache_control,
+                fetched.etag.as_deref(),
+                link.as_deref(),
+                fetched.http_status,
+                &fetched.body,
+            );
+            if let Err(e) = stored {
+                println!("Error while trying to cache response from {url}: {e}");
+            }
+        }
+    }
+}
+
+impl BlocklistFetcher for HttpFetcher {
+    async fn fetch_blocklist(&self, domain: &str, path: &str) -> Result<FetchedBody, FetchError> {
+        self.get_all_pages(&format!("https://{domain}/{path}"))
+            .await
+    }
+
+    async fn fetch_page(&self, domain: &str, path: &str) -> Result<Vec<u8>, FetchError> {
+        let url = format!("https://{domain}/{path}");
+        Ok(self.get(&url, false).await?.0.body)
+    }
+}
+
+pub enum FetchOutcome {
+    Updated,
+    Unchanged,
+    Failed(FetchError),
+}
+
+/// Collects the outcome of every instance fetched in a run.
+#[derive(Default)]
+pub struct FetchSummary {
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub failed: Vec<(String, FetchError)>,
+}
+
+impl FetchSummary {
+    pub fn add(&mut self, domain: &str, outcome: FetchOutcome) {
+        match outcome {
+            FetchOutcome::Updated => self.updated.push(domain.to_string()),
+            FetchOutcome::Unchanged => self.unchanged.push(domain.to_string()),
+            FetchOutcome::Failed(e) => self.failed.push((domain.to_string(), e)),
+        }
+    }
+
+    pub fn succeeded(&self) -> usize {
+        self.updated.len() + self.unchanged.len()
+    }
+
+    pub fn print(&self) {
+        println!("Fetch summary:");
+        println!("- updated: {}", self.updated.len());
+        println!("- unchanged: {}", self.unchanged.len());
+        println!("- failed: {}", self.failed.len());
+        for (domain, e) in &self.failed {
+            println!("  - {domain}: {e}");
+        }
+    }
+}
+
+pub async fn try_load_blocklist(
+    db: &mut DatabaseAccess,
+    fetcher: &impl BlocklistFetcher,
+    config: &Config,
+    domain: &str,
+) -> Result<FetchOutcome> {
+    let fetched_at = crate::unix_now();
+    let started = Instant::now();
+
+    // the first source that works wins, if none does the error of the first one is reported
+    let mut result = None;
+    for source in source::SOURCES {
+        let attempt = fetcher
+            .fetch_blocklist(domain, source.path())
+            .await
+            .and_then(|fetched| {
+                if let Some(archive_dir) = &config.archive_dir {
+                    if let Err(e) = archive::store(archive_dir, domain, fetched_at, &fetched.body) {
+                        println!("Error while trying to archive response from {domain}: {e}");
+                    }
+                }
+
+                source
+                    .parse(&fetched.body)
+                    .map(|parsed| (*source, fetched, parsed))
+                    .map_err(|e| FetchError::new(FetchStatus::ParseError, e))
+            });
+
+        match attempt {
+            Ok(_) => {
+                result = Some(attempt);
+                break;
+            }
+            Err(_) if result.is_none() => result = Some(attempt),
+            Err(_) => {}
+        }
+    }
+    let result = result.expect("there is at least one block source");
+    let duration = started.elapsed();
+
+    let (outcome, needs_scrape) = match result {
+        Ok((source, fetched, ParsedBlocklist { list, skipped })) => {
+            println!("Loaded {} blocklist items from {domain}", list.len());
+            if !skipped.is_empty() {
+                println!("Skipped {} malformed blocklist items from {domain}", skipped.len());
+            }
+            set_status(db, domain, FetchStatus::Ok, None)?;
+
+            let previous = db.get::<MastodonBlockList>(domain)?;
+            let hash = content_hash(&fetched.body);
+            let mapping = severity::fingerprint();
+            // the same response parsed with the same mapping gives the same list, only when
+            // either differs do the lists have to be compared
+            let unchanged = previous.as_ref().is_some_and(|p| {
+                let same_response = p.metadata.as_ref().is_some_and(|m| {
+                    m.content_hash.as_ref() == Some(&hash)
+                        && m.severity_mapping.as_ref() == Some(&mapping)
+                });
+                same_response || p.list == list
+            });
+            let obfuscation = Obfuscation::of(&list);
+            if !unchanged {
+                record_changes(db, domain, previous.as_ref(), &list)?;
+            }
+            let metadata = FetchMetadata {
+                fetched_at,
+                http_status: Some(fetched.http_status),
+                etag: fetched.etag,
+                duration_ms: Some(duration.as_millis() as u64),
+                item_count: list.len(),
+                skipped_count: skipped.len(),
+                skip_reasons: count_reasons(&skipped),
+                obfuscation: Some(obfuscation),
+                source: Some(source.name().to_string()),
+                content_hash: Some(hash),
+                severity_mapping: Some(mapping),
+            };
+            let removed = removed_digests(previous.as_ref(), &list);
+            db.set(MastodonBlockList {
+                domain: domain.to_string(),
+                list,
+                processed: unchanged && previous.is_some_and(|p| p.processed),
+                metadata: Some(metadata),
+                removed,
+            });
+
+            let outcome = if unchanged {
+                FetchOutcome::Unchanged
+            } else {
+                FetchOutcome::Updated
+            };
+            (outcome, obfuscation != Obfuscation::None)
+        }
+        Err(e) => {
+            println!("Error while trying to load blocklist from {domain}: {e}");
+            set_status(db, domain, e.status, Some(e.message.clone()))?;
+            (FetchOutcome::Failed(e), true)
+        }
+    };
+
+    // the about page may show the full domains when the api doesn't
+    if needs_scrape {
+        scrape::scrape_about_page(db, fetcher, domain).await;
+    }
+
+    Ok(outcome)
+}
+
+fn record_changes(
+    db: &mut DatabaseAccess,
+    domain: &str,
+    previous: Option<&MastodonBlockList>,
+    list: &[DomainBlock],
+) -> Result<()> {
+    let Some(previous) = previous else {
+        return journal::record(
+            db,
+            Event::InstanceAdded {
+                instance: domain.to_string(),
+                blocks: list.len(),
+            },
+        );
+    };
+
+    let previous_digests = previous
+        .list
+        .iter()
+        .map(|b| b.digest.as_str())
+        .collect::<HashSet<_>>();
+    let digests = list
+        .iter()
+        .map(|b| b.digest.as_str())
+        .collect::<HashSet<_>>();
+
+    for block in list {
+        if !previous_digests.contains(block.digest.as_str()) {
+            journal::record(
+                db,
+                Event::NewBlock {
+                    instance: domain.to_string(),
+                    digest: block.digest.clone(),
+                    domain: block.domain.clone(),
+                    severity: block.severity,
+                },
+            )?;
+        }
+    }
+
+    for block in &previous.list {
+        if !digests.contains(block.digest.as_str()) {
+            journal::record(
+                db,
+                Event::Unblocked {
+                    instance: domain.to_string(),
+                    digest: block.digest.clone(),
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Gets the digests that `previous` blocked and `list` doesn't, along with those of earlier
+/// fetches that weren't processed yet.
+fn removed_digests(previous: Option<&MastodonBlockList>, list: &[DomainBlock]) -> BTreeSet<String> {
+    let Some(previous) = previous else {
+        return BTreeSet::new();
+    };
+
+    let digests = list
+        .iter()
+        .map(|b| b.digest.to_lowercase())
+        .collect::<HashSet<_>>();
+    let pending = (!previous.processed).then_some(&previous.removed);
+    pending
+        .into_iter()
+        .flatten()
+        .cloned()
+        .chain(previous.list.iter().map(|b| b.digest.to_lowercase()))
+        .filter(|digest| !digests.contains(digest))
+        .collect()
+}
+
+#[derive(Default)]
+pub struct ParsedBlocklist {
+    pub list: Vec<DomainBlock>,
+    /// Why each malformed item was skipped.
+    pub skipped: Vec<SkippedItem>,
+}
+
+/// A malformed item left out of a blocklist.
+#[derive(Debug)]
+pub struct SkippedItem {
+    pub reason: SkipReason,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipReason {
+    /// The item isn't a domain block object.
+    Malformed,
+    /// The domain is obfuscated and there is no digest to resolve it with.
+    MissingDigest,
+    /// The digest isn't a hex-encoded SHA-256 digest.
+    InvalidDigest,
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Malformed => "malformed",
+            Self::MissingDigest => "missing digest",
+            Self::InvalidDigest => "invalid digest",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Whether an instance publishes the full domains it blocks. Full lists resolve digests by
+/// themselves, obfuscated ones need cracking.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Obfuscation {
+    /// No domain is obfuscated.
+    None,
+    /// Some domains are obfuscated.
+    Partial,
+    /// Every domain is obfuscated.
+    Full,
+}
+
+impl Obfuscation {
+    pub fn of(list: &[DomainBlock]) -> Self {
+        let obfuscated = list.iter().filter(|b| b.domain.contains('*')).count();
+        if obfuscated == 0 {
+            Self::None
+        } else if obfuscated < list.len() {
+            Self::Partial
+        } else {
+            Self::Full
+        }
+    }
+}
+
+impl Display for Obfuscation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::None => "none",
+            Self::Partial => "partial",
+            Self::Full => "full",
+        };
+        f.write_str(name)
+    }
+}
+
+fn count_reasons(skipped: &[SkippedItem]) -> BTreeMap<SkipReason, usize> {
+    let mut counts = BTreeMap::new();
+    for item in skipped {
+        *counts.entry(item.reason).or_default() += 1;
+    }
+    counts
+}
+
+/// Parses a blocklist response item by item, so a few malformed items don't lose the whole list.
+/// Items are deserialized one at a time from the buffered body rather than through a json tree
+/// of the whole list, but the body itself is complete in memory.
+pub fn parse_blocklist(body: &[u8]) -> Result<ParsedBlocklist> {
+    let mut deserializer = serde_json::Deserializer::from_slice(body);
+    let parsed = (&mut deserializer).deserialize_seq(BlocklistVisitor)?;
+    deserializer.end()?;
+
+    Ok(parsed)
+}
+
+struct BlocklistVisitor;
+
+impl<'de> Visitor<'de> for BlocklistVisitor {
+    type Value = ParsedBlocklist;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an array of domain blocks")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut parsed = ParsedBlocklist::default();
+        while let Some(item) = seq.next_element::<&RawValue>()? {
+            match parse_block(item.get()) {
+                Ok(block) => parsed.list.push(block),
+                Err(reason) => parsed.skipped.push(reason),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// The level of a block as the instance published it. The limitation flags are only set by the
+/// admin api, whose output can be read by `audit`.
+#[derive(Deserialize)]
+struct PublishedLevel {
+    #[serde(default)]
+    severity: Option<String>,
+    #[serde(default)]
+    reject_media: bool,
+    #[serde(default)]
+    reject_reports: bool,
+}
+
+fn parse_block(json: &str) -> Result<DomainBlock, SkippedItem> {
+    let skip = |reason, message| SkippedItem { reason, message };
+    let mut block = serde_json::from_str::<DomainBlock>(json)
+        .map_err(|e| skip(SkipReason::Malformed, e.to_string()))?;
+
+    // other software publishes its own terms, which the enum alone would all turn into unknown
+    if let Ok(level) = serde_json::from_str::<PublishedLevel>(json) {
+        if let Some(raw) = level.severity {
+            block.severity = severity::normalize(&raw);
+            block.original_severity = severity::original(&raw, block.severity);
+            block.limitations.extend(severity::limitation(&raw));
+        }
+        if level.reject_media {
+            block.limitations.insert(Limitation::RejectMedia);
+        }
+        if level.reject_reports {
+            block.limitations.insert(Limitation::RejectReports);
+        }
+    }
+
+    if block.digest.is_empty() {
+        if block.domain.contains('*') {
+            return Err(skip(
+                SkipReason::MissingDigest,
+                format!("missing digest for {}", block.domain),
+            ));
+        }
+
+        block.digest = hex::encode(crate::hash_domain(&block.domain));
+    }
+
+    block.digest = block.digest.to_lowercase();
+    if block.digest.len() != 64 || !block.digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(skip(
+            SkipReason::InvalidDigest,
+            format!("invalid digest for {}", block.domain),
+        ));
+    }
+
+    Ok(block)
+}
+
+/// Parses the latest archived response of each instance again and stores the result.
+pub fn replay(db: &mut DatabaseAccess, archive_dir: &str, domains: Vec<String>) -> Result<()> {
+    let domains = if domains.is_empty() {
+        archive::domains(archive_dir)?
+    } else {
+        domains
+    };
+
+    for domain in domains {
+        let Some(timestamp) = archive::timestamps(archive_dir, &domain)?.pop() else {
+            println!("No archived responses for {domain}");
+            continue;
+        };
+
+        let body = archive::load(archive_dir, &domain, timestamp)?;
+        let source = source::stored_source(db, &domain)?;
+        match source.parse(&body) {
+            Ok(ParsedBlocklist { list, skipped }) => {
+                println!(
+                    "Replayed {} blocklist items from {domain}, skipped {}",
+                    list.len(),
+                    skipped.len()
+                );
+                let metadata = FetchMetadata {
+                    fetched_at: timestamp,
+                    http_status: None,
+                    etag: None,
+                    duration_ms: None,
+                    item_count: list.len(),
+                    skipped_count: skipped.len(),
+                    skip_reasons: count_reasons(&skipped),
+                    obfuscation: Some(Obfuscation::of(&list)),
+                    source: Some(source.name().to_string()),
+                    content_hash: Some(content_hash(&body)),
+                    severity_mapping: Some(severity::fingerprint()),
+                };
+                let previous = db.get::<MastodonBlockList>(&domain)?;
+                db.set(MastodonBlockList {
+                    removed: removed_digests(previous.as_ref(), &list),
+                    domain,
+                    list,
+                    processed: false,
+                    metadata: Some(metadata),
+                });
+            }
+            Err(e) => println!("Error while trying to parse archived response from {domain}: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
+fn set_status(
+    db: &mut DatabaseAccess,
+    domain: &str,
+    status: FetchStatus,
+    message: Option<String>,
+) -> Result<()> {
+    let now = crate::unix_now();
+    let previous = db.get::<InstanceStatus>(domain)?;
+
+    let (last_ok_at, consecutive_failures) = match status {
+        FetchStatus::Ok => (Some(now), 0),
+        _ => (
+            previous.as_ref().and_then(|s| s.last_ok_at),
+            previous.as_ref().map_or(0, |s| s.consecutive_failures) + 1,
+        ),
+    };
+
+    let retry_at = status.is_transient().then(|| {
+        let delay = RETRY_BASE_DELAY.saturating_mul(1 << (consecutive_failures - 1).min(16));
+        now + delay.min(RETRY_MAX_DELAY)
+    });
+
+    db.set(InstanceStatus {
+        domain: domain.to_string(),
+        status,
+        message,
+        checked_at: now,
+        last_ok_at,
+        consecutive_failures,
+        retry_at,
+    });
+
+    Ok(())
+}
+
+/// Gets the instances whose stored blocklist was fetched at or after `timestamp`.
+pub fn fetched_since(db: &mut DatabaseAccess, timestamp: u64) -> Result<HashSet<String>> {
+    let keys = db.iter_keys::<MastodonBlockList>().collect::<Vec<_>>();
+
+    let mut recent = HashSet::new();
+    for key in keys {
+        let blocklist = db.get::<MastodonBlockList>(&key)?.unwrap();
+        if blocklist
+            .metadata
+            .is_some_and(|m| m.fetched_at >= timestamp)
+        {
+            recent.insert(blocklist.domain);
+        }
+    }
+
+    Ok(recent)
+}
+
+/// Recognizes responses that can't be json, such as error pages served with status 200, so they
+/// get a more useful status than a parse error. The message contains the start of the body.
+fn classify_non_json(content_type: Option<&str>, body: &[u8]) -> Option<FetchError> {
+    let start = body.iter().find(|b| !b.is_ascii_whitespace());
+    if matches!(start, Some(b'[' | b'{')) {
+        return None;
+    }
+
+    let text = String::from_utf8_lossy(&body[..body.len().min(4096)]);
+    let snippet = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let snippet = snippet.chars().take(120).collect::<String>();
+
+    let content_type = content_type.unwrap_or("unknown content type");
+    let is_html = content_type.contains("html") || start == Some(&b'<');
+    let status =
+        if is_html && (text.contains("challenge-platform") || text.contains("Just a moment")) {
+            FetchStatus::Cloudflare
+        } else if is_html {
+            FetchStatus::HtmlError
+        } else if !content_type.contains("json") {
+            FetchStatus::WrongContentType
+        } else {
+            return None;
+        };
+
+    Some(FetchError::new(
+        status,
+        format!("{content_type}: {snippet}"),
+    ))
+}
+
+/// Gets the instances that failed with a transient error and are due for a retry.
+pub fn due_retries(db: &mut DatabaseAccess) -> Result<Vec<String>> {
+    let keys = db.iter_keys::<InstanceStatus>().collect::<Vec<_>>();
+    let now = crate::unix_now();
+
+    let mut due = vec![];
+    for key in keys {
+        let status = db.get::<InstanceStatus>(&key)?.unwrap();
+        if status.retry_at.is_some_and(|retry_at| retry_at <= now) {
+            due.push(status.domain);
+        }
+    }
+
+    Ok(due)
+}
+
+pub fn health(db: &mut DatabaseAccess) -> Result<()> {
+    let keys = db.iter_keys::<InstanceStatus>().collect::<Vec<_>>();
+    let mut statuses = keys
+        .into_iter()
+        .map(|k| db.get::<InstanceStatus>(&k).map(Option::unwrap))
+        .collect::<Result<Vec<_>>>()?;
+    statuses.sort_by(|a, b| a.status.cmp(&b.status).then(a.domain.cmp(&b.domain)));
+
+    let mut counts = BTreeMap::<FetchStatus, usize>::new();
+    for status in &statuses {
+        *counts.entry(status.status).or_default() += 1;
+    }
+
+    println!("Fetch status of {} instances:", statuses.len());
+    for (status, count) in &counts {
+        println!("- {status}: {count}");
+    }
+    let frontier = crate::crawl::frontier_counts(db)?;
+    if !frontier.is_empty() {
+        let counts = frontier
+            .iter()
+            .map(|(state, count)| format!("{count} {state}"))
+            .collect::<Vec<_>>();
+        println!("Crawl frontier: {}", counts.join(", "));
+    }
+    println!();
+
+    let now = crate::unix_now();
+    let mut table = Table::new(&["Domain", "Status", "Checked", "Details"]);
+    for status in &statuses {
+        let mut details = vec![];
+        if status.status == FetchStatus::Ok {
+            if let Some(blocklist) = db.get::<MastodonBlockList>(&status.domain)? {
+                details.push(format!("{} blocks", blocklist.list.len()));
+                if let Some(metadata) = &blocklist.metadata {
+                    if let Some(obfuscation) = metadata.obfuscation {
+                        details.push(format!("obfuscation {obfuscation}"));
+                    }
+                    if metadata.skipped_count > 0 {
+                        let reasons = metadata
+                            .skip_reasons
+                            .iter()
+                            .map(|(reason, count)| format!("{count} {reason}"))
+                            .collect::<Vec<_>>();
+                        // blocklists fetched by older versions only have the count
+                        let reasons = if reasons.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" ({})", reasons.join(", "))
+                        };
+                        details.push(format!(
+                            "{} records skipped{reasons}",
+                            metadata.skipped_count
+                        ));
+                    }
+                    if let Some(duration_ms) = metadata.duration_ms {
+                        details.push(format!("took {duration_ms}ms"));
+                    }
+                }
+            }
+        } else {
+            if let Some(retry_at) = status.retry_at {
+                match retry_at.checked_sub(now) {
+                    Some(wait) if wait > 0 => {
+                        details.push(format!("retry in {}", crate::format_age(wait)))
+                    }
+                    _ => details.push("retry due".to_string()),
+                }
+            }
+
+            match status.last_ok_at {
+                Some(last_ok_at) => details.push(format!(
+                    "last ok {} ago",
+                    crate::format_age(now.saturating_sub(last_ok_at))
+                )),
+                None => details.push("never ok".to_string()),
+            }
+        }
+
+        let mut details = details.join(", ");
+        if let Some(message) = &status.message {
+            details += &format!(": {message}");
+        }
+
+        let color = match status.status {
+            FetchStatus::Ok => Color::Green,
+            status if status.is_transient() => Color::Yellow,
+            _ => Color::Red,
+        };
+        table.add_row(vec![
+            (status.domain.clone(), None),
+            (status.status.to_string(), Some(color)),
+            (
+                format!(
+                    "{} ago",
+                    crate::format_age(now.saturating_sub(status.checked_at))
+                ),
+                Some(Color::Dim),
+            ),
+            (details, None),
+        ]);
+    }
+    table.print();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{api::DomainBlockSeverity, database::DatabaseInstance};
+
+    struct CannedFetcher(HashMap<&'static str, Result<&'static str, FetchStatus>>);
+
+    impl BlocklistFetcher for CannedFetcher {
+        async fn fetch_blocklist(
+            &self,
+            domain: &str,
+            _path: &str,
+        ) -> Result<FetchedBody, FetchError> {
+            match self.0.get(domain) {
+                Some(Ok(body)) => Ok(FetchedBody {
+                    body: body.as_bytes().to_vec(),
+                    http_status: 200,
+                    etag: None,
+                }),
+                Some(Err(status)) => Err(FetchError::new(*status, "canned error")),
+                None => Err(FetchError::new(FetchStatus::Unreachable, "unknown domain")),
+            }
+        }
+
+        async fn fetch_page(&self, _domain: &str, _path: &str) -> Result<Vec<u8>, FetchError> {
+            Err(FetchError::new(FetchStatus::Disabled, "no pages"))
+        }
+    }
+
+    fn load(fetcher: &CannedFetcher, db: &mut DatabaseAccess, domain: &str) -> FetchOutcome {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(try_load_blocklist(db, fetcher, &Config::default(), domain))
+            .unwrap()
+    }
+
+    fn fetcher() -> CannedFetcher {
+        CannedFetcher(HashMap::from([
+            (
+                "good.social",
+                Ok(r#"[{"domain":"exa*ple.com","digest":"a379a6f6eeafb9a55e378c118034e2751e682fab9f2d30ab13d2125586ce1947","severity":"suspend","comment":"spam"}]"#),
+            ),
+            ("broken.social", Ok("<html>oops</html>")),
+            ("private.social", Err(FetchStatus::RequiresAuth)),
+        ]))
+    }
+
+    #[test]
+    fn classifies_obfuscation() {
+        let list = |domains: &[&str]| {
+            domains
+                .iter()
+                .map(|d| DomainBlock {
+                    domain: d.to_string(),
+                    digest: String::new(),
+                    severity: DomainBlockSeverity::Suspend,
+                    original_severity: None,
+                    limitations: Default::default(),
+                    comment: None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(Obfuscation::of(&list(&[])), Obfuscation::None);
+        assert_eq!(Obfuscation::of(&list(&["a.com"])), Obfuscation::None);
+        assert_eq!(
+            Obfuscation::of(&list(&["a.com", "b*.com"])),
+            Obfuscation::Partial
+        );
+        assert_eq!(Obfuscation::of(&list(&["b*.com"])), Obfuscation::Full);
+    }
+
+    #[test]
+    fn parses_leniently() {
+        let body = r#"[
+            {"domain":"example.com","severity":"suspend","extra":[1, 2]},
+            {"domain":"exa*ple.org","digest":"4E4C2B1D6FE34FD69A2D7BC3C0C1F1F5F3CBEAF7E1A7D3E0E2A7D1C3B4A5F6E7","severity":"reject"},
+            {"domain":"exa*ple.net","severity":"silence"},
+            {"domain":"bad.digest","digest":"xyz","severity":"silence"},
+            {"domain":5},
+            "not an object"
+        ]"#;
+
+        let parsed = parse_blocklist(body.as_bytes()).unwrap();
+
+        assert_eq!(parsed.list.len(), 2);
+        assert_eq!(
+            parsed.list[0].digest,
+            "a379a6f6eeafb9a55e378c118034e2751e682fab9f2d30ab13d2125586ce1947"
+        );
+        assert_eq!(parsed.list[1].severity, DomainBlockSeverity::Suspend);
+        assert_eq!(parsed.list[1].original_severity.as_deref(), Some("reject"));
+        assert_eq!(parsed.list[0].original_severity, None);
+        assert_eq!(
+            parsed.list[1].digest,
+            "4e4c2b1d6fe34fd69a2d7bc3c0c1f1f5f3cbeaf7e1a7d3e0e2a7d1c3b4a5f6e7"
+        );
+        assert_eq!(parsed.skipped.len(), 4);
+        assert_eq!(
+            count_reasons(&parsed.skipped),
+            BTreeMap::from([
+                (SkipReason::Malformed, 2),
+                (SkipReason::MissingDigest, 1),
+                (SkipReason::InvalidDigest, 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn classifies_non_json_responses() {
+        let classify = |content_type, body: &str| {
+            classify_non_json(content_type, body.as_bytes()).map(|e| e.status)
+        };
+
+        assert_eq!(classify(Some("application/json"), " [] "), None);
+        assert_eq!(classify(None, "{\"error\":\"x\"}"), None);
+        assert_eq!(
+            classify(
+                Some("text/html"),
+                "<html><title>Just a moment...</title></html>"
+            ),
+            Some(FetchStatus::Cloudflare)
+        );
+        assert_eq!(
+            classify(None, "<!DOCTYPE html><h1>Maintenance</h1>"),
+            Some(FetchStatus::HtmlError)
+        );
+        assert_eq!(
+            classify(Some("text/plain"), "nothing here"),
+            Some(FetchStatus::WrongContentType)
+        );
+        // broken json is still a parse error
+        assert_eq!(classify(Some("application/json"), "nul"), None);
+    }
+
+    #[test]
+    fn loads_blocklist() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        let fetcher = fetcher();
+        assert!(matches!(
+            load(&fetcher, &mut db, "good.social"),
+            FetchOutcome::Updated
+        ));
+        assert!(matches!(
+            load(&fetcher, &mut db, "good.social"),
+            FetchOutcome::Unchanged
+        ));
+
+        let blocklist = db.get::<MastodonBlockList>("good.social").unwrap().unwrap();
+        assert_eq!(blocklist.list.len(), 1);
+        assert_eq!(blocklist.list[0].domain, "exa*ple.com");
+        let hash = blocklist.metadata.unwrap().content_hash.unwrap();
+        assert_eq!(hash.len(), 64);
+
+        let status = db.get::<InstanceStatus>("good.social").unwrap().unwrap();
+        assert_eq!(status.status, FetchStatus::Ok);
+        assert!(status.last_ok_at.is_some());
+    }
+
+    #[test]
+    fn compares_hashes_before_lists() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        let fetcher = fetcher();
+        load(&fetcher, &mut db, "good.social");
+
+        // the same response parsed with the same mapping can't give another list
+        let mut stored = db.get::<MastodonBlockList>("good.social").unwrap().unwrap();
+        stored.list.clear();
+        db.set(stored);
+
+        assert!(matches!(
+            load(&fetcher, &mut db, "good.social"),
+            FetchOutcome::Unchanged
+        ));
+    }
+
+    #[test]
+    fn reprocesses_same_body_parsed_differently() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        let fetcher = fetcher();
+        load(&fetcher, &mut db, "good.social");
+
+        // as if the same response was parsed with another severity mapping before
+        let mut stored = db.get::<MastodonBlockList>("good.social").unwrap().unwrap();
+        stored.list[0].severity = DomainBlockSeverity::Silence;
+        stored.processed = true;
+        stored.metadata.as_mut().unwrap().severity_mapping = Some("other".to_string());
+        let hash = stored.metadata.as_ref().unwrap().content_hash.clone();
+        db.set(stored);
+
+        assert!(matches!(
+            load(&fetcher, &mut db, "good.social"),
+            FetchOutcome::Updated
+        ));
+        let blocklist = db.get::<MastodonBlockList>("good.social").unwrap().unwrap();
+        assert_eq!(blocklist.list[0].severity, DomainBlockSeverity::Suspend);
+        assert!(!blocklist.processed);
+        assert_eq!(blocklist.metadata.unwrap().content_hash, hash);
+    }
+
+    #[test]
+    fn tracks_removed_digests_until_processed() {
+        let block = |digest: &str| DomainBlock {
+            domain: String::new(),
+            digest: digest.to_string(),
+            severity: DomainBlockSeverity::Suspend,
+            original_severity: None,
+            limitations: Default::default(),
+            comment: None,
+        };
+        let mut previous = MastodonBlockList {
+            domain: "a.social".to_string(),
+            list: vec![block("aa"), block("bb")],
+            processed: false,
+            metadata: None,
+            removed: BTreeSet::from(["cc".to_string(), "dd".to_string()]),
+        };
+
+        // cc is blocked again, the others are gone
+        let list = [block("AA"), block("cc")];
+        assert_eq!(
+            removed_digests(Some(&previous), &list),
+            BTreeSet::from(["bb".to_string(), "dd".to_string()])
+        );
+
+        previous.processed = true;
+        assert_eq!(
+            removed_digests(Some(&previous), &list),
+            BTreeSet::from(["bb".to_string()])
+        );
+        assert!(removed_digests(None, &list).is_empty());
+    }
+
+    #[test]
+    fn records_parse_error() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        assert!(matches!(
+            load(&fetcher(), &mut db, "broken.social"),
+            FetchOutcome::Failed(FetchError {
+                status: FetchStatus::ParseError,
+                ..
+            })
+        ));
+
+        assert!(db
+            .get::<MastodonBlockList>("broken.social")
+            .unwrap()
+            .is_none());
+        let status = db.get::<InstanceStatus>("broken.social").unwrap().unwrap();
+        assert_eq!(status.status, FetchStatus::ParseError);
+        assert!(status.retry_at.is_none());
+    }
+
+    #[test]
+    fn retries_transient_failures() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        let fetcher = fetcher();
+        load(&fetcher, &mut db, "private.social");
+        load(&fetcher, &mut db, "down.social");
+        load(&fetcher, &mut db, "down.social");
+
+        let status = db.get::<InstanceStatus>("private.social").unwrap().unwrap();
+        assert_eq!(status.status, FetchStatus::RequiresAuth);
+        assert!(status.retry_at.is_none());
+
+        let status = db.get::<InstanceStatus>("down.social").unwrap().unwrap();
+        assert_eq!(status.status, FetchStatus::Unreachable);
+        assert_eq!(status.consecutive_failures, 2);
+        assert!(status.retry_at.unwrap() >= status.checked_at + 2 * RETRY_BASE_DELAY);
+    }
+
+    #[test]
+    fn sends_tokens_to_their_host_only() {
+        let mut config = Config::default();
+        config
+            .instance_tokens
+            .insert("Private.social".to_string(), "secret".to_string());
+        let fetcher = HttpFetcher::new(&config).unwrap();
+
+        assert_eq!(
+            fetcher.token_for("https://private.social/api/v1/instance/peers"),
+            Some("secret")
+        );
+        assert_eq!(fetcher.token_for("https://sub.private.social/"), None);
+        assert_eq!(fetcher.token_for("https://other.social/"), None);
+    }
+
+    #[test]
+    fn takes_html_pages_as_is() {
+        use std::io::{BufRead, BufReader, Write};
+
+        // answers every request with the html about page of an instance
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/about", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let page = "<html><body>Moderated servers</body></html>";
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\ncontent-type: text/html\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{page}",
+                    page.len()
+                )
+                .unwrap();
+            }
+        });
+
+        let fetcher = HttpFetcher::new(&Config::default()).unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let (page, _) = runtime.block_on(fetcher.get(&url, false)).unwrap();
+        assert!(page.body.starts_with(b"<html>"));
+
+        let error = runtime.block_on(fetcher.get(&url, true)).unwrap_err();
+        assert_eq!(error.status, FetchStatus::HtmlError);
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashSet;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{BlockedBy, DomainEntry};
+
+/// Include and exclude glob patterns, such as `*.jp` or `mastodon.*`, that decide which instances
+/// get fetched or which partial domains get cracked. `*` matches any amount of characters and `?`
+/// matches a single character.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DomainFilter {
+    /// If not empty, only domains matching one of these patterns are allowed.
+    pub include: Vec<String>,
+    /// Domains matching any of these patterns are never allowed.
+    pub exclude: Vec<String>,
+}
+
+impl DomainFilter {
+    pub fn allows(&self, domain: &str) -> bool {
+        let domain = domain.to_lowercase();
+
+        let included = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|p| glob_match(&p.to_lowercase(), &domain));
+        let excluded = self
+            .exclude
+            .iter()
+            .any(|p| glob_match(&p.to_lowercase(), &domain));
+
+        included && !excluded
+    }
+}
+
+/// Include and exclude regular expressions for the partial domains of crack jobs, such as
+/// `.*\.social`. A pattern has to match the whole partial domain, wildcards included.
+#[derive(Default)]
+pub struct PatternFilter {
+    /// If set, only partial domains matching it are allowed.
+    pub include: Option<Regex>,
+    /// Partial domains matching it are never allowed.
+    pub exclude: Option<Regex>,
+}
+
+impl PatternFilter {
+    /// Compiles a regex that has to match the whole partial domain.
+    pub fn compile(regex: &str) -> Result<Regex, regex::Error> {
+        Regex::new(&format!("^(?:{regex})$"))
+    }
+
+    pub fn allows(&self, pattern: &str) -> bool {
+        let pattern = pattern.to_lowercase();
+
+        self.include.as_ref().is_none_or(|r| r.is_match(&pattern))
+            && !self.exclude.as_ref().is_some_and(|r| r.is_match(&pattern))
+    }
+}
+
+/// Decides which blocked domains are listed by `show`, `publish` and `export`: by their tags
+/// (`--tag` and `--exclude-tag`) and the `ignore` list in the config.
+#[derive(Default)]
+pub struct EntryFilter {
+    /// If not empty, only domains with at least one of these tags are listed.
+    pub include_tags: Vec<String>,
+    /// Domains with any of these tags are not listed.
+    pub exclude_tags: Vec<String>,
+    /// If not empty, only blocks with a comment in one of these languages are listed.
+    pub comment_languages: Vec<String>,
+    /// Digests of domains that are never listed.
+    pub ignored: HashSet<String>,
+}
+
+impl EntryFilter {
+    pub fn matches(&self, entry: &DomainEntry) -> bool {
+        let tags = &entry.annotation.tags;
+        let has_any = |wanted: &[String]| wanted.iter().any(|t| tags.contains(t));
+
+        (self.include_tags.is_empty() || has_any(&self.include_tags))
+            && !has_any(&self.exclude_tags)
+            && !self.ignored.contains(&hex::encode(entry.digest))
+            && (self.comment_languages.is_empty()
+                || entry.blocked_by.values().any(|b| self.blocker_matches(b)))
+    }
+
+    /// Whether a single block is listed, which only depends on the language of its comment.
+    pub fn blocker_matches(&self, blocked_by: &BlockedBy) -> bool {
+        self.comment_languages.is_empty()
+            || blocked_by
+                .comment_language
+                .as_ref()
+                .is_some_and(|l| self.comment_languages.contains(l))
+    }
+
+    /// Like [`Self::blocker_matches`], for the block of `instance` on `entry`.
+    pub fn instance_matches(&self, entry: &DomainEntry, instance: &str) -> bool {
+        self.comment_languages.is_empty()
+            || entry
+                .blocked_by
+                .get(instance)
+                .is_some_and(|b| self.blocker_matches(b))
+    }
+}
+
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    let text = text.as_bytes();
+
+    // iterative matching with backtracking to the last `*`
+    let (mut p, mut t) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            last_star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = last_star {
+            p = star_p + 1;
+            t = star_t + 1;
+            last_star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("*.jp", "mstdn.jp"));
+        assert!(glob_match("mastodon.*", "mastodon.social"));
+        assert!(glob_match("*", "anything.example"));
+        assert!(glob_match("m?s.to", "mas.to"));
+        assert!(glob_match("*.*.*", "a.b.c"));
+        assert!(!glob_match("*.jp", "mstdn.social"));
+        assert!(!glob_match("m?s.to", "mast.to"));
+        assert!(!glob_match("*.a.*", "a.b.c"));
+    }
+
+    #[test]
+    fn include_and_exclude() {
+        let filter = DomainFilter {
+            include: vec!["*.social".into(), "*.jp".into()],
+            exclude: vec!["bad.*".into()],
+        };
+
+        assert!(filter.allows("mastodon.social"));
+        assert!(filter.allows("MSTDN.JP"));
+        assert!(!filter.allows("mas.to"));
+        assert!(!filter.allows("bad.social"));
+        assert!(DomainFilter::default().allows("mas.to"));
+    }
+
+    #[test]
+    fn pattern_filter() {
+        let filter = PatternFilter {
+            include: Some(PatternFilter::compile(r".*\.social").unwrap()),
+            exclude: Some(PatternFilter::compile(".{10,}").unwrap()),
+        };
+
+        assert!(filter.allows("m*s.social"));
+        assert!(filter.allows("MAS*.SOCIAL"));
+        assert!(!filter.allows("m*s.social.jp"));
+        assert!(!filter.allows("m*stodon.social"));
+        assert!(!filter.allows("mas.t*"));
+        assert!(PatternFilter::default().allows("mas.t*"));
+        assert!(PatternFilter::compile("(").is_err());
+    }
+
+    #[test]
+    fn entry_filter() {
+        let entry = |domain: &str, tags: &[&str]| DomainEntry {
+            digest: crate::hash_domain(domain),
+            known_domain: Some(domain.to_string()),
+            partial_domains: Default::default(),
+            blocked_by: Default::default(),
+            annotation: crate::annotation::Annotation {
+                notes: vec![],
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+            },
+        };
+        let filter = EntryFilter {
+            include_tags: vec!["spam".into()],
+            exclude_tags: vec!["false-positive".into()],
+            comment_languages: vec![],
+            ignored: HashSet::from([hex::encode(crate::hash_domain("ignored.example"))]),
+        };
+
+        assert!(filter.matches(&entry("spam.example", &["spam"])));
+        assert!(!filter.matches(&entry("plain.example", &[])));
+        assert!(!filter.matches(&entry("spam.example", &["spam", "false-positive"])));
+        assert!(!filter.matches(&entry("ignored.example", &["spam"])));
+        assert!(EntryFilter::default().matches(&entry("plain.example", &[])));
+    }
+}
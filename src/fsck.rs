@@ -0,0 +1,241 @@
+use color_eyre::Result;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    crawl::FetchCandidate,
+    database::{DatabaseAccess, DatabaseObject},
+    discover::DiscoveredInstances,
+    enrich::{ProbeResult, RdapInfo},
+    fetch::InstanceStatus,
+    hash_domain,
+    jobs::{CrackJob, CrackStats},
+    journal::{JournalCounter, JournalEntry},
+    metrics::Counters,
+    scrape::ScrapedBlocklist,
+    translate::Translation,
+    DomainEntry, MastodonBlockList,
+};
+
+/// Counts the problems found, and how many of them were repaired.
+struct Problems {
+    repair: bool,
+    found: usize,
+    repaired: usize,
+}
+
+impl Problems {
+    fn report(&mut self, message: String, repaired: bool) {
+        self.found += 1;
+        if repaired {
+            self.repaired += 1;
+            println!("{message} (repaired)");
+        } else {
+            println!("{message}");
+        }
+    }
+}
+
+/// Checks the integrity of the database, repairing what it can when `repair` is set. Returns
+/// how many problems are left.
+pub fn fsck(db: &mut DatabaseAccess, repair: bool) -> Result<usize> {
+    let mut problems = Problems {
+        repair,
+        found: 0,
+        repaired: 0,
+    };
+
+    check_keys(db, &mut problems);
+    check_objects::<MastodonBlockList>(db, &mut problems);
+    check_objects::<DomainEntry>(db, &mut problems);
+    check_objects::<CrackJob>(db, &mut problems);
+    check_objects::<JournalEntry>(db, &mut problems);
+    check_objects::<JournalCounter>(db, &mut problems);
+    check_objects::<InstanceStatus>(db, &mut problems);
+    check_objects::<FetchCandidate>(db, &mut problems);
+    check_objects::<ScrapedBlocklist>(db, &mut problems);
+    check_objects::<DiscoveredInstances>(db, &mut problems);
+    check_objects::<RdapInfo>(db, &mut problems);
+    check_objects::<ProbeResult>(db, &mut problems);
+    check_objects::<Counters>(db, &mut problems);
+    check_objects::<CrackStats>(db, &mut problems);
+    check_objects::<Translation>(db, &mut problems);
+    check_domain_entries(db, &mut problems)?;
+    check_blocklists(db, &mut problems)?;
+
+    println!(
+        "Found {} problems, repaired {}",
+        problems.found, problems.repaired
+    );
+    Ok(problems.found - problems.repaired)
+}
+
+/// Every key must start with the prefix of a known object type.
+fn check_keys(db: &mut DatabaseAccess, problems: &mut Problems) {
+    let known = [
+        MastodonBlockList::KEY_NAME,
+        DomainEntry::KEY_NAME,
+        CrackJob::KEY_NAME,
+        JournalEntry::KEY_NAME,
+        JournalCounter::KEY_NAME,
+        InstanceStatus::KEY_NAME,
+        FetchCandidate::KEY_NAME,
+        ScrapedBlocklist::KEY_NAME,
+        DiscoveredInstances::KEY_NAME,
+        RdapInfo::KEY_NAME,
+        ProbeResult::KEY_NAME,
+        Counters::KEY_NAME,
+        CrackStats::KEY_NAME,
+        Translation::KEY_NAME,
+    ];
+
+    for key in db.raw_keys() {
+        let valid = key
+            .split_once(':')
+            .is_some_and(|(prefix, id)| known.contains(&prefix) && !id.is_empty());
+        if !valid {
+            let repaired = problems.repair && db.remove_raw(&key);
+            problems.report(format!("Malformed key {key}"), repaired);
+        }
+    }
+}
+
+fn check_objects<T: DatabaseObject + DeserializeOwned>(
+    db: &mut DatabaseAccess,
+    problems: &mut Problems,
+) {
+    let keys = db.iter_keys::<T>().collect::<Vec<_>>();
+    for key in keys {
+        if let Err(e) = db.get::<T>(&key) {
+            problems.report(format!("Unreadable {} {key}: {e}", T::KEY_NAME), false);
+        }
+    }
+}
+
+/// Domain entries must be stored under the hex of their digest, and their known domain must
+/// hash to it.
+fn check_domain_entries(db: &mut DatabaseAccess, problems: &mut Problems) -> Result<()> {
+    let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
+    for key in keys {
+        let Ok(Some(mut entry)) = db.get::<DomainEntry>(&key) else {
+            continue;
+        };
+
+        if entry
+            .known_domain
+            .as_deref()
+            .is_some_and(|d| hash_domain(d) != entry.digest)
+        {
+            let known_domain = entry.known_domain.clone().unwrap();
+            // an entry without any domain can't be shown, so only drop it if a partial is left
+            let repaired = problems.repair && !entry.partial_domains.is_empty();
+            if repaired {
+                entry.known_domain = None;
+                db.set(entry);
+            }
+            problems.report(
+                format!("Domain entry {key} has known domain {known_domain} with another digest"),
+                repaired,
+            );
+            continue;
+        }
+
+        let id = entry.get_id().into_owned();
+        if key != id {
+            if problems.repair {
+                db.remove::<DomainEntry>(&key);
+                if let Some(existing) = db.get::<DomainEntry>(&id)? {
+                    entry = entry.merge(existing);
+                }
+                db.set(entry);
+            }
+            problems.report(
+                format!("Domain entry {key} is stored under the wrong digest"),
+                problems.repair,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Every digest in a blocklist must be valid and have a domain entry.
+fn check_blocklists(db: &mut DatabaseAccess, problems: &mut Problems) -> Result<()> {
+    let keys = db.iter_keys::<MastodonBlockList>().collect::<Vec<_>>();
+    for key in keys {
+        let Ok(Some(blocklist)) = db.get::<MastodonBlockList>(&key) else {
+            continue;
+        };
+
+        for block in blocklist.list {
+            if block.digest.is_empty() {
+                continue;
+            }
+
+            let digest = block.digest.to_lowercase();
+            if !hex::decode(&digest).is_ok_and(|d| d.len() == 32) {
+                problems.report(
+                    format!("Blocklist of {key} has invalid digest {}", block.digest),
+                    false,
+                );
+                continue;
+            }
+
+            if db.get::<DomainEntry>(&digest)?.is_none() {
+                let repaired = problems.repair;
+                if repaired {
+                    db.set(DomainEntry::try_from(block)?);
+                }
+                problems.report(
+                    format!("Blocklist of {key} has digest {digest} without a domain entry"),
+                    repaired,
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::{
+        api::{DomainBlock, DomainBlockSeverity},
+        database::DatabaseInstance,
+    };
+
+    #[test]
+    fn repairs_database() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        db.set(MastodonBlockList {
+            domain: "a.social".to_string(),
+            list: vec![DomainBlock {
+                domain: "exa*ple.com".to_string(),
+                digest: hex::encode(hash_domain("example.com")),
+                severity: DomainBlockSeverity::Suspend,
+                original_severity: None,
+                limitations: Default::default(),
+                comment: None,
+            }],
+            processed: true,
+            metadata: None,
+            removed: Default::default(),
+        });
+        db.set(DomainEntry {
+            digest: hash_domain("example.org"),
+            known_domain: Some("example.net".to_string()),
+            partial_domains: BTreeSet::from(["exa*ple.org".to_string()]),
+            blocked_by: Default::default(),
+            annotation: Default::default(),
+        });
+
+        assert_eq!(fsck(&mut db, false).unwrap(), 2);
+        assert_eq!(fsck(&mut db, true).unwrap(), 0);
+        assert_eq!(fsck(&mut db, false).unwrap(), 0);
+
+        let digest = hex::encode(hash_domain("example.com"));
+        assert!(db.get::<DomainEntry>(&digest).unwrap().is_some());
+    }
+}
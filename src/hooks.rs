@@ -0,0 +1,108 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+
+use serde::Serialize;
+
+use crate::{
+    config::HooksConfig,
+    fetch::FetchSummary,
+    journal::{Event, JournalEntry},
+};
+
+static HOOKS: OnceLock<HooksConfig> = OnceLock::new();
+
+/// Sets the hook commands for this run. Until this is called no hooks run, as in tests.
+pub fn init(config: &HooksConfig) {
+    _ = HOOKS.set(config.clone());
+}
+
+/// Runs `on_new_block` or `on_resolved` with the recorded journal entry.
+pub fn event_recorded(entry: &JournalEntry) {
+    let Some(hooks) = HOOKS.get() else {
+        return;
+    };
+
+    let (name, command) = match entry.event {
+        Event::NewBlock { .. } => ("on_new_block", &hooks.on_new_block),
+        Event::Resolved { .. } => ("on_resolved", &hooks.on_resolved),
+        _ => return,
+    };
+    if let Some(command) = command {
+        run(name, command, entry);
+    }
+}
+
+#[derive(Serialize)]
+struct FetchComplete<'a> {
+    updated: &'a [String],
+    unchanged: &'a [String],
+    failed: Vec<FailedFetch<'a>>,
+}
+
+#[derive(Serialize)]
+struct FailedFetch<'a> {
+    domain: &'a str,
+    status: String,
+    message: &'a str,
+}
+
+/// Runs `on_fetch_complete` with the outcome of every fetched instance.
+pub fn fetch_completed(summary: &FetchSummary) {
+    let Some(command) = HOOKS.get().and_then(|h| h.on_fetch_complete.as_ref()) else {
+        return;
+    };
+
+    let payload = FetchComplete {
+        updated: &summary.updated,
+        unchanged: &summary.unchanged,
+        failed: summary
+            .failed
+            .iter()
+            .map(|(domain, e)| FailedFetch {
+                domain,
+                status: e.status.to_string(),
+                message: &e.message,
+            })
+            .collect(),
+    };
+    run("on_fetch_complete", command, &payload);
+}
+
+/// Runs a hook through `sh` with the payload as json on stdin. A failing hook is reported but
+/// doesn't stop the verb.
+fn run(name: &str, command: &str, payload: &impl Serialize) {
+    let payload = match serde_json::to_vec(payload) {
+        Ok(payload) => payload,
+        Err(e) => {
+            println!("Error while trying to serialize the payload of {name}: {e}");
+            return;
+        }
+    };
+
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            println!("Error while trying to run {name} hook: {e}");
+            return;
+        }
+    };
+
+    // the hook may not read its stdin
+    if let Some(mut stdin) = child.stdin.take() {
+        _ = stdin.write_all(&payload);
+    }
+
+    match child.wait() {
+        Ok(status) if !status.success() => println!("{name} hook exited with {status}"),
+        Ok(_) => {}
+        Err(e) => println!("Error while waiting for {name} hook: {e}"),
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use color_eyre::{eyre::Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// An on-disk cache of http responses, keyed by url. Responses are reused while their
+/// `Cache-Control: max-age` allows it, and revalidated with their ETag after that.
+pub struct HttpCache {
+    dir: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub url: String,
+    pub stored_at: u64,
+    pub max_age: Option<u64>,
+    pub etag: Option<String>,
+    pub http_status: u16,
+    /// The `Link` header, pointing to the next page of paginated responses.
+    #[serde(default)]
+    pub link: Option<String>,
+}
+
+impl CachedResponse {
+    pub fn is_fresh(&self, now: u64) -> bool {
+        self.max_age
+            .is_some_and(|max_age| self.stored_at + max_age > now)
+    }
+}
+
+impl HttpCache {
+    pub fn new(dir: &str) -> Result<Self> {
+        std::fs::create_dir_all(dir).context("create cache directory")?;
+        Ok(Self { dir: dir.into() })
+    }
+
+    fn path(&self, url: &str, extension: &str) -> PathBuf {
+        let key = hex::encode(Sha256::digest(url.as_bytes()));
+        self.dir.join(format!("{key}.{extension}"))
+    }
+
+    pub fn lookup(&self, url: &str) -> Option<(CachedResponse, Vec<u8>)> {
+        let metadata = std::fs::read(self.path(url, "json")).ok()?;
+        let metadata = serde_json::from_slice::<CachedResponse>(&metadata).ok()?;
+        let body = std::fs::read(self.path(url, "body")).ok()?;
+
+        // hash collisions are unlikely, but a stale file from a different url is not a response
+        (metadata.url == url).then_some((metadata, body))
+    }
+
+    /// Stores a response, unless its cache headers forbid it.
+    pub fn store(
+        &self,
+        url: &str,
+        cache_control: Option<&str>,
+        etag: Option<&str>,
+        link: Option<&str>,
+        http_status: u16,
+        body: &[u8],
+    ) -> Result<()> {
+        if cache_control.is_some_and(|c| c.contains("no-store")) {
+            return Ok(());
+        }
+
+        let metadata = CachedResponse {
+            url: url.to_string(),
+            stored_at: crate::unix_now(),
+            max_age: cache_control.and_then(max_age),
+            etag: etag.map(|e| e.to_string()),
+            http_status,
+            link: link.map(|l| l.to_string()),
+        };
+
+        std::fs::write(self.path(url, "body"), body).context("write cached body")?;
+        std::fs::write(self.path(url, "json"), serde_json::to_vec(&metadata)?)
+            .context("write cached metadata")?;
+        Ok(())
+    }
+}
+
+/// Gets how many seconds a response may be reused without revalidating it.
+fn max_age(cache_control: &str) -> Option<u64> {
+    let directives = cache_control
+        .split(',')
+        .map(|d| d.trim().to_lowercase())
+        .collect::<Vec<_>>();
+    if directives.iter().any(|d| d == "no-cache") {
+        return None;
+    }
+
+    directives
+        .iter()
+        .find_map(|d| d.strip_prefix("max-age=")?.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_max_age() {
+        assert_eq!(max_age("public, max-age=300"), Some(300));
+        assert_eq!(max_age("max-age=300, no-cache"), None);
+        assert_eq!(max_age("private"), None);
+    }
+}
@@ -0,0 +1,518 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{DatabaseAccess, DatabaseObject},
+    keyspace,
+    output::{Color, Table},
+    suffix, DomainEntry,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum JobState {
+    Pending,
+    /// Being cracked, or the run was interrupted. These are picked up first by the next run.
+    Running,
+    /// The whole keyspace was searched without a match.
+    Exhausted,
+    /// The domain was found, either by this job or in another way.
+    Solved,
+    Cancelled,
+}
+
+impl JobState {
+    fn is_queued(self) -> bool {
+        matches!(self, Self::Pending | Self::Running)
+    }
+}
+
+impl std::fmt::Display for JobState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Exhausted => "exhausted",
+            Self::Solved => "solved",
+            Self::Cancelled => "cancelled",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Brute-forcing a single partial domain of a digest.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CrackJob {
+    pub digest: String,
+    pub pattern: String,
+    pub state: JobState,
+    /// Jobs with a higher priority are cracked first.
+    pub priority: i32,
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// What was searched when the job got exhausted.
+    #[serde(default)]
+    pub searched: Option<SearchedKeyspace>,
+    /// How far brute-forcing got in a run that ran out of time.
+    #[serde(default)]
+    pub checkpoint: Option<Checkpoint>,
+    /// The shards that were searched without a match, when the job is cracked in shards.
+    #[serde(default)]
+    pub finished_shards: Option<FinishedShards>,
+}
+
+/// Shards of a job's keyspace that were searched in full, as their index and count.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FinishedShards {
+    pub keyspace: SearchedKeyspace,
+    pub shards: BTreeSet<(usize, usize)>,
+}
+
+/// Where an unfinished brute-force search continues. It only applies to the same shard of the
+/// same keyspace, since the candidate indices mean something else in another one.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Checkpoint {
+    pub keyspace: SearchedKeyspace,
+    /// The shard that was being searched, as its index and count.
+    pub shard: (usize, usize),
+    /// Index of the next candidate. The candidates of the shard before it were all tried.
+    pub resume_at: u64,
+}
+
+impl Checkpoint {
+    pub fn new(charsets: &[Vec<u8>], shard: keyspace::Shard, resume_at: usize) -> Self {
+        Self {
+            keyspace: SearchedKeyspace::new(charsets),
+            shard: (shard.index, shard.count),
+            resume_at: resume_at as u64,
+        }
+    }
+}
+
+/// The parameters of a completed keyspace walk. An exhausted job is only skipped while these
+/// still cover what the current engine would search.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SearchedKeyspace {
+    pub engine_version: u32,
+    /// The characters that were tried for each wildcard.
+    pub charsets: Vec<String>,
+}
+
+impl SearchedKeyspace {
+    pub fn new(charsets: &[Vec<u8>]) -> Self {
+        Self {
+            engine_version: keyspace::ENGINE_VERSION,
+            charsets: charsets
+                .iter()
+                .map(|c| String::from_utf8_lossy(c).into_owned())
+                .collect(),
+        }
+    }
+
+    fn covers(&self, charsets: &[Vec<u8>]) -> bool {
+        self.engine_version == keyspace::ENGINE_VERSION
+            && self.charsets.len() == charsets.len()
+            && self
+                .charsets
+                .iter()
+                .zip(charsets)
+                .all(|(searched, charset)| charset.iter().all(|b| searched.as_bytes().contains(b)))
+    }
+}
+
+impl CrackJob {
+    pub fn wildcard_count(&self) -> usize {
+        self.pattern.chars().filter(|c| *c == '*').count()
+    }
+
+    /// Where brute-forcing `shard` continues, which is the start unless an earlier run left a
+    /// checkpoint in the same keyspace.
+    pub fn resume_at(&self, charsets: &[Vec<u8>], shard: keyspace::Shard) -> usize {
+        self.checkpoint
+            .as_ref()
+            .filter(|c| {
+                c.shard == (shard.index, shard.count)
+                    && c.keyspace == SearchedKeyspace::new(charsets)
+            })
+            .map_or(0, |c| c.resume_at as usize)
+    }
+
+    /// Whether `shard` was already searched in full, in the same keyspace.
+    pub fn is_shard_finished(&self, charsets: &[Vec<u8>], shard: keyspace::Shard) -> bool {
+        self.finished_shards.as_ref().is_some_and(|f| {
+            f.keyspace == SearchedKeyspace::new(charsets)
+                && f.shards.contains(&(shard.index, shard.count))
+        })
+    }
+
+    /// Records that `shard` was searched in full. Returns whether every shard of the same count
+    /// now is, which means the whole keyspace was searched.
+    pub fn finish_shard(&mut self, charsets: &[Vec<u8>], shard: keyspace::Shard) -> bool {
+        let keyspace = SearchedKeyspace::new(charsets);
+        if self
+            .finished_shards
+            .as_ref()
+            .is_some_and(|f| f.keyspace != keyspace)
+        {
+            self.finished_shards = None;
+        }
+        let finished = self.finished_shards.get_or_insert_with(|| FinishedShards {
+            keyspace,
+            shards: BTreeSet::new(),
+        });
+        finished.shards.insert((shard.index, shard.count));
+
+        (1..=shard.count).all(|index| finished.shards.contains(&(index, shard.count)))
+    }
+}
+
+impl DatabaseObject for CrackJob {
+    const KEY_NAME: &'static str = "crack_job";
+
+    fn get_id(&self) -> Cow<str> {
+        format!("{}/{}", self.digest, self.pattern).into()
+    }
+}
+
+/// How much cracking effort went into a digest, by candidate source.
+#[derive(Serialize, Deserialize, Default)]
+pub struct CrackStats {
+    pub digest: String,
+    pub by_source: BTreeMap<String, SourceStats>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+pub struct SourceStats {
+    pub runs: u32,
+    pub candidates: u64,
+    /// Wall time multiplied by the number of worker threads, since every source keeps all cores
+    /// busy.
+    pub cpu_seconds: f64,
+}
+
+impl CrackStats {
+    pub fn cpu_seconds(&self) -> f64 {
+        self.by_source.values().map(|s| s.cpu_seconds).sum()
+    }
+}
+
+impl DatabaseObject for CrackStats {
+    const KEY_NAME: &'static str = "crack_stats";
+
+    fn get_id(&self) -> Cow<str> {
+        (&self.digest).into()
+    }
+}
+
+/// Adds a run of a candidate source on a digest to its statistics.
+pub fn record_attempt(
+    db: &mut DatabaseAccess,
+    digest: &str,
+    source: &str,
+    tried: u64,
+    elapsed: Duration,
+) -> Result<()> {
+    let mut stats = db.get::<CrackStats>(digest)?.unwrap_or_else(|| CrackStats {
+        digest: digest.to_string(),
+        ..Default::default()
+    });
+
+    let source = stats.by_source.entry(source.to_string()).or_default();
+    source.runs += 1;
+    source.candidates += tried;
+    source.cpu_seconds += elapsed.as_secs_f64() * rayon::current_num_threads() as f64;
+
+    db.set(stats);
+    Ok(())
+}
+
+/// Creates jobs for partial domains that don't have one yet, and for the hosting suffixes they
+/// may end with, and marks the jobs of resolved digests as solved. Exhausted jobs are queued again when the alphabet, the known characters
+/// or the engine changed since they were searched. Jobs of digests that are no longer in the
+/// database, such as those moved by `fsck --repair`, are removed. Returns how many jobs were
+/// created, queued again and removed.
+pub fn sync_jobs(db: &mut DatabaseAccess) -> Result<(usize, usize, usize)> {
+    let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
+
+    let mut created = 0;
+    let mut requeued = 0;
+    for key in keys {
+        let entry = db.get::<DomainEntry>(&key)?.unwrap();
+        let hypotheses = entry
+            .partial_domains
+            .iter()
+            .flat_map(|p| suffix::hypotheses(p))
+            .collect::<Vec<_>>();
+        for pattern in entry.partial_domains.iter().chain(&hypotheses) {
+            let id = format!("{key}/{pattern}");
+            match db.get::<CrackJob>(&id)? {
+                Some(job) if entry.known_domain.is_some() && job.state.is_queued() => {
+                    set_state(db, job, JobState::Solved);
+                }
+                Some(job) if entry.known_domain.is_none() && job.state == JobState::Exhausted => {
+                    let charsets = keyspace::wildcard_charsets(pattern, &entry.partial_domains);
+                    if !job.searched.as_ref().is_some_and(|s| s.covers(&charsets)) {
+                        set_state(db, job, JobState::Pending);
+                        requeued += 1;
+                    }
+                }
+                None if entry.known_domain.is_none() => {
+                    let now = crate::unix_now();
+                    db.set(CrackJob {
+                        digest: key.clone(),
+                        pattern: pattern.clone(),
+                        state: JobState::Pending,
+                        priority: 0,
+                        created_at: now,
+                        updated_at: now,
+                        searched: None,
+                        checkpoint: None,
+                        finished_shards: None,
+                    });
+                    created += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let job_keys = db.iter_keys::<CrackJob>().collect::<Vec<_>>();
+    let mut removed = 0;
+    for key in job_keys {
+        let job = db.get::<CrackJob>(&key)?.unwrap();
+        if db.get::<DomainEntry>(&job.digest)?.is_none() {
+            db.remove::<CrackJob>(&key);
+            removed += 1;
+        }
+    }
+
+    Ok((created, requeued, removed))
+}
+
+/// Gets all jobs, in the order they will be cracked: running first, then by priority and
+/// the size of their keyspace.
+pub fn all_jobs(db: &mut DatabaseAccess) -> Result<Vec<CrackJob>> {
+    let keys = db.iter_keys::<CrackJob>().collect::<Vec<_>>();
+    let mut jobs = keys
+        .into_iter()
+        .map(|k| db.get::<CrackJob>(&k).map(Option::unwrap))
+        .collect::<Result<Vec<_>>>()?;
+
+    jobs.sort_by(|a, b| {
+        (b.state == JobState::Running)
+            .cmp(&(a.state == JobState::Running))
+            .then(b.priority.cmp(&a.priority))
+            .then(a.wildcard_count().cmp(&b.wildcard_count()))
+    });
+
+    Ok(jobs)
+}
+
+/// Gets the jobs that still need to be cracked, in order.
+pub fn queued_jobs(db: &mut DatabaseAccess) -> Result<Vec<CrackJob>> {
+    let mut jobs = all_jobs(db)?;
+    jobs.retain(|j| j.state.is_queued());
+    Ok(jobs)
+}
+
+pub fn set_state(db: &mut DatabaseAccess, mut job: CrackJob, state: JobState) {
+    job.state = state;
+    job.updated_at = crate::unix_now();
+    db.set(job);
+}
+
+/// Queues every job of a digest again with the given priority, including exhausted and
+/// cancelled ones. Returns how many jobs were updated.
+pub fn enqueue(db: &mut DatabaseAccess, digest: &str, priority: i32) -> Result<usize> {
+    update_digest(db, digest, |job| {
+        (job.state != JobState::Solved).then_some((JobState::Pending, priority))
+    })
+}
+
+/// Cancels the queued jobs of a digest. Returns how many jobs were updated.
+pub fn cancel(db: &mut DatabaseAccess, digest: &str) -> Result<usize> {
+    update_digest(db, digest, |job| {
+        job.state
+            .is_queued()
+            .then_some((JobState::Cancelled, job.priority))
+    })
+}
+
+fn update_digest(
+    db: &mut DatabaseAccess,
+    digest: &str,
+    update: impl Fn(&CrackJob) -> Option<(JobState, i32)>,
+) -> Result<usize> {
+    let prefix = format!("{}/", digest.to_lowercase());
+    let keys = db
+        .iter_keys::<CrackJob>()
+        .filter(|k| k.starts_with(&prefix))
+        .collect::<Vec<_>>();
+
+    let mut updated = 0;
+    for key in keys {
+        let mut job = db.get::<CrackJob>(&key)?.unwrap();
+        if let Some((state, priority)) = update(&job) {
+            job.priority = priority;
+            set_state(db, job, state);
+            updated += 1;
+        }
+    }
+
+    Ok(updated)
+}
+
+pub fn print_queue(db: &mut DatabaseAccess) -> Result<()> {
+    let jobs = all_jobs(db)?;
+
+    let mut table = Table::new(&["Digest", "Pattern", "State", "Priority"]);
+    for job in &jobs {
+        let color = match job.state {
+            JobState::Pending => None,
+            JobState::Running => Some(Color::Yellow),
+            JobState::Solved => Some(Color::Green),
+            JobState::Exhausted | JobState::Cancelled => Some(Color::Dim),
+        };
+        table.add_row(vec![
+            (job.digest.clone(), None),
+            (job.pattern.clone(), None),
+            (job.state.to_string(), color),
+            (job.priority.to_string(), None),
+        ]);
+    }
+    table.print();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseInstance;
+
+    #[test]
+    fn syncs_and_orders_jobs() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        db.set(DomainEntry {
+            digest: crate::hash_domain("example.com"),
+            known_domain: None,
+            partial_domains: BTreeSet::from(["e*a*ple.com".to_string(), "exa*ple.com".to_string()]),
+            blocked_by: Default::default(),
+            annotation: Default::default(),
+        });
+
+        assert_eq!(sync_jobs(&mut db).unwrap(), (2, 0, 0));
+        assert_eq!(sync_jobs(&mut db).unwrap(), (0, 0, 0));
+
+        let jobs = queued_jobs(&mut db).unwrap();
+        assert_eq!(jobs[0].pattern, "exa*ple.com");
+
+        let digest = hex::encode(crate::hash_domain("example.com"));
+        assert_eq!(enqueue(&mut db, &digest, 5).unwrap(), 2);
+        assert_eq!(cancel(&mut db, &digest).unwrap(), 2);
+        assert!(queued_jobs(&mut db).unwrap().is_empty());
+    }
+
+    #[test]
+    fn removes_jobs_without_entry() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        let digest = hex::encode(crate::hash_domain("example.com"));
+        db.set(DomainEntry {
+            digest: crate::hash_domain("example.com"),
+            known_domain: None,
+            partial_domains: BTreeSet::from(["exa*ple.com".to_string()]),
+            blocked_by: Default::default(),
+            annotation: Default::default(),
+        });
+        assert_eq!(sync_jobs(&mut db).unwrap(), (1, 0, 0));
+
+        // as if fsck moved the entry to another key
+        db.remove::<DomainEntry>(&digest);
+        assert_eq!(sync_jobs(&mut db).unwrap(), (0, 0, 1));
+        assert!(queued_jobs(&mut db).unwrap().is_empty());
+    }
+
+    #[test]
+    fn records_attempts() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        let second = Duration::from_secs(1);
+        record_attempt(&mut db, "abc", "plausible", 10, Duration::ZERO).unwrap();
+        record_attempt(&mut db, "abc", "brute-force", 100, second).unwrap();
+        record_attempt(&mut db, "abc", "brute-force", 50, second).unwrap();
+
+        let stats = db.get::<CrackStats>("abc").unwrap().unwrap();
+        assert_eq!(stats.by_source["plausible"].candidates, 10);
+        assert_eq!(stats.by_source["brute-force"].runs, 2);
+        assert_eq!(stats.by_source["brute-force"].candidates, 150);
+        assert!(stats.cpu_seconds() >= 2.);
+    }
+
+    #[test]
+    fn requeues_exhausted_jobs_when_keyspace_grows() {
+        let searched = SearchedKeyspace::new(&[b"ab".to_vec(), keyspace::ALPHABET.to_vec()]);
+
+        assert!(searched.covers(&[b"a".to_vec(), keyspace::ALPHABET.to_vec()]));
+        assert!(!searched.covers(&[b"c".to_vec(), keyspace::ALPHABET.to_vec()]));
+        assert!(!searched.covers(&[b"a".to_vec()]));
+
+        let outdated = SearchedKeyspace {
+            engine_version: keyspace::ENGINE_VERSION - 1,
+            ..searched
+        };
+        assert!(!outdated.covers(&[b"a".to_vec(), keyspace::ALPHABET.to_vec()]));
+    }
+
+    #[test]
+    fn resumes_from_matching_checkpoint_only() {
+        let charsets = [keyspace::ALPHABET.to_vec()];
+        let shard = keyspace::Shard { index: 2, count: 4 };
+        let job = CrackJob {
+            digest: "abc".to_string(),
+            pattern: "ex*mple.com".to_string(),
+            state: JobState::Running,
+            priority: 0,
+            created_at: 0,
+            updated_at: 0,
+            searched: None,
+            checkpoint: Some(Checkpoint::new(&charsets, shard, 12)),
+            finished_shards: None,
+        };
+
+        assert_eq!(job.resume_at(&charsets, shard), 12);
+        assert_eq!(job.resume_at(&charsets, keyspace::Shard::default()), 0);
+        assert_eq!(job.resume_at(&[b"ab".to_vec()], shard), 0);
+    }
+
+    #[test]
+    fn exhausts_after_every_shard_finished() {
+        let charsets = [keyspace::ALPHABET.to_vec()];
+        let shard = |index| keyspace::Shard { index, count: 2 };
+        let mut job = CrackJob {
+            digest: "abc".to_string(),
+            pattern: "ex*mple.com".to_string(),
+            state: JobState::Running,
+            priority: 0,
+            created_at: 0,
+            updated_at: 0,
+            searched: None,
+            checkpoint: None,
+            finished_shards: None,
+        };
+
+        assert!(!job.finish_shard(&charsets, shard(2)));
+        assert!(job.is_shard_finished(&charsets, shard(2)));
+        assert!(!job.is_shard_finished(&charsets, shard(1)));
+        assert!(job.finish_shard(&charsets, shard(1)));
+
+        // shards of another keyspace don't count
+        let grown = [b"ab".to_vec()];
+        assert!(!job.is_shard_finished(&grown, shard(2)));
+        assert!(!job.finish_shard(&grown, shard(1)));
+    }
+}
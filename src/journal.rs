@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::DomainBlockSeverity,
+    database::{DatabaseAccess, DatabaseObject},
+    hooks,
+};
+
+/// Something that changed in the dataset, recorded so consumers can follow along.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// An instance was fetched for the first time.
+    InstanceAdded { instance: String, blocks: usize },
+    /// An already known instance started blocking a domain.
+    NewBlock {
+        instance: String,
+        digest: String,
+        domain: String,
+        severity: DomainBlockSeverity,
+    },
+    /// An already known instance stopped blocking a domain.
+    Unblocked { instance: String, digest: String },
+    /// The domain of a digest became known.
+    Resolved {
+        digest: String,
+        domain: String,
+        /// How it was resolved, such as `brute-force` or `import`.
+        source: String,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: u64,
+    pub at: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+impl DatabaseObject for JournalEntry {
+    const KEY_NAME: &'static str = "event";
+
+    fn get_id(&self) -> Cow<str> {
+        // padded so the keys sort in order
+        format!("{:012}", self.id).into()
+    }
+}
+
+/// The id the next recorded event gets, kept apart from the events so recording one doesn't
+/// need to look at the others and ids never repeat after pruning.
+#[derive(Serialize, Deserialize)]
+pub struct JournalCounter {
+    pub next_id: u64,
+}
+
+impl DatabaseObject for JournalCounter {
+    const KEY_NAME: &'static str = "journal_counter";
+
+    fn get_id(&self) -> Cow<str> {
+        "global".into()
+    }
+}
+
+/// Gets the id the next recorded event will get.
+pub fn next_id(db: &mut DatabaseAccess) -> Result<u64> {
+    if let Some(counter) = db.get::<JournalCounter>("global")? {
+        return Ok(counter.next_id);
+    }
+
+    // databases from before the counter
+    Ok(db
+        .iter_keys::<JournalEntry>()
+        .last()
+        .map(|k| k.parse::<u64>())
+        .transpose()?
+        .map_or(0, |id| id + 1))
+}
+
+pub fn record(db: &mut DatabaseAccess, event: Event) -> Result<()> {
+    let id = next_id(db)?;
+
+    let entry = JournalEntry {
+        id,
+        at: crate::unix_now(),
+        event,
+    };
+    hooks::event_recorded(&entry);
+    db.set(entry);
+    db.set(JournalCounter { next_id: id + 1 });
+
+    Ok(())
+}
+
+/// Gets all events starting at `since`, in order.
+pub fn events_since(db: &mut DatabaseAccess, since: u64) -> Result<Vec<JournalEntry>> {
+    let keys = db.iter_keys::<JournalEntry>().collect::<Vec<_>>();
+
+    let mut entries = vec![];
+    for key in keys {
+        if key.parse::<u64>()? >= since {
+            entries.push(db.get::<JournalEntry>(&key)?.unwrap());
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Prints all events starting at `since` as json lines, returning the id of the next event.
+pub fn print_events(db: &mut DatabaseAccess, since: u64) -> Result<u64> {
+    let mut next = since;
+    for entry in events_since(db, since)? {
+        println!("{}", serde_json::to_string(&entry)?);
+        next = entry.id + 1;
+    }
+
+    Ok(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseInstance;
+
+    #[test]
+    fn records_in_order() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        for i in 0..12 {
+            record(
+                &mut db,
+                Event::InstanceAdded {
+                    instance: format!("{i}.social"),
+                    blocks: i,
+                },
+            )
+            .unwrap();
+        }
+
+        let keys = db.iter_keys::<JournalEntry>().collect::<Vec<_>>();
+        assert_eq!(keys.len(), 12);
+        assert_eq!(keys.last().unwrap(), "000000000011");
+        assert_eq!(print_events(&mut db, 10).unwrap(), 12);
+
+        // ids keep counting after every event is removed
+        for key in keys {
+            db.remove::<JournalEntry>(&key);
+        }
+        assert_eq!(next_id(&mut db).unwrap(), 12);
+    }
+}
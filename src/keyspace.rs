@@ -0,0 +1,125 @@
+use std::{collections::BTreeSet, ops::Range};
+
+use color_eyre::{eyre::eyre, Result};
+
+/// The characters a censored character is brute-forced with.
+pub const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Version of the brute-forcing engine. Bump this when it changes in a way that may find
+/// candidates it missed before, so exhausted jobs get searched again.
+pub const ENGINE_VERSION: u32 = 1;
+
+/// Gets the possible characters of each wildcard in `pattern`, in order. Instances censor
+/// different characters of the same domain, so a sibling pattern of the same length may reveal
+/// what a wildcard is, which shrinks the keyspace by a factor 36 for every revealed position.
+pub fn wildcard_charsets(pattern: &str, siblings: &BTreeSet<String>) -> Vec<Vec<u8>> {
+    // censoring keeps the length of the domain, so other lengths are different domains
+    let siblings = siblings
+        .iter()
+        .map(|s| s.as_bytes())
+        .filter(|s| s.len() == pattern.len())
+        .collect::<Vec<_>>();
+
+    pattern
+        .bytes()
+        .enumerate()
+        .filter(|(_, b)| *b == b'*')
+        .map(|(i, _)| {
+            let mut revealed = siblings
+                .iter()
+                .map(|s| s[i])
+                .filter(|b| *b != b'*')
+                .collect::<Vec<_>>();
+            revealed.sort();
+            revealed.dedup();
+
+            if revealed.is_empty() {
+                ALPHABET.to_vec()
+            } else {
+                revealed
+            }
+        })
+        .collect()
+}
+
+/// The number of candidates needed to exhaust a pattern.
+pub fn keyspace_size(charsets: &[Vec<u8>]) -> u128 {
+    charsets.iter().map(|c| c.len() as u128).product()
+}
+
+/// One of `count` equal slices of every keyspace, numbered from 1, so a big pattern can be split
+/// over several machines by hand.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Default for Shard {
+    fn default() -> Self {
+        Self { index: 1, count: 1 }
+    }
+}
+
+impl Shard {
+    /// Parses a shard in the form `i/n`.
+    pub fn parse(s: &str) -> Result<Self> {
+        let (index, count) = s
+            .split_once('/')
+            .and_then(|(i, n)| Some((i.trim().parse().ok()?, n.trim().parse().ok()?)))
+            .ok_or_else(|| eyre!("invalid shard {s}, expected i/n such as 2/4"))?;
+        if count == 0 || index == 0 || index > count {
+            return Err(eyre!("invalid shard {s}, i must be between 1 and n"));
+        }
+
+        Ok(Self { index, count })
+    }
+
+    pub fn is_whole(&self) -> bool {
+        self.count == 1
+    }
+
+    /// The candidate indices of this slice of a keyspace with `total` candidates. The slices of
+    /// all shards cover the keyspace without overlapping.
+    pub fn range(&self, total: usize) -> Range<usize> {
+        let bound = |i: usize| (total as u128 * i as u128 / self.count as u128) as usize;
+        bound(self.index - 1)..bound(self.index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infers_from_siblings() {
+        let siblings = BTreeSet::from([
+            "exa*p*e.com".to_string(),
+            "e*am*le.com".to_string(),
+            "ex*mple.co".to_string(),
+        ]);
+
+        let charsets = wildcard_charsets("exa*p*e.com", &siblings);
+
+        assert_eq!(charsets, vec![b"m".to_vec(), b"l".to_vec()]);
+        assert_eq!(keyspace_size(&charsets), 1);
+        assert_eq!(
+            keyspace_size(&wildcard_charsets("exa*p*e.com", &BTreeSet::new())),
+            36 * 36
+        );
+    }
+
+    #[test]
+    fn shards_cover_keyspace() {
+        assert_eq!(Shard::parse("2/3").unwrap(), Shard { index: 2, count: 3 });
+        assert!(Shard::parse("0/3").is_err());
+        assert!(Shard::parse("4/3").is_err());
+        assert!(Shard::parse("3").is_err());
+
+        let ranges = (1..=3)
+            .map(|index| Shard { index, count: 3 }.range(10))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, vec![0..3, 3..6, 6..10]);
+        assert_eq!(Shard::default().range(10), 0..10);
+    }
+}
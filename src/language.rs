@@ -0,0 +1,164 @@
+/// Common words that tell apart the languages written in latin script. Short comments rarely
+/// contain more than one or two of them, so these are the words most likely to show up in a
+/// block reason.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the",
+            "and",
+            "of",
+            "to",
+            "is",
+            "for",
+            "with",
+            "no",
+            "not",
+            "are",
+            "this",
+            "by",
+            "harassment",
+            "spam",
+            "hate",
+            "racism",
+            "instance",
+            "users",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "und",
+            "der",
+            "die",
+            "das",
+            "ist",
+            "nicht",
+            "mit",
+            "von",
+            "für",
+            "auf",
+            "ein",
+            "eine",
+            "keine",
+            "belästigung",
+            "rassismus",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "et",
+            "le",
+            "la",
+            "les",
+            "des",
+            "est",
+            "pas",
+            "pour",
+            "une",
+            "du",
+            "avec",
+            "harcèlement",
+            "racisme",
+            "haine",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "y", "el", "los", "las", "del", "es", "por", "para", "con", "una", "acoso", "racismo",
+            "odio",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "en",
+            "het",
+            "een",
+            "van",
+            "niet",
+            "voor",
+            "met",
+            "zijn",
+            "op",
+            "intimidatie",
+            "racisme",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "e", "il", "di", "che", "non", "per", "gli", "della", "molestie", "razzismo",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "e", "o", "os", "do", "da", "não", "para", "com", "uma", "assédio", "racismo",
+        ],
+    ),
+];
+
+/// Guesses the language of a block comment, as an ISO 639-1 code. Languages with their own
+/// script are recognized by it, latin script by common words. Returns `None` when there is
+/// nothing to go on, such as a comment consisting of a single unknown word.
+pub fn detect(text: &str) -> Option<&'static str> {
+    let mut han = 0;
+    for c in text.chars() {
+        match c {
+            // kana only occurs in japanese, which also uses han characters
+            '\u{3040}'..='\u{30ff}' => return Some("ja"),
+            '\u{ac00}'..='\u{d7af}' | '\u{1100}'..='\u{11ff}' => return Some("ko"),
+            '\u{0400}'..='\u{04ff}' => return Some("ru"),
+            '\u{0600}'..='\u{06ff}' => return Some("ar"),
+            '\u{0590}'..='\u{05ff}' => return Some("he"),
+            '\u{0e00}'..='\u{0e7f}' => return Some("th"),
+            '\u{0370}'..='\u{03ff}' => return Some("el"),
+            '\u{4e00}'..='\u{9fff}' => han += 1,
+            _ => {}
+        }
+    }
+    if han > 0 {
+        return Some("zh");
+    }
+
+    let lower = text.to_lowercase();
+    let words = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut best = None;
+    let mut best_hits = 0;
+    for (language, stopwords) in STOPWORDS {
+        let hits = words.iter().filter(|w| stopwords.contains(w)).count();
+        if hits > best_hits {
+            best = Some(*language);
+            best_hits = hits;
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_languages() {
+        assert_eq!(detect("スパム"), Some("ja"));
+        assert_eq!(detect("垃圾信息"), Some("zh"));
+        assert_eq!(detect("스팸"), Some("ko"));
+        assert_eq!(detect("Harassment and hate speech"), Some("en"));
+        assert_eq!(
+            detect("Keine Moderation, Rassismus und Belästigung"),
+            Some("de")
+        );
+        assert_eq!(detect("Racisme et harcèlement"), Some("fr"));
+        assert_eq!(detect("fediblock"), None);
+        assert_eq!(detect(""), None);
+    }
+}
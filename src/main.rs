@@ -1,129 +1,1047 @@
+mod annotation;
 mod api;
+mod archive;
+mod audit;
+mod autosave;
+mod availability;
+mod candidates;
+mod cli;
+mod cluster;
+mod config;
+mod crawl;
 mod database;
+mod digest_index;
+mod discover;
+mod enrich;
+mod error;
+mod export;
+mod fetch;
+mod filter;
+mod fsck;
+mod hooks;
+mod http_cache;
+mod interrupt;
+mod jobs;
+mod journal;
+mod keyspace;
+mod language;
+mod metrics;
+mod output;
+mod plausible;
+mod priority;
+mod prune;
+mod publish;
+mod query;
+mod redact;
+mod report;
+mod resolutions;
+mod resolve;
+mod run_report;
+mod score;
+mod scrape;
+mod seeds;
+mod severity;
+mod sha;
+mod share;
+mod signing;
+mod source;
+mod suffix;
+mod throttle;
+mod translate;
 
-use std::{collections::BTreeSet, time::Instant};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+    process::ExitCode,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use api::DomainBlock;
-use color_eyre::Result;
+use api::{DomainBlock, DomainBlockSeverity, Limitation};
+use autosave::Autosave;
+use candidates::CandidateSource;
+use cli::Args;
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use config::Config;
 use database::{DatabaseAccess, DatabaseInstance, DatabaseObject};
+use digest_index::DigestIndex;
+use enrich::ProbeResult;
+use error::CliError;
+use fetch::{FetchOutcome, FetchSummary};
+use jobs::JobState;
+use output::{Color, Table};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use sha::PatternMatcher;
 use sha2::{Digest, Sha256};
 
 const DATABASE_FILE: &str = "database.json";
+const CONFIG_FILE: &str = "config.json";
+const DATABASE_NAMESPACE: &str = "mastodon-blocks";
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tokio::runtime::Builder::new_current_thread()
+// these never modify the database, so they shouldn't overwrite changes made by other processes
+const READ_ONLY_VERBS: &[&str] = &[
+    "show",
+    "conflicts",
+    "compare",
+    "audit",
+    "probe",
+    "cluster",
+    "explain",
+    "lookup",
+    "estimate",
+    "timeline",
+    "health",
+    "metrics",
+    "events",
+    "publish",
+    "export",
+    "query",
+    "share",
+    "keygen",
+];
+
+const DEFAULT_SEEDS: &[&str] = &[
+    "mastodon.social",
+    // "pawoo.net",
+    "mstdn.jp",
+    "mastodon.cloud",
+    "mastodon.online",
+    // "counter.social",
+    "mstdn.social",
+    "mas.to",
+    // "gc2.jp",
+    // "mastodon.world",
+    "home.social",
+];
+
+fn main() -> ExitCode {
+    if let Err(e) = color_eyre::install() {
+        eprintln!("Failed to install error handler: {e}");
+    }
+
+    let runtime = match tokio::runtime::Builder::new_current_thread()
         .enable_all()
-        .build()?
-        .block_on(async {
-            if let Err(e) = real_main().await {
-                panic!("Error in main: {e}");
-            }
-        });
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Failed to start async runtime: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
 
-    Ok(())
+    match runtime.block_on(real_main()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e:?}");
+            ExitCode::from(e.exit_code())
+        }
+    }
 }
 
-async fn real_main() -> Result<()> {
-    let arg = std::env::args().collect::<Vec<_>>();
-    if arg.len() < 2 {
-        println!("Available verbs: fetch, process, crack, show");
+async fn real_main() -> Result<(), CliError> {
+    let mut arg = std::env::args().skip(1);
+    let Some(verb) = arg.next() else {
+        println!("Available verbs: discover, fetch, process, resolve, replay, prune, crack, enrich, jobs, estimate, show, conflicts, compare, audit, annotate, probe, cluster, explain, lookup, timeline, publish, export, health, metrics, events, import, share, keygen, query, translate, run, fsck");
         return Ok(());
+    };
+    let mut args = Args::new(arg);
+    output::init(args.option("--color").map_err(CliError::usage)?.as_deref())
+        .map_err(CliError::usage)?;
+
+    let cache_dir = args.option("--cache-dir").map_err(CliError::usage)?;
+    let max_rps = args
+        .option("--max-rps")
+        .map_err(CliError::usage)?
+        .map(|s| s.parse::<f64>())
+        .transpose()
+        .map_err(|e| CliError::Usage(format!("Invalid --max-rps: {e}")))?;
+    if max_rps.is_some_and(|rps| rps.is_nan() || rps <= 0.) {
+        return Err(CliError::Usage("--max-rps must be positive".into()));
+    }
+    // the interval between requests has to fit in a Duration
+    if max_rps.is_some_and(|rps| Duration::try_from_secs_f64(1. / rps).is_err()) {
+        return Err(CliError::Usage("--max-rps is too small".into()));
     }
 
-    let mut db = DatabaseInstance::load(DATABASE_FILE)
-        .unwrap_or_default()
-        .use_namespace("mastodon-blocks");
+    let mut config = Config::load(CONFIG_FILE)?;
+    throttle::init(max_rps, config.host_interval_secs.map(Duration::from_secs));
+    if cache_dir.is_some() {
+        config.cache_dir = cache_dir;
+    }
+    hooks::init(&config.hooks);
+    severity::init(&config.severity_map);
+    interrupt::install()?;
+
+    let mut db = load_database()?;
+
+    let started_at = unix_now();
+    let first_event = journal::next_id(&mut db).map_err(CliError::Database)?;
+
+    // progress is saved even if the verb failed halfway through
+    let result = run_verb(&verb, args, &mut db, &config).await;
+    if config.crawl_resolved
+        && !READ_ONLY_VERBS.contains(&verb.as_str())
+        && !interrupt::is_interrupted()
+    {
+        // probing the resolved domains needs the network, so it happens after the verb
+        if let Err(e) = crawl::enqueue_resolved(&mut db, &config, first_event).await {
+            println!("Error while trying to queue resolved domains: {e}");
+        }
+    }
+    if let Some(reports_dir) = &config.reports_dir {
+        if run_report::REPORTED_VERBS.contains(&verb.as_str()) {
+            let written = run_report::write(
+                &mut db,
+                reports_dir,
+                &verb,
+                started_at,
+                first_event,
+                result.is_ok(),
+            );
+            if let Err(e) = written {
+                println!("Error while trying to write the run report: {e}");
+            }
+        }
+    }
+    if !READ_ONLY_VERBS.contains(&verb.as_str()) {
+        db.pop_namespace()
+            .save(DATABASE_FILE)
+            .map_err(CliError::Database)?;
+    }
 
-    match arg.get(1).unwrap().as_str() {
+    result?;
+    if interrupt::is_interrupted() {
+        return Err(CliError::Interrupted);
+    }
+
+    Ok(())
+}
+
+fn load_database() -> Result<DatabaseAccess, CliError> {
+    // don't silently start over when an existing database can't be read
+    let db = if Path::new(DATABASE_FILE).exists() {
+        DatabaseInstance::load(DATABASE_FILE).map_err(CliError::Database)?
+    } else {
+        DatabaseInstance::default()
+    };
+
+    Ok(db.use_namespace(DATABASE_NAMESPACE))
+}
+
+async fn run_verb(
+    verb: &str,
+    mut args: Args,
+    db: &mut DatabaseAccess,
+    config: &Config,
+) -> Result<(), CliError> {
+    match verb {
+        "discover" => {
+            println!("Discovering instances through instance directories");
+            discover::discover(db, config).await;
+        }
         "fetch" => {
-            println!("Loading blocklist from seed domains");
-            try_load_blocklist(&mut db, "mastodon.social").await;
-            // try_load_blocklist(&mut db, "pawoo.net").await;
-            try_load_blocklist(&mut db, "mstdn.jp").await;
-            try_load_blocklist(&mut db, "mastodon.cloud").await;
-            try_load_blocklist(&mut db, "mastodon.online").await;
-            // try_load_blocklist(&mut db, "counter.social").await;
-            try_load_blocklist(&mut db, "mstdn.social").await;
-            try_load_blocklist(&mut db, "mas.to").await;
-            // try_load_blocklist(&mut db, "gc2.jp").await;
-            // try_load_blocklist(&mut db, "mastodon.world").await;
-            try_load_blocklist(&mut db, "home.social").await;
+            let discovered = args.flag("--discovered");
+            let fail_fast = args.flag("--fail-fast");
+            let seeds_file = args.option("--seeds-file").map_err(CliError::usage)?;
+            let presets = args
+                .option("--seeds")
+                .map_err(CliError::usage)?
+                .map(|names| {
+                    names
+                        .split(',')
+                        .map(|name| {
+                            seeds::preset(name.trim()).ok_or_else(|| {
+                                CliError::Usage(format!(
+                                    "Unknown seed preset {name}, available presets: {}",
+                                    seeds::preset_names().join(", ")
+                                ))
+                            })
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .transpose()?
+                .unwrap_or_default();
+            let min_success = args
+                .option("--min-success")
+                .map_err(CliError::usage)?
+                .map(|s| s.parse::<usize>())
+                .transpose()
+                .map_err(|e| CliError::Usage(format!("Invalid --min-success: {e}")))?;
+            let max_age = args
+                .option("--max-age")
+                .map_err(CliError::usage)?
+                .map(|s| cli::parse_duration(&s))
+                .transpose()
+                .map_err(CliError::usage)?;
+            let mut explicit = args.positional().map_err(CliError::usage)?;
+            explicit.extend(presets.concat().iter().map(|s| s.to_string()));
+
+            let mut seeds = collect_seeds(db, config, discovered, seeds_file, explicit)
+                .map_err(CliError::Fetch)?;
+            if let Some(max_age) = max_age {
+                let fetched_after = unix_now().saturating_sub(max_age.as_secs());
+                let seed_count = seeds.len();
+                let recent = fetch::fetched_since(db, fetched_after).map_err(CliError::Fetch)?;
+                seeds.retain(|s| !recent.contains(s));
+                println!(
+                    "Skipping {} instances fetched within {max_age:?}",
+                    seed_count - seeds.len()
+                );
+            }
+            let summary = fetch_seeds(db, config, &seeds, fail_fast, None)
+                .await
+                .map_err(CliError::Fetch)?;
+            summary.print();
+            hooks::fetch_completed(&summary);
+
+            // whatever was fetched gets processed on the next run
+            if interrupt::is_interrupted() {
+                return Ok(());
+            }
+
+            if fail_fast && !summary.failed.is_empty() {
+                return Err(CliError::Fetch(eyre!("Stopped after the first failure")));
+            }
+            if let Some(min_success) = min_success {
+                if summary.succeeded() < min_success {
+                    return Err(CliError::Fetch(eyre!(
+                        "Only {} instances were fetched successfully, expected at least {min_success}",
+                        summary.succeeded()
+                    )));
+                }
+            }
 
             println!("Updating database");
-            process_db(&mut db)?;
+            process_db(db, config, false).map_err(CliError::Process)?;
         }
         "process" => {
+            let full = args.flag("--full");
+            args.positional().map_err(CliError::usage)?;
+
             println!("Updating database");
-            process_db(&mut db)?;
+            process_db(db, config, full).map_err(CliError::Process)?;
+        }
+        "resolve" => {
+            args.positional().map_err(CliError::usage)?;
+
+            let resolved = resolve::resolve(db).map_err(CliError::Process)?;
+            println!("Resolved {resolved} domains using other known domains");
+        }
+        "replay" => {
+            let Some(archive_dir) = &config.archive_dir else {
+                return Err(CliError::Usage("No archive_dir configured".into()));
+            };
+
+            let domains = args.positional().map_err(CliError::usage)?;
+            fetch::replay(db, archive_dir, domains).map_err(CliError::Fetch)?;
+
+            println!("Updating database");
+            process_db(db, config, false).map_err(CliError::Process)?;
+        }
+        "enrich" => {
+            let refresh = args.flag("--refresh");
+            let probe = args.flag("--probe");
+            let instances = args.flag("--instances");
+            args.positional().map_err(CliError::usage)?;
+
+            enrich::enrich(db, config, refresh, probe, instances).await?;
+        }
+        "prune" => {
+            args.positional().map_err(CliError::usage)?;
+            prune::prune(db, config.archive_dir.as_deref(), &config.retention)?;
         }
         "crack" => {
-            crack(&mut db)?;
+            let time_budget = args
+                .option("--time-budget")
+                .map_err(CliError::usage)?
+                .map(|s| cli::parse_duration(&s))
+                .transpose()
+                .map_err(CliError::usage)?;
+            let mut regex = |name: &str| -> Result<Option<regex::Regex>, CliError> {
+                args.option(name)
+                    .map_err(CliError::usage)?
+                    .map(|r| filter::PatternFilter::compile(&r))
+                    .transpose()
+                    .map_err(|e| CliError::Usage(format!("Invalid {name} regex: {e}")))
+            };
+            let patterns = filter::PatternFilter {
+                include: regex("--match")?,
+                exclude: regex("--exclude")?,
+            };
+            let shard = args
+                .option("--shard")
+                .map_err(CliError::usage)?
+                .map(|s| keyspace::Shard::parse(&s))
+                .transpose()
+                .map_err(CliError::usage)?
+                .unwrap_or_default();
+            let background = args.flag("--background");
+            args.positional().map_err(CliError::usage)?;
+
+            if background {
+                priority::lower();
+            }
+
+            crack(db, config, time_budget, &patterns, shard).map_err(CliError::Crack)?;
+        }
+        "run" => {
+            let mut budget = |name: &str| -> Result<Option<Duration>, CliError> {
+                args.option(name)
+                    .map_err(CliError::usage)?
+                    .map(|s| cli::parse_duration(&s))
+                    .transpose()
+                    .map_err(CliError::usage)
+            };
+            let fetch_budget = budget("--fetch-budget")?;
+            let crack_budget = budget("--crack-budget")?;
+            args.positional().map_err(CliError::usage)?;
+
+            run_pipeline(db, config, fetch_budget, crack_budget).await?;
+        }
+        "fsck" => {
+            let repair = args.flag("--repair");
+            args.positional().map_err(CliError::usage)?;
+
+            let left = fsck::fsck(db, repair).map_err(CliError::Database)?;
+            if left > 0 {
+                return Err(CliError::Database(eyre!(
+                    "{left} problems left, run with --repair to fix what can be fixed"
+                )));
+            }
+        }
+        "jobs" => {
+            let priority = args
+                .option("--priority")
+                .map_err(CliError::usage)?
+                .map(|s| s.parse::<i32>())
+                .transpose()
+                .map_err(|e| CliError::Usage(format!("Invalid --priority: {e}")))?;
+            let positional = args.positional().map_err(CliError::usage)?;
+
+            match positional
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .as_slice()
+            {
+                [] | ["list"] => {
+                    jobs::sync_jobs(db)?;
+                    jobs::print_queue(db)?;
+                }
+                ["enqueue", digest] => {
+                    jobs::sync_jobs(db)?;
+                    let updated = jobs::enqueue(db, digest, priority.unwrap_or_default())?;
+                    println!("Queued {updated} jobs");
+                }
+                ["cancel", digest] => {
+                    let cancelled = jobs::cancel(db, digest)?;
+                    println!("Cancelled {cancelled} jobs");
+                }
+                _ => {
+                    return Err(CliError::Usage(
+                        "Usage: jobs [list | enqueue <digest> [--priority <n>] | cancel <digest>]"
+                            .into(),
+                    ))
+                }
+            }
         }
         "show" => {
-            show(&mut db)?;
+            let no_pager = args.flag("--no-pager");
+            let filter = entry_filter(&mut args, config)?;
+            args.positional().map_err(CliError::usage)?;
+
+            show(db, config, &filter, no_pager)?;
+        }
+        "conflicts" => {
+            args.positional().map_err(CliError::usage)?;
+            report::conflicts(db)?;
+        }
+        "annotate" => {
+            let tags = args.option("--tag").map_err(CliError::usage)?;
+            let untags = args.option("--untag").map_err(CliError::usage)?;
+            let split = |tags: Option<String>| {
+                tags.map(|t| {
+                    t.split(',')
+                        .map(|t| t.trim().to_string())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+            };
+            let (domain, note) = match args.positional().map_err(CliError::usage)?.as_slice() {
+                [domain] => (domain.clone(), None),
+                [domain, note] => (domain.clone(), Some(note.clone())),
+                _ => {
+                    return Err(CliError::Usage(
+                        "Usage: annotate <domain or digest> [note] [--tag a,b] [--untag a,b]"
+                            .into(),
+                    ))
+                }
+            };
+
+            annotation::annotate(db, &domain, note, &split(tags), &split(untags))?;
+        }
+        "compare" => {
+            let [a, b] = args
+                .positional()
+                .map_err(CliError::usage)?
+                .try_into()
+                .map_err(|_| CliError::Usage("Usage: compare <instance-a> <instance-b>".into()))?;
+
+            report::compare(db, &a, &b)?;
+        }
+        "probe" => {
+            let domains = args.positional().map_err(CliError::usage)?;
+            if domains.is_empty() {
+                return Err(CliError::Usage("Usage: probe <domain...>".into()));
+            }
+
+            let fetcher = fetch::HttpFetcher::new(config).map_err(CliError::Fetch)?;
+            availability::probe(&fetcher, &domains).await;
+        }
+        "audit" => {
+            let mine = args.option("--mine").map_err(CliError::usage)?;
+            let min_blockers = args
+                .option("--min-blockers")
+                .map_err(CliError::usage)?
+                .map(|s| s.parse::<usize>())
+                .transpose()
+                .map_err(|e| CliError::Usage(format!("Invalid --min-blockers: {e}")))?
+                .unwrap_or(5);
+            args.positional().map_err(CliError::usage)?;
+            let Some(mine) = mine else {
+                return Err(CliError::Usage(
+                    "Usage: audit --mine <blocklist.csv> [--min-blockers <count>]".into(),
+                ));
+            };
+
+            let content = std::fs::read_to_string(&mine).context("read own blocklist")?;
+            let own = audit::parse_blocklist(&content).context("parse own blocklist")?;
+            audit::audit(db, own, min_blockers)?;
+        }
+        "cluster" => {
+            let max_distance = args
+                .option("--max-distance")
+                .map_err(CliError::usage)?
+                .map(|s| s.parse::<f64>())
+                .transpose()
+                .map_err(|e| CliError::Usage(format!("Invalid --max-distance: {e}")))?;
+            args.positional().map_err(CliError::usage)?;
+
+            cluster::print_clusters(db, max_distance.unwrap_or(cluster::DEFAULT_MAX_DISTANCE))?;
+        }
+        "estimate" => {
+            args.positional().map_err(CliError::usage)?;
+            report::estimate(db)?;
+        }
+        "lookup" => {
+            let prefix = args.option("--digest").map_err(CliError::usage)?;
+            args.positional().map_err(CliError::usage)?;
+            let Some(prefix) = prefix else {
+                return Err(CliError::Usage(
+                    "Usage: lookup --digest <hex-prefix>".into(),
+                ));
+            };
+
+            report::lookup_digest(db, &prefix)?;
+        }
+        "explain" => {
+            let [domain] = args
+                .positional()
+                .map_err(CliError::usage)?
+                .try_into()
+                .map_err(|_| CliError::Usage("Usage: explain <domain or digest>".into()))?;
+
+            report::explain(db, &domain)?;
+        }
+        "timeline" => {
+            let Some(archive_dir) = &config.archive_dir else {
+                return Err(CliError::Usage("No archive_dir configured".into()));
+            };
+            let [domain] = args
+                .positional()
+                .map_err(CliError::usage)?
+                .try_into()
+                .map_err(|_| CliError::Usage("Usage: timeline <domain or digest>".into()))?;
+
+            report::timeline(archive_dir, &domain)?;
+        }
+        "translate" => {
+            let Some(command) = &config.translate_command else {
+                return Err(CliError::Usage("No translate_command configured".into()));
+            };
+
+            let refresh = args.flag("--refresh");
+            args.positional().map_err(CliError::usage)?;
+            translate::translate(db, config, command, refresh)?;
+        }
+        "health" => {
+            fetch::health(db)?;
+        }
+        "events" => {
+            let follow = args.flag("--follow");
+            let since = args
+                .option("--since")
+                .map_err(CliError::usage)?
+                .map(|s| s.parse::<u64>())
+                .transpose()
+                .map_err(|e| CliError::Usage(format!("Invalid --since: {e}")))?;
+            args.positional().map_err(CliError::usage)?;
+
+            // other processes write to the database file, so it's re-read to find new events
+            let mut next = journal::print_events(db, since.unwrap_or_default())?;
+            while follow && !interrupt::is_interrupted() {
+                std::thread::sleep(Duration::from_secs(2));
+                next = journal::print_events(&mut load_database()?, next)?;
+            }
+        }
+        "import" => {
+            let [kind, path] = args
+                .positional()
+                .map_err(CliError::usage)?
+                .try_into()
+                .map_err(|_| {
+                    CliError::Usage(
+                        "Usage: import resolutions <file> | import share <file> \
+                         | import dataset <file>"
+                            .into(),
+                    )
+                })?;
+
+            match kind.as_str() {
+                "resolutions" => {
+                    let content = std::fs::read(&path).context("read resolutions file")?;
+                    let resolutions =
+                        serde_json::from_slice(&content).context("deserialize resolutions file")?;
+                    resolutions::apply_resolutions(db, resolutions, "import")?.print();
+                }
+                "share" => {
+                    let content = std::fs::read(&path).context("read share bundle")?;
+                    let bundle =
+                        serde_json::from_slice(&content).context("deserialize share bundle")?;
+                    share::import(db, bundle, "share")?.print();
+                }
+                "dataset" => {
+                    let content = std::fs::read(&path).context("read dataset")?;
+                    signing::check_file(&path, &content, &config.trusted_signers)?;
+                    let bundle = export::parse_dataset(&content)?;
+                    share::import(db, bundle, "dataset")?.print();
+                }
+                kind => return Err(CliError::Usage(format!("Unknown import kind: {kind}"))),
+            }
+        }
+        "publish" => {
+            let redaction = redaction(&mut args, config)?;
+            let filter = entry_filter(&mut args, config)?;
+            let [out_dir] = args
+                .positional()
+                .map_err(CliError::usage)?
+                .try_into()
+                .map_err(|_| CliError::Usage("Usage: publish <out-dir>".into()))?;
+
+            publish::publish(db, &out_dir, &redaction, &filter)?;
+        }
+        "export" => {
+            let format = args.option("--format").map_err(CliError::usage)?;
+            let output = args.option("--output").map_err(CliError::usage)?;
+            let sign = args.option("--sign").map_err(CliError::usage)?;
+            let redaction = redaction(&mut args, config)?;
+            let filter = entry_filter(&mut args, config)?;
+            let usage = || {
+                CliError::Usage(
+                    "Usage: export dataset [--format json|ndjson] [--output <file> [--sign <key-file>]] \
+                     | export sqlite <file> | export parquet <dir>"
+                        .into(),
+                )
+            };
+            let mut positional = args.positional().map_err(CliError::usage)?.into_iter();
+            let (Some(kind), file, None) =
+                (positional.next(), positional.next(), positional.next())
+            else {
+                return Err(usage());
+            };
+
+            let format = match format.as_deref() {
+                None => export::ExportFormat::Json,
+                Some(format) => export::ExportFormat::parse(format)
+                    .ok_or_else(|| CliError::Usage(format!("Unknown export format: {format}")))?,
+            };
+
+            match (kind.as_str(), file) {
+                ("dataset", None) => {
+                    let key = match sign {
+                        Some(_) if output.is_none() => {
+                            return Err(CliError::Usage("--sign needs --output".into()))
+                        }
+                        Some(path) => Some(signing::load_key(&path)?),
+                        None => None,
+                    };
+                    export::export_dataset(db, format, output.as_deref(), &redaction, &filter)?;
+                    if let (Some(key), Some(output)) = (&key, &output) {
+                        signing::sign_file(key, output)?;
+                    }
+                }
+                ("sqlite", Some(file)) => export::export_sqlite(db, &file, &redaction, &filter)?,
+                ("parquet", Some(dir)) => {
+                    export::export_parquet(db, Path::new(&dir), &redaction, &filter)?
+                }
+                ("dataset" | "sqlite" | "parquet", _) => return Err(usage()),
+                (kind, _) => return Err(CliError::Usage(format!("Unknown export kind: {kind}"))),
+            }
+        }
+        "query" => {
+            let format = args.option("--format").map_err(CliError::usage)?;
+            let filter = entry_filter(&mut args, config)?;
+            let [query] = args
+                .positional()
+                .map_err(CliError::usage)?
+                .try_into()
+                .map_err(|_| {
+                    CliError::Usage("Usage: query '<query>' [--format json|ndjson]".into())
+                })?;
+
+            let format =
+                match format.as_deref() {
+                    None | Some("table") => None,
+                    Some(format) => Some(export::ExportFormat::parse(format).ok_or_else(|| {
+                        CliError::Usage(format!("Unknown output format: {format}"))
+                    })?),
+                };
+            let expr = query::parse(&query).map_err(CliError::usage)?;
+            query::query(db, config, &expr, format, &filter)?;
+        }
+        "share" => {
+            let output = args.option("--output").map_err(CliError::usage)?;
+            let filter = entry_filter(&mut args, config)?;
+            args.positional().map_err(CliError::usage)?;
+
+            share::share(db, output.as_deref(), &filter)?;
+        }
+        "keygen" => {
+            let [path] = args
+                .positional()
+                .map_err(CliError::usage)?
+                .try_into()
+                .map_err(|_| CliError::Usage("Usage: keygen <key-file>".into()))?;
+
+            let public_key = signing::generate_key(&path)?;
+            println!("Wrote a signing key to {path}, its public key is {public_key}");
+        }
+        "metrics" => {
+            let output = args.option("--output").map_err(CliError::usage)?;
+            args.positional().map_err(CliError::usage)?;
+
+            match output {
+                Some(output) => metrics::write_file(db, &output)?,
+                None => print!("{}", metrics::render(db)?),
+            }
         }
         verb => {
-            println!("Unknown verb: {verb}");
+            return Err(CliError::Usage(format!("Unknown verb: {verb}")));
         }
     }
 
-    db.pop_namespace().save(DATABASE_FILE)?;
-
     Ok(())
 }
 
-async fn try_load_blocklist(db: &mut DatabaseAccess, domain: &str) {
-    let err = load_blocklist(db, domain).await;
+fn collect_seeds(
+    db: &mut DatabaseAccess,
+    config: &Config,
+    discovered: bool,
+    seeds_file: Option<String>,
+    explicit: Vec<String>,
+) -> Result<BTreeSet<String>> {
+    // explicitly passed domains replace the default seeds and queue
+    let mut seeds = BTreeSet::new();
+    if explicit.is_empty() && seeds_file.is_none() {
+        seeds.extend(DEFAULT_SEEDS.iter().map(|s| s.to_string()));
+        seeds.extend(crawl::queued_domains(db, config.crawl_batch_size)?);
+        seeds.extend(fetch::due_retries(db)?);
+    }
+    seeds.extend(explicit);
+    if let Some(seeds_file) = seeds_file {
+        seeds.extend(read_seeds_file(&seeds_file)?);
+    }
+    if discovered {
+        seeds.extend(discover::domain_corpus(db)?);
+    }
+
+    let seed_count = seeds.len();
+    seeds.retain(|s| config.fetch_filter.allows(s));
+    if seeds.len() != seed_count {
+        println!("Skipping {} filtered domains", seed_count - seeds.len());
+    }
 
-    if let Err(e) = err {
-        println!("Error while trying to load blocklist from {domain}: {e}");
+    let opted_out = crawl::opted_out_domains(db)?;
+    let seed_count = seeds.len();
+    seeds.retain(|s| !opted_out.contains(s));
+    if seeds.len() != seed_count {
+        println!(
+            "Skipping {} instances that opted out of crawling",
+            seed_count - seeds.len()
+        );
     }
+
+    Ok(seeds)
 }
 
-async fn load_blocklist(db: &mut DatabaseAccess, domain: &str) -> Result<()> {
-    let client = reqwest::Client::new();
+/// Reads `--redact-comments` and `--redact-keywords a,b`, adding to the configured keywords.
+fn redaction(args: &mut Args, config: &Config) -> Result<redact::Redaction, CliError> {
+    let drop_comments = args.flag("--redact-comments");
+    let mut keywords = config.redact_keywords.clone();
+    if let Some(extra) = args.option("--redact-keywords").map_err(CliError::usage)? {
+        keywords.extend(extra.split(',').map(|k| k.trim().to_string()));
+    }
 
-    // mstdn.jp requires a user agent or will serve a 404
-    let val: Vec<DomainBlock> = client
-        .get(format!("https://{domain}/api/v1/instance/domain_blocks"))
-        .header("user-agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/112.0.0.0 Safari/537.36")
-        .send()
-        .await?
-        .json()
-        .await?;
+    Ok(redact::Redaction {
+        drop_comments,
+        keywords,
+    })
+}
 
-    println!("Loaded {} blocklist items from {domain}", val.len());
+/// Reads `--tag a,b`, `--exclude-tag a,b` and `--comment-lang a,b`, and leaves out the ignored
+/// domains.
+fn entry_filter(args: &mut Args, config: &Config) -> Result<filter::EntryFilter, CliError> {
+    let mut list = |name: &str| -> Result<Vec<String>, CliError> {
+        Ok(args
+            .option(name)
+            .map_err(CliError::usage)?
+            .map(|tags| tags.split(',').map(|t| t.trim().to_string()).collect())
+            .unwrap_or_default())
+    };
 
-    db.set(MastodonBlockList {
-        domain: domain.to_string(),
-        list: val,
-    });
+    Ok(filter::EntryFilter {
+        include_tags: list("--tag")?,
+        exclude_tags: list("--exclude-tag")?,
+        comment_languages: list("--comment-lang")?,
+        ignored: config.ignored_digests(),
+    })
+}
+
+async fn fetch_seeds(
+    db: &mut DatabaseAccess,
+    config: &Config,
+    seeds: &BTreeSet<String>,
+    fail_fast: bool,
+    deadline: Option<Instant>,
+) -> Result<FetchSummary> {
+    println!("Loading blocklist from {} seed domains", seeds.len());
+    let fetcher = fetch::HttpFetcher::new(config)?;
+    let mut summary = FetchSummary::default();
+    let mut autosave = Autosave::new(config, DATABASE_FILE);
+    for (i, seed) in seeds.iter().enumerate() {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            println!(
+                "Fetch budget used up, {} instances are left for the next run",
+                seeds.len() - i
+            );
+            break;
+        }
+        if crawl::check_opt_out(db, config, seed).await? {
+            continue;
+        }
+
+        let outcome = fetch::try_load_blocklist(db, &fetcher, config, seed).await?;
+        crawl::mark_visited(db, seed, !matches!(outcome, FetchOutcome::Failed(_)))?;
+        summary.add(seed, outcome);
+        autosave.changed(db)?;
+
+        if (fail_fast && !summary.failed.is_empty()) || interrupt::is_interrupted() {
+            break;
+        }
+    }
+
+    metrics::update(db, |c| {
+        c.fetch_successes += summary.succeeded() as u64;
+        c.fetch_failures += summary.failed.len() as u64;
+        c.last_fetch_at = Some(unix_now());
+    })?;
+
+    Ok(summary)
+}
+
+/// Runs `fetch`, `process` and `crack` one after another and prints what the run did. The budgets
+/// limit how long fetching and cracking may take, what is left is picked up by the next run.
+async fn run_pipeline(
+    db: &mut DatabaseAccess,
+    config: &Config,
+    fetch_budget: Option<Duration>,
+    crack_budget: Option<Duration>,
+) -> Result<(), CliError> {
+    let first_event = journal::next_id(db).map_err(CliError::Database)?;
+    let mut stages = vec![];
+
+    let started = Instant::now();
+    let seeds = collect_seeds(db, config, false, None, vec![]).map_err(CliError::Fetch)?;
+    let deadline = fetch_budget.and_then(|budget| started.checked_add(budget));
+    let summary = fetch_seeds(db, config, &seeds, false, deadline)
+        .await
+        .map_err(CliError::Fetch)?;
+    summary.print();
+    hooks::fetch_completed(&summary);
+    stages.push(("fetch", started.elapsed()));
+    if interrupt::is_interrupted() {
+        return Ok(());
+    }
+
+    let started = Instant::now();
+    println!("Updating database");
+    process_db(db, config, false).map_err(CliError::Process)?;
+    stages.push(("process", started.elapsed()));
+
+    let started = Instant::now();
+    let patterns = filter::PatternFilter::default();
+    crack(
+        db,
+        config,
+        crack_budget,
+        &patterns,
+        keyspace::Shard::default(),
+    )
+    .map_err(CliError::Crack)?;
+    stages.push(("crack", started.elapsed()));
+    if interrupt::is_interrupted() {
+        return Ok(());
+    }
+
+    let mut table = Table::new(&["Stage", "Took"]);
+    for (stage, took) in stages {
+        table.add_row(vec![
+            (stage.to_string(), None),
+            (format_age(took.as_secs()), None),
+        ]);
+    }
+    table.print();
+
+    let (mut added, mut new_blocks, mut unblocked, mut resolved) = (0, 0, 0, 0);
+    for entry in journal::events_since(db, first_event).map_err(CliError::Database)? {
+        match entry.event {
+            journal::Event::InstanceAdded { .. } => added += 1,
+            journal::Event::NewBlock { .. } => new_blocks += 1,
+            journal::Event::Unblocked { .. } => unblocked += 1,
+            journal::Event::Resolved { .. } => resolved += 1,
+        }
+    }
+    println!(
+        "Fetched {} instances ({} failed), {added} new instances, {new_blocks} new blocks, \
+         {unblocked} removed blocks, {resolved} resolved domains",
+        summary.succeeded(),
+        summary.failed.len()
+    );
 
     Ok(())
 }
 
-fn process_db(db: &mut DatabaseAccess) -> Result<()> {
+/// Reads a file with one domain per line. Empty lines and `#` comments are ignored.
+fn read_seeds_file(path: &str) -> Result<Vec<String>> {
+    let content = std::fs::read_to_string(path).context("read seeds file")?;
+
+    Ok(content
+        .lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_lowercase())
+        .collect())
+}
+
+fn process_db(db: &mut DatabaseAccess, config: &Config, full: bool) -> Result<()> {
+    let ignored = config.ignored_digests();
     let things = db.iter_keys::<MastodonBlockList>().collect::<Vec<_>>();
+    let total = things.len();
+    let mut processed = 0;
     for thing in things {
-        let item = db.get::<MastodonBlockList>(&thing)?.unwrap();
-        for blocked_item in item.list {
+        let mut item = db.get::<MastodonBlockList>(&thing)?.unwrap();
+        if item.processed && !full {
+            continue;
+        }
+
+        // the instance may have stopped blocking domains it blocked before
+        for digest in std::mem::take(&mut item.removed) {
+            if let Some(mut entry) = db.get::<DomainEntry>(&digest)? {
+                if entry.blocked_by.remove(&item.domain).is_some() {
+                    db.set(entry);
+                }
+            }
+        }
+
+        for blocked_item in item.list.iter().cloned() {
+            if ignored.contains(&blocked_item.digest.to_lowercase()) {
+                continue;
+            }
+
+            let blocked_by = BlockedBy {
+                severity: blocked_item.severity,
+                original_severity: blocked_item.original_severity.clone(),
+                limitations: blocked_item.limitations.clone(),
+                comment: blocked_item.comment.clone(),
+                comment_language: blocked_item
+                    .comment
+                    .as_deref()
+                    .and_then(language::detect)
+                    .map(str::to_string),
+            };
+
             // TODO: should update instead
             let mut domain: DomainEntry = blocked_item.try_into()?;
+            domain.blocked_by.insert(item.domain.clone(), blocked_by);
 
             if let Some(existing) = db.get::<DomainEntry>(&domain.get_id())? {
+                // an instance that doesn't obfuscate reveals the domain others only published
+                // obfuscated, which resolves it for every blocklist and archived snapshot
+                if existing.known_domain.is_none() {
+                    if let Some(revealed) = &domain.known_domain {
+                        journal::record(
+                            db,
+                            journal::Event::Resolved {
+                                digest: domain.get_id().to_string(),
+                                domain: revealed.clone(),
+                                source: format!("blocklist:{}", item.domain),
+                            },
+                        )?;
+                    }
+                }
                 domain = domain.merge(existing);
             }
 
             db.set(domain);
         }
+
+        item.processed = true;
+        db.set(item);
+        processed += 1;
+    }
+    println!("Processed {processed}/{total} changed blocklists");
+    metrics::update(db, |c| c.last_process_at = Some(unix_now()))?;
+
+    let scraped = scrape::apply_scraped(db)?;
+    if scraped > 0 {
+        println!("Resolved {scraped} domains using scraped about pages");
+    }
+
+    let resolved = resolve::resolve(db)?;
+    if resolved > 0 {
+        println!("Resolved {resolved} domains using other known domains");
     }
 
     Ok(())
 }
 
-fn crack(db: &mut DatabaseAccess) -> Result<()> {
+/// Cracks queued jobs until they run out, or until `time_budget` is used up. Only jobs whose
+/// pattern is allowed by `patterns` are cracked, the others stay queued.
+fn crack(
+    db: &mut DatabaseAccess,
+    config: &Config,
+    time_budget: Option<Duration>,
+    patterns: &filter::PatternFilter,
+    shard: keyspace::Shard,
+) -> Result<()> {
+    let run_deadline = time_budget.and_then(|budget| Instant::now().checked_add(budget));
+    metrics::update(db, |c| c.last_crack_at = Some(unix_now()))?;
+
     let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
     let mut entries = keys
         .into_iter()
@@ -137,121 +1055,425 @@ fn crack(db: &mut DatabaseAccess) -> Result<()> {
         num_total
     );
 
+    let blocker_counts = publish::blockers_by_digest(db)?
+        .into_iter()
+        .map(|(digest, blockers)| (digest, blockers.len()))
+        .collect::<HashMap<_, _>>();
+    let policy_for =
+        |digest: &str| config.crack_policy(blocker_counts.get(digest).copied().unwrap_or_default());
+
+    // cheap dictionary pass over every domain we know of before brute-forcing anything
+    let index = DigestIndex::new(&entries);
+    let dictionary = discover::domain_corpus(db)?
+        .into_iter()
+        .filter_map(|d| Some((index.lookup(&d)?, d)))
+        .collect::<HashMap<_, _>>();
+    for entry in entries {
+        if !policy_for(&entry.get_id()).dictionary {
+            continue;
+        }
+
+        if let Some(found) = dictionary.get(&entry.digest) {
+            println!("{}: found {found} in dictionary", entry.get_id());
+            store_resolution(db, entry, found.clone(), "dictionary")?;
+        }
+    }
+
     // TODO: merge domains where multiple partial domains are known
 
-    entries.sort_by_key(|x| {
-        x.partial_domains
-            .iter()
-            .map(|d| d.chars().filter(|c| *c == '*').count())
-            .min()
-    });
+    let (created, requeued, removed) = jobs::sync_jobs(db)?;
+    let mut queue = jobs::queued_jobs(db)?;
+    println!(
+        "Created {created} crack jobs and requeued {requeued} outdated exhausted jobs, {} jobs are queued",
+        queue.len()
+    );
+    if removed > 0 {
+        println!("Removed {removed} crack jobs of digests that are no longer in the database");
+    }
+    let queued = queue.len();
+    queue.retain(|job| patterns.allows(&job.pattern));
+    if queue.len() < queued {
+        println!(
+            "Skipping {} jobs not matching --match or matching --exclude",
+            queued - queue.len()
+        );
+    }
+
+    let wordlist = config
+        .crack_wordlist
+        .as_deref()
+        .map(candidates::Wordlist::load)
+        .transpose()?;
+
+    let mut autosave = Autosave::new(config, DATABASE_FILE);
+    for job in queue {
+        if run_deadline.is_some_and(|d| Instant::now() >= d) {
+            println!("Time budget used up, the remaining jobs stay queued");
+            break;
+        }
+
+        // the entry may have been removed or moved to another key since the queue was read
+        let Some(entry) = db.get::<DomainEntry>(&job.digest)? else {
+            println!(
+                "{} is no longer in the database, cancelling its job",
+                job.digest
+            );
+            jobs::set_state(db, job, JobState::Cancelled);
+            continue;
+        };
+
+        // another job for the same digest may have found it already
+        if entry.known_domain.is_some() {
+            jobs::set_state(db, job, JobState::Solved);
+            continue;
+        }
+
+        let policy = policy_for(&job.digest);
+        if policy
+            .max_wildcards
+            .is_some_and(|max| job.wildcard_count() > max)
+        {
+            continue;
+        }
+
+        // another run cracking the same shard finished it already, the other shards are left
+        let charsets = keyspace::wildcard_charsets(&job.pattern, &entry.partial_domains);
+        if job.is_shard_finished(&charsets, shard) {
+            continue;
+        }
+
+        println!("{}: {}", job.digest, job.pattern);
+        jobs::set_state(db, job.clone(), JobState::Running);
+        let now = Instant::now();
+        let job_deadline = policy.max_seconds.map(|s| now + Duration::from_secs(s));
+        let running = metrics::RunningJob {
+            digest: job.digest.clone(),
+            pattern: job.pattern.clone(),
+            keyspace: keyspace::keyspace_size(&charsets) as f64 / shard.count as f64,
+            started_at: unix_now(),
+        };
+        metrics::set_crack_job(db, config.metrics_file.as_deref(), Some(running))?;
+        let target = candidates::Target {
+            pattern: &job.pattern,
+            charsets: &charsets,
+            digest: entry.digest,
+            deadline: job_deadline.into_iter().chain(run_deadline).min(),
+            shard,
+            resume_at: job.resume_at(&charsets, shard),
+        };
 
-    for entry in &entries {
-        for d in &entry.partial_domains {
-            println!("{}: {d}", entry.get_id());
-            let now = Instant::now();
-            let found = brute_force(d, entry.digest);
-            let elapsed = Instant::now() - now;
-            println!("> Found: {found:?} in {elapsed:?}");
-
-            if let Some(found) = found {
-                let mut domain = db.get::<DomainEntry>(&entry.get_id())?.unwrap();
-                domain.known_domain = Some(found);
-                db.set(domain);
-
-                // TODO: not ideal
-                db.clone().pop_namespace().save(DATABASE_FILE)?;
+        let mut pipeline: Vec<&dyn CandidateSource> = vec![];
+        if policy.plausible {
+            pipeline.push(&candidates::Plausible);
+        }
+        if let Some(wordlist) = wordlist.as_ref().filter(|_| policy.dictionary) {
+            pipeline.push(wordlist);
+        }
+        if policy.brute_force {
+            pipeline.push(&candidates::BruteForce);
+        }
+        let mut found = None;
+        let mut resume_at = None;
+        for source in &pipeline {
+            let started = Instant::now();
+            let search = source.find(&target);
+            jobs::record_attempt(
+                db,
+                &job.digest,
+                source.name(),
+                search.tried,
+                started.elapsed(),
+            )?;
+            resume_at = resume_at.or(search.resume_at);
+            if let Some(domain) = search.found {
+                found = Some((source.name(), domain));
+                break;
             }
         }
+        let elapsed = Instant::now() - now;
+
+        let mut job = job;
+        if let Some(resume_at) = resume_at {
+            job.checkpoint = Some(jobs::Checkpoint::new(&charsets, shard, resume_at));
+        }
+
+        // the job stays running, so the next run picks it up first
+        if interrupt::is_interrupted() {
+            jobs::set_state(db, job, JobState::Running);
+            metrics::set_crack_job(db, config.metrics_file.as_deref(), None)?;
+            return Ok(());
+        }
+
+        if let Some((source, found)) = found {
+            println!("> Found {found} using {source} in {elapsed:?}");
+            store_resolution(db, entry, found, source)?;
+            job.checkpoint = None;
+            jobs::set_state(db, job, JobState::Solved);
+            autosave.changed(db)?;
+            continue;
+        }
+
+        // without brute-forcing the keyspace isn't exhausted
+        if !policy.brute_force {
+            jobs::set_state(db, job, JobState::Pending);
+            autosave.changed(db)?;
+            continue;
+        }
+
+        // brute-forcing only leaves a checkpoint when it ran out of time
+        if resume_at.is_some() && run_deadline.is_some_and(|d| Instant::now() >= d) {
+            println!("Time budget used up after {elapsed:?}, it is picked up first next run");
+            jobs::set_state(db, job, JobState::Running);
+            break;
+        }
+
+        if resume_at.is_some() {
+            println!("> Gave up after {elapsed:?}");
+            jobs::set_state(db, job, JobState::Pending);
+            autosave.changed(db)?;
+            continue;
+        }
+        println!("> Not found in {elapsed:?}");
+
+        if !elapsed.is_zero() {
+            let hashes = keyspace::keyspace_size(&charsets) as f64 / shard.count as f64;
+            metrics::update(db, |c| c.last_hash_rate = hashes / elapsed.as_secs_f64())?;
+        }
+
+        // the other shards may still find it
+        job.checkpoint = None;
+        if !shard.is_whole() && !job.finish_shard(&charsets, shard) {
+            jobs::set_state(db, job, JobState::Pending);
+            autosave.changed(db)?;
+            continue;
+        }
+        job.finished_shards = None;
+        job.searched = Some(jobs::SearchedKeyspace::new(&charsets));
+        jobs::set_state(db, job, JobState::Exhausted);
+        autosave.changed(db)?;
     }
 
+    metrics::set_crack_job(db, config.metrics_file.as_deref(), None)
+}
+
+fn store_resolution(
+    db: &mut DatabaseAccess,
+    mut entry: DomainEntry,
+    domain: String,
+    source: &str,
+) -> Result<()> {
+    journal::record(
+        db,
+        journal::Event::Resolved {
+            digest: entry.get_id().to_string(),
+            domain: domain.clone(),
+            source: source.to_string(),
+        },
+    )?;
+    entry.known_domain = Some(domain);
+    metrics::update(db, |c| c.digests_resolved += 1)?;
+
+    db.set(entry);
     Ok(())
 }
 
-fn brute_force(pattern: &str, expected_digest: [u8; 32]) -> Option<String> {
+/// Brute-force searches stop at a multiple of this many candidates after where they started, so
+/// a search that runs out of time can continue from there in the next run.
+const CHECKPOINT_INTERVAL: usize = 1 << 28;
+
+/// Tries every combination of `charsets` in the slice `shard` from index `resume_at` on, where
+/// `charsets` holds the possible characters of each wildcard in `pattern`, stopping early when
+/// interrupted or past `deadline`. Also returns roughly how many candidates were tried and, when
+/// it stopped early, the index to resume at.
+fn brute_force(
+    pattern: &str,
+    charsets: &[Vec<u8>],
+    expected_digest: [u8; 32],
+    shard: keyspace::Shard,
+    resume_at: usize,
+    deadline: Option<Instant>,
+) -> (Option<String>, u64, Option<usize>) {
     // TODO: we can narrow down the TLD, there is no need to brute-force that
-    if pattern.len() > 32 {
-        panic!("url {pattern} too long");
-    }
+    let matcher = PatternMatcher::new(pattern.as_bytes(), expected_digest);
+    let tail_offset = matcher.tail_offset();
+    let wildcard_positions = pattern
+        .bytes()
+        .enumerate()
+        .filter(|(_, b)| *b == b'*')
+        .map(|(i, _)| i - tail_offset)
+        .collect::<Vec<_>>();
 
-    let buffer_len = pattern.len();
+    let total_count = charsets.iter().map(|c| c.len()).product::<usize>();
+    // println!("Brute-force attempt count for {pattern} is {total_count}");
 
-    const ALPHABET: &[u8; 36] = b"abcdefghijklmnopqrstuvwxyz0123456789";
-    let wildcard_count = pattern.chars().filter(|c| *c == '*').count();
+    // the candidates are tried in parallel in no particular order, so only the start of a chunk
+    // that wasn't finished is a safe place to resume at
+    let range = shard.range(total_count);
+    let mut start = range.start.max(resume_at);
+    let mut tried = 0;
+    while start < range.end {
+        let chunk = start..start.saturating_add(CHECKPOINT_INTERVAL).min(range.end);
+        let chunk_len = chunk.len() as u64;
 
-    let total_count = ALPHABET.len().pow(wildcard_count as u32);
-    // println!("Brute-force attempt count for {pattern} is {total_count}");
+        // every thread keeps writing candidates into its own copy of the blocks, only the
+        // wildcard positions change between candidates
+        // returns Some(None) to stop early when interrupted or out of time
+        let started_blocks = AtomicU64::new(0);
+        let found = chunk
+            .into_par_iter()
+            .map_init(
+                || matcher.template(),
+                |tail, i| {
+                    if (i - start) % 0x10000 == 0 {
+                        if interrupt::is_interrupted()
+                            || deadline.is_some_and(|d| Instant::now() >= d)
+                        {
+                            return Some(None);
+                        }
+                        started_blocks.fetch_add(1, Ordering::Relaxed);
+                    }
 
-    // (0..total_count).find_map(|i| {
-    (0..total_count).into_par_iter().find_map_any(|i| {
-        let mut buffer = [0u8; 32];
-        let buffer = &mut buffer[..buffer_len];
-        buffer.copy_from_slice(pattern.as_bytes());
+                    let mut x = i;
+                    for (position, charset) in wildcard_positions.iter().zip(charsets) {
+                        tail[*position] = charset[x % charset.len()];
+                        x /= charset.len();
+                    }
 
-        for wc_idx in 0..wildcard_count {
-            let x = i / ALPHABET.len().pow(wc_idx as u32);
-            let x = x % ALPHABET.len();
+                    matcher.matches(tail).then(|| {
+                        let mut found = pattern.as_bytes().to_vec();
+                        found[tail_offset..].copy_from_slice(&tail[..pattern.len() - tail_offset]);
+                        Some(String::from_utf8_lossy(&found).to_string())
+                    })
+                },
+            )
+            .find_map_any(|found| found);
 
-            let char_to_place = ALPHABET[x];
-            let (char_index, _) = buffer
-                .iter()
-                .enumerate()
-                .find(|(_, b)| **b == b'*')
-                .unwrap();
-            buffer[char_index] = char_to_place;
+        match found {
+            Some(found) => {
+                tried += (started_blocks.into_inner() * 0x10000).min(chunk_len);
+                let resume_at = found.is_none().then_some(start);
+                return (found, tried, resume_at);
+            }
+            None => {
+                tried += chunk_len;
+                start += chunk_len as usize;
+            }
         }
-        // println!("iteration {i}: {}", String::from_utf8_lossy(buffer));
+    }
 
-        let mut hasher = Sha256::new();
-        hasher.update(&buffer);
-        let found_digest = hasher.finalize();
-        // let found_digest = sha256::digest(buffer.as_ref());
+    (None, tried, None)
+}
 
-        if found_digest[..] == expected_digest {
-            Some(String::from_utf8_lossy(buffer).to_string())
-        } else {
-            None
-        }
-    })
+fn hash_domain(domain: &str) -> [u8; 32] {
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(&Sha256::digest(domain.as_bytes()));
+    digest
 }
 
-fn show(db: &mut DatabaseAccess) -> Result<()> {
-    let entries = db
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{seconds}s"),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86399 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
+
+fn show(
+    db: &mut DatabaseAccess,
+    config: &Config,
+    filter: &filter::EntryFilter,
+    no_pager: bool,
+) -> Result<()> {
+    let scorer = score::Scorer::new(db, &config.scoring)?;
+    let mut entries = db
         .iter_keys::<DomainEntry>()
         .collect::<Vec<_>>()
         .into_iter()
         .map(|k| db.get::<DomainEntry>(&k).unwrap().unwrap())
+        .filter(|entry| filter.matches(entry))
+        .map(|entry| (scorer.score(&entry.blocked_by), entry))
         .collect::<Vec<_>>();
+    entries.sort_by(|(score_a, a), (score_b, b)| {
+        score_b.total_cmp(score_a).then_with(|| a.cmp_display(b))
+    });
 
-    let blocklists = db
-        .iter_keys::<MastodonBlockList>()
-        .collect::<Vec<_>>()
-        .into_iter()
-        .map(|k| db.get::<MastodonBlockList>(&k).unwrap().unwrap())
-        .collect::<Vec<_>>();
-
-    for entry in &entries {
-        let display_domain = entry
-            .known_domain
-            .clone()
-            .unwrap_or_else(|| entry.partial_domains.first().unwrap().clone());
-        println!("{display_domain}");
-
-        // find which domains block this one
-        for blocklist in &blocklists {
-            if let Some(blocklist_entry) =
-                blocklist.list.iter().find(|e| e.digest == entry.get_id())
-            {
-                if let Some(reason) = &blocklist_entry.comment {
-                    println!("- Blocked by {} for reason: {reason}", blocklist.domain);
-                } else {
-                    println!("- Blocked by {}", blocklist.domain);
-                }
+    let now = unix_now();
+    let mut table = Table::new(&["Domain", "Score", "Blocked by", "Severity", "Reason"]);
+    for (score, entry) in &entries {
+        let domain_color = entry.known_domain.is_none().then_some(Color::Dim);
+        let mut label = entry.display_domain().to_string();
+        if let Some(domain) = &entry.known_domain {
+            if let Some(probe) = db.get::<ProbeResult>(domain)? {
+                label += &format!(" ({})", probe.summary(now));
             }
         }
+        if !entry.annotation.tags.is_empty() {
+            let tags = entry.annotation.tags.iter().cloned().collect::<Vec<_>>();
+            label += &format!(" [{}]", tags.join(", "));
+        }
+        if let Some(note) = entry.annotation.notes.last() {
+            label += &format!(" \"{}\"", note.text);
+        }
 
-        println!();
+        if entry.blocked_by.is_empty() {
+            table.add_row(vec![
+                (label, domain_color),
+                (String::new(), None),
+                (String::new(), None),
+                (String::new(), None),
+                (String::new(), None),
+            ]);
+            continue;
+        }
+
+        // instances often copy each other's reasons, these are listed once
+        let blockers = entry
+            .blocked_by
+            .iter()
+            .filter(|(_, b)| filter.blocker_matches(b));
+        let groups = output::group_by(blockers, |(_, b)| (b.severity_label(), b.comment.clone()));
+        for (i, group) in groups.iter().enumerate() {
+            let (_, blocker) = group[0];
+            let instances = group
+                .iter()
+                .map(|(instance, _)| instance.as_str())
+                .collect::<Vec<_>>();
+            // only name the domain once, so the blockers are grouped below it
+            let (domain, score) = if i == 0 {
+                (label.clone(), format!("{score:.1}"))
+            } else {
+                (String::new(), String::new())
+            };
+            let severity_color = match blocker.severity {
+                DomainBlockSeverity::Suspend => Color::Red,
+                DomainBlockSeverity::Silence => Color::Yellow,
+                DomainBlockSeverity::Noop | DomainBlockSeverity::Unknown => Color::Dim,
+            };
+            table.add_row(vec![
+                (domain, domain_color),
+                (score, None),
+                (instances.join(", "), None),
+                (blocker.severity_label(), Some(severity_color)),
+                (
+                    match &blocker.comment {
+                        Some(comment) => translate::with_translation(db, comment)?,
+                        None => String::new(),
+                    },
+                    None,
+                ),
+            ]);
+        }
     }
+    // the full listing is thousands of lines
+    output::page(&table.render(), no_pager);
 
     Ok(())
 }
@@ -260,6 +1482,16 @@ fn show(db: &mut DatabaseAccess) -> Result<()> {
 struct MastodonBlockList {
     pub domain: String,
     pub list: Vec<DomainBlock>,
+    /// Whether this list has been processed into domain entries since it last changed.
+    #[serde(default)]
+    pub processed: bool,
+    /// How the stored list was fetched. Missing for lists fetched by older versions.
+    #[serde(default)]
+    pub metadata: Option<fetch::FetchMetadata>,
+    /// Digests the instance stopped blocking since the list was last processed. `process` only
+    /// has to remove the instance from the domain entries of these.
+    #[serde(default)]
+    pub removed: BTreeSet<String>,
 }
 
 impl DatabaseObject for MastodonBlockList {
@@ -277,9 +1509,57 @@ struct DomainEntry {
     pub digest: [u8; 32],
     pub known_domain: Option<String>,
     pub partial_domains: BTreeSet<String>,
+    /// The instances blocking this domain, kept up to date by `process`.
+    #[serde(default)]
+    pub blocked_by: BTreeMap<String, BlockedBy>,
+    /// The user's own notes and tags, set by `annotate`.
+    #[serde(default, skip_serializing_if = "annotation::Annotation::is_empty")]
+    pub annotation: annotation::Annotation,
+}
+
+/// How an instance blocks a domain.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+struct BlockedBy {
+    pub severity: DomainBlockSeverity,
+    /// The severity as the instance published it, if it used another term.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_severity: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub limitations: BTreeSet<Limitation>,
+    pub comment: Option<String>,
+    /// The language the comment seems to be written in, as an ISO 639-1 code.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment_language: Option<String>,
+}
+
+impl BlockedBy {
+    pub fn severity_label(&self) -> String {
+        severity::label(
+            self.severity,
+            &self.limitations,
+            self.original_severity.as_deref(),
+        )
+    }
 }
 
 impl DomainEntry {
+    /// Gets the known domain, or a partial domain if it's not known. Entries with neither, such
+    /// as those of a damaged database, show their digest.
+    pub fn display_domain(&self) -> std::borrow::Cow<str> {
+        match self.known_domain.as_ref().or(self.partial_domains.first()) {
+            Some(domain) => domain.into(),
+            None => hex::encode(self.digest).into(),
+        }
+    }
+
+    /// Orders entries by their displayed domain. Different digests can share a partial domain, so
+    /// ties are broken by digest to keep listings the same between runs.
+    pub fn cmp_display(&self, other: &Self) -> std::cmp::Ordering {
+        self.display_domain()
+            .cmp(&other.display_domain())
+            .then_with(|| self.digest.cmp(&other.digest))
+    }
+
     pub fn merge(self, other: Self) -> Self {
         debug_assert_eq!(self.digest, other.digest);
 
@@ -291,6 +1571,13 @@ impl DomainEntry {
                 .into_iter()
                 .chain(other.partial_domains)
                 .collect(),
+            // the blocks of self are the most recent
+            blocked_by: other
+                .blocked_by
+                .into_iter()
+                .chain(self.blocked_by)
+                .collect(),
+            annotation: self.annotation.merge(other.annotation),
         }
     }
 }
@@ -317,6 +1604,8 @@ impl TryFrom<DomainBlock> for DomainEntry {
                     set
                 })
                 .unwrap_or_default(),
+            blocked_by: BTreeMap::new(),
+            annotation: Default::default(),
         })
     }
 }
@@ -0,0 +1,335 @@
+use std::{borrow::Cow, collections::BTreeMap, fmt::Write};
+
+use color_eyre::{eyre::Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{DatabaseAccess, DatabaseObject},
+    fetch::InstanceStatus,
+    jobs::CrackJob,
+    DomainEntry, MastodonBlockList,
+};
+
+const PREFIX: &str = "mastodon_block_enum";
+
+/// Counters that accumulate over every run, for monitoring.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Counters {
+    pub fetch_successes: u64,
+    pub fetch_failures: u64,
+    pub digests_resolved: u64,
+    /// Hashes per second measured during the last exhausted brute-force attempt.
+    pub last_hash_rate: f64,
+    pub last_fetch_at: Option<u64>,
+    pub last_process_at: Option<u64>,
+    pub last_crack_at: Option<u64>,
+    /// The job `crack` is working on, if it is running.
+    pub crack_job: Option<RunningJob>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RunningJob {
+    pub digest: String,
+    pub pattern: String,
+    /// The number of candidates, as a float since it doesn't always fit in a u64.
+    pub keyspace: f64,
+    pub started_at: u64,
+}
+
+impl DatabaseObject for Counters {
+    const KEY_NAME: &'static str = "counters";
+
+    fn get_id(&self) -> Cow<str> {
+        "global".into()
+    }
+}
+
+impl RunningJob {
+    /// Estimates how much of the keyspace was searched by now, since the search itself doesn't
+    /// report its progress.
+    pub fn progress(&self, hash_rate: f64, now: u64) -> f64 {
+        if self.keyspace <= 0. {
+            return 0.;
+        }
+
+        let searched = now.saturating_sub(self.started_at) as f64 * hash_rate;
+        (searched / self.keyspace).min(1.)
+    }
+}
+
+pub fn update(db: &mut DatabaseAccess, f: impl FnOnce(&mut Counters)) -> Result<()> {
+    let mut counters = db.get::<Counters>("global")?.unwrap_or_default();
+    f(&mut counters);
+    db.set(counters);
+
+    Ok(())
+}
+
+/// Records the job `crack` is working on, and rewrites the metrics file if there is one so
+/// collectors see the progress while `crack` runs.
+pub fn set_crack_job(
+    db: &mut DatabaseAccess,
+    metrics_file: Option<&str>,
+    job: Option<RunningJob>,
+) -> Result<()> {
+    update(db, |c| c.crack_job = job)?;
+    if let Some(path) = metrics_file {
+        write_file(db, path)?;
+    }
+
+    Ok(())
+}
+
+/// Renders the current state of the database in the prometheus text format.
+pub fn render(db: &mut DatabaseAccess) -> Result<String> {
+    let counters = db.get::<Counters>("global")?.unwrap_or_default();
+
+    let keys = db.iter_keys::<InstanceStatus>().collect::<Vec<_>>();
+    let mut statuses = BTreeMap::<String, u64>::new();
+    for key in keys {
+        let status = db.get::<InstanceStatus>(&key)?.unwrap();
+        *statuses.entry(status.status.to_string()).or_default() += 1;
+    }
+
+    let keys = db.iter_keys::<MastodonBlockList>().collect::<Vec<_>>();
+    let mut blocks = 0;
+    for key in keys {
+        blocks += db.get::<MastodonBlockList>(&key)?.unwrap().list.len();
+    }
+
+    let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
+    let (mut resolved, mut unresolved) = (0, 0);
+    let mut comment_languages = BTreeMap::<String, u64>::new();
+    for key in keys {
+        let entry = db.get::<DomainEntry>(&key)?.unwrap();
+        match entry.known_domain {
+            Some(_) => resolved += 1,
+            None => unresolved += 1,
+        }
+        for blocked_by in entry.blocked_by.values().filter(|b| b.comment.is_some()) {
+            let language = blocked_by.comment_language.as_deref().unwrap_or("unknown");
+            *comment_languages.entry(language.to_string()).or_default() += 1;
+        }
+    }
+
+    let keys = db.iter_keys::<CrackJob>().collect::<Vec<_>>();
+    let mut job_states = BTreeMap::<String, u64>::new();
+    for key in keys {
+        let job = db.get::<CrackJob>(&key)?.unwrap();
+        *job_states.entry(job.state.to_string()).or_default() += 1;
+    }
+
+    let mut out = String::new();
+    metric(
+        &mut out,
+        "fetch_successes_total",
+        "counter",
+        "Successful blocklist fetches.",
+        &[],
+        counters.fetch_successes,
+    );
+    metric(
+        &mut out,
+        "fetch_failures_total",
+        "counter",
+        "Failed blocklist fetches.",
+        &[],
+        counters.fetch_failures,
+    );
+    metric(
+        &mut out,
+        "digests_resolved_total",
+        "counter",
+        "Digests resolved by cracking.",
+        &[],
+        counters.digests_resolved,
+    );
+    metric(
+        &mut out,
+        "hash_rate",
+        "gauge",
+        "Hashes per second of the last exhausted brute-force attempt.",
+        &[],
+        counters.last_hash_rate,
+    );
+
+    header(
+        &mut out,
+        "crack_jobs",
+        "gauge",
+        "Crack jobs by their state.",
+    );
+    for (state, count) in &job_states {
+        sample(&mut out, "crack_jobs", &[("state", state.as_str())], count);
+    }
+
+    if let Some(job) = &counters.crack_job {
+        let labels = [
+            ("digest", job.digest.as_str()),
+            ("pattern", job.pattern.as_str()),
+        ];
+        metric(
+            &mut out,
+            "crack_job_keyspace_size",
+            "gauge",
+            "Candidates in the keyspace of the running crack job.",
+            &labels,
+            job.keyspace,
+        );
+        metric(
+            &mut out,
+            "crack_job_started_timestamp_seconds",
+            "gauge",
+            "When the running crack job started.",
+            &labels,
+            job.started_at,
+        );
+        metric(
+            &mut out,
+            "crack_job_progress_ratio",
+            "gauge",
+            "Estimated part of the keyspace of the running crack job that was searched, based on the last hash rate.",
+            &labels,
+            job.progress(counters.last_hash_rate, crate::unix_now()),
+        );
+    }
+
+    header(
+        &mut out,
+        "instances",
+        "gauge",
+        "Instances by their last fetch status.",
+    );
+    for (status, count) in &statuses {
+        sample(&mut out, "instances", &[("status", status.as_str())], count);
+    }
+
+    metric(
+        &mut out,
+        "blocks_stored",
+        "gauge",
+        "Blocks in all stored blocklists.",
+        &[],
+        blocks,
+    );
+    header(
+        &mut out,
+        "domains",
+        "gauge",
+        "Blocked domains by whether their name is known.",
+    );
+    sample(&mut out, "domains", &[("resolved", "true")], resolved);
+    sample(&mut out, "domains", &[("resolved", "false")], unresolved);
+    header(
+        &mut out,
+        "block_comments",
+        "gauge",
+        "Blocks with a comment by the language of the comment.",
+    );
+    for (language, count) in &comment_languages {
+        sample(&mut out, "block_comments", &[("language", language)], count);
+    }
+
+    header(
+        &mut out,
+        "last_run_timestamp_seconds",
+        "gauge",
+        "When each verb last ran.",
+    );
+    for (verb, timestamp) in [
+        ("fetch", counters.last_fetch_at),
+        ("process", counters.last_process_at),
+        ("crack", counters.last_crack_at),
+    ] {
+        if let Some(timestamp) = timestamp {
+            sample(
+                &mut out,
+                "last_run_timestamp_seconds",
+                &[("verb", verb)],
+                timestamp,
+            );
+        }
+    }
+
+    Ok(out)
+}
+
+/// Writes the metrics to a file, replacing it atomically so collectors never see partial output.
+pub fn write_file(db: &mut DatabaseAccess, path: &str) -> Result<()> {
+    let temp_path = format!("{path}.tmp");
+    std::fs::write(&temp_path, render(db)?).context("write metrics file")?;
+    std::fs::rename(&temp_path, path).context("replace metrics file")?;
+
+    Ok(())
+}
+
+fn metric(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    labels: &[(&str, &str)],
+    value: impl std::fmt::Display,
+) {
+    header(out, name, kind, help);
+    sample(out, name, labels, value);
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {PREFIX}_{name} {help}").unwrap();
+    writeln!(out, "# TYPE {PREFIX}_{name} {kind}").unwrap();
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    let labels = labels
+        .iter()
+        .map(|(k, v)| format!("{k}=\"{}\"", v.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    if labels.is_empty() {
+        writeln!(out, "{PREFIX}_{name} {value}").unwrap();
+    } else {
+        writeln!(out, "{PREFIX}_{name}{{{labels}}} {value}").unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DatabaseInstance;
+
+    #[test]
+    fn renders_counters() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        update(&mut db, |c| {
+            c.fetch_successes += 3;
+            c.last_fetch_at = Some(1234);
+        })
+        .unwrap();
+
+        let rendered = render(&mut db).unwrap();
+
+        assert!(rendered.contains("# TYPE mastodon_block_enum_fetch_successes_total counter\n"));
+        assert!(rendered.contains("mastodon_block_enum_fetch_successes_total 3\n"));
+        assert!(rendered
+            .contains("mastodon_block_enum_last_run_timestamp_seconds{verb=\"fetch\"} 1234\n"));
+        assert!(rendered.contains("mastodon_block_enum_domains{resolved=\"false\"} 0\n"));
+        assert!(!rendered.contains("crack_job_progress_ratio"));
+    }
+
+    #[test]
+    fn estimates_job_progress() {
+        let job = RunningJob {
+            digest: "abc".to_string(),
+            pattern: "exa*ple.com".to_string(),
+            keyspace: 1000.,
+            started_at: 100,
+        };
+
+        assert_eq!(job.progress(10., 100), 0.);
+        assert_eq!(job.progress(10., 150), 0.5);
+        assert_eq!(job.progress(10., 1000), 1.);
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    io::{IsTerminal, Write},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use color_eyre::{eyre::eyre, Result};
+
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+    Dim,
+}
+
+impl Color {
+    fn ansi_code(self) -> &'static str {
+        match self {
+            Self::Red => "31",
+            Self::Green => "32",
+            Self::Yellow => "33",
+            Self::Dim => "2",
+        }
+    }
+}
+
+/// Decides whether output is colored, based on `--color auto|always|never`. `auto` only colors
+/// output going to a terminal, and respects `NO_COLOR`.
+pub fn init(choice: Option<&str>) -> Result<()> {
+    let enabled = match choice.unwrap_or("auto") {
+        "auto" => std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        "always" => true,
+        "never" => false,
+        choice => return Err(eyre!("invalid --color value {choice}")),
+    };
+
+    COLOR_ENABLED.store(enabled, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn paint(text: &str, color: Color) -> String {
+    if COLOR_ENABLED.load(Ordering::Relaxed) {
+        format!("\x1b[{}m{text}\x1b[0m", color.ansi_code())
+    } else {
+        text.to_string()
+    }
+}
+
+/// Prints long output through `$PAGER` when writing to a terminal. The default `less -FRX` exits
+/// immediately when the output fits on the screen.
+pub fn page(text: &str, no_pager: bool) {
+    if no_pager || !std::io::stdout().is_terminal() {
+        print!("{text}");
+        return;
+    }
+
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(&pager).stdin(Stdio::piped());
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+
+    let Ok(mut child) = command.spawn() else {
+        print!("{text}");
+        return;
+    };
+
+    // the pager closing early is not an error
+    if let Some(mut stdin) = child.stdin.take() {
+        _ = stdin.write_all(text.as_bytes());
+    }
+    _ = child.wait();
+}
+
+/// A table with aligned columns. Colors are applied after padding so they don't affect alignment.
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<(String, Option<Color>)>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: vec![],
+        }
+    }
+
+    pub fn add_row(&mut self, cells: Vec<(String, Option<Color>)>) {
+        debug_assert_eq!(cells.len(), self.headers.len());
+        self.rows.push(cells);
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn render(&self) -> String {
+        let mut widths = self.headers.iter().map(|h| h.len()).collect::<Vec<_>>();
+        for row in &self.rows {
+            for (width, (text, _)) in widths.iter_mut().zip(row) {
+                *width = (*width).max(text.chars().count());
+            }
+        }
+
+        let mut rendered = String::new();
+        let header = self
+            .headers
+            .iter()
+            .map(|h| (h.clone(), None))
+            .collect::<Vec<_>>();
+        for row in std::iter::once(&header).chain(&self.rows) {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|((text, color), width)| {
+                    let padded = format!("{text:width$}");
+                    match color {
+                        Some(color) => paint(&padded, *color),
+                        None => padded,
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("  ");
+            rendered += line.trim_end();
+            rendered.push('\n');
+        }
+
+        rendered
+    }
+
+    pub fn print(&self) {
+        print!("{}", self.render());
+    }
+}
+
+/// Groups items with the same key, keeping the order in which each key first appears. Used to
+/// list a reason shared by many instances once, with all of them.
+pub fn group_by<T, K: PartialEq>(
+    items: impl IntoIterator<Item = T>,
+    key: impl Fn(&T) -> K,
+) -> Vec<Vec<T>> {
+    let mut groups: Vec<(K, Vec<T>)> = vec![];
+    for item in items {
+        let item_key = key(&item);
+        match groups.iter_mut().find(|(k, _)| *k == item_key) {
+            Some((_, group)) => group.push(item),
+            None => groups.push((item_key, vec![item])),
+        }
+    }
+
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_in_order() {
+        let groups = group_by(["a:spam", "b:nazis", "c:spam"], |s| s.split(':').nth(1));
+
+        assert_eq!(groups, vec![vec!["a:spam", "c:spam"], vec!["b:nazis"]]);
+    }
+
+    #[test]
+    fn aligns_columns() {
+        let mut table = Table::new(&["Domain", "Status"]);
+        table.add_row(vec![("a.social".into(), None), ("ok".into(), None)]);
+        table.add_row(vec![("b.town".into(), None), ("unreachable".into(), None)]);
+
+        assert_eq!(
+            table.render(),
+            "Domain    Status\na.social  ok\nb.town    unreachable\n"
+        );
+    }
+}
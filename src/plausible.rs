@@ -0,0 +1,117 @@
+// words that show up a lot in fediverse domains
+const FRAGMENTS: &[&str] = &[
+    "mastodon", "masto", "mstdn", "social", "club", "toot", "fedi", "verse", "online", "space",
+    "town", "world", "city", "cafe", "party", "zone", "home", "instance", "network", "pub", "chat",
+    "art", "tech", "game", "games", "music", "furry", "queer", "gay", "porn", "news", "free",
+    "speech", "truth", "pleroma", "misskey", "akkoma", "kiwi", "farm", "xyz", "net", "org", "com",
+    "dev", "the", "my", "best", "all", "one",
+];
+
+// prevents combining fragments from blowing up on patterns with many censored runs
+const MAX_CANDIDATES: usize = 1_000_000;
+
+/// Builds candidates from common domain fragments, most plausible first, to try before the
+/// keyspace gets brute-forced. `charsets` holds the possible characters of each wildcard.
+pub fn ranked(pattern: &str, charsets: &[Vec<u8>]) -> Vec<String> {
+    let mut candidates = candidates(pattern, charsets);
+    candidates.sort_by_cached_key(|c| std::cmp::Reverse(score(c)));
+    candidates
+}
+
+/// Fills every run of wildcards with a substring of a fragment of the same length.
+fn candidates(pattern: &str, charsets: &[Vec<u8>]) -> Vec<String> {
+    let mut candidates = vec![pattern.as_bytes().to_vec()];
+
+    let mut charsets = charsets.iter();
+    let bytes = pattern.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'*' {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < bytes.len() && bytes[i] == b'*' {
+            i += 1;
+        }
+        let run_charsets = charsets.by_ref().take(i - start).collect::<Vec<_>>();
+
+        let mut fills = FRAGMENTS
+            .iter()
+            .flat_map(|f| f.as_bytes().windows(i - start))
+            .filter(|fill| fill.iter().zip(&run_charsets).all(|(b, c)| c.contains(b)))
+            .collect::<Vec<_>>();
+        fills.sort();
+        fills.dedup();
+
+        if fills.is_empty() || candidates.len() * fills.len() > MAX_CANDIDATES {
+            return vec![];
+        }
+
+        candidates = candidates
+            .iter()
+            .flat_map(|c| {
+                fills.iter().map(move |fill| {
+                    let mut c = c.clone();
+                    c[start..start + fill.len()].copy_from_slice(fill);
+                    c
+                })
+            })
+            .collect();
+    }
+
+    candidates
+        .into_iter()
+        .filter_map(|c| String::from_utf8(c).ok())
+        .collect()
+}
+
+/// Rates how much a candidate looks like a real domain: characters covered by known fragments
+/// count for it, long runs of consonants against it.
+fn score(candidate: &str) -> i32 {
+    let name = candidate
+        .rsplit_once('.')
+        .map_or(candidate, |(name, _)| name);
+
+    let mut covered = vec![false; name.len()];
+    for fragment in FRAGMENTS {
+        for (start, _) in name.match_indices(fragment) {
+            covered[start..start + fragment.len()].fill(true);
+        }
+    }
+    let covered = covered.iter().filter(|c| **c).count() as i32;
+
+    let mut consonant_run = 0;
+    let mut unpronounceable = 0;
+    for c in name.chars() {
+        if c.is_ascii_alphabetic() && !"aeiouy".contains(c) {
+            consonant_run += 1;
+            if consonant_run > 3 {
+                unpronounceable += 1;
+            }
+        } else {
+            consonant_run = 0;
+        }
+    }
+
+    covered * 2 - unpronounceable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hash_domain, keyspace::ALPHABET};
+
+    #[test]
+    fn finds_plausible_domain() {
+        let charsets = vec![ALPHABET.to_vec(); 3];
+
+        let found = ranked("m***n.social", &charsets)
+            .into_iter()
+            .find(|c| hash_domain(c) == hash_domain("mstdn.social"));
+
+        assert_eq!(found.as_deref(), Some("mstdn.social"));
+        assert!(score("mstdn.social") > score("mqxzn.social"));
+    }
+}
@@ -0,0 +1,32 @@
+use std::process::Command;
+
+/// Lowers the cpu and io priority of this process so a long crack can run next to normal work on
+/// the machine. Threads inherit the priority when they are created, so this has to be called
+/// before the first parallel search starts the worker threads. Failures are reported but don't
+/// stop the run.
+pub fn lower() {
+    let pid = std::process::id().to_string();
+
+    if cfg!(unix) {
+        run("renice", &["-n", "19", "-p", &pid]);
+    } else {
+        println!("Lowering the priority is not supported on this platform");
+    }
+
+    // the idle io class only exists on linux
+    if cfg!(target_os = "linux") {
+        run("ionice", &["-c", "3", "-p", &pid]);
+    }
+}
+
+fn run(program: &str, args: &[&str]) {
+    match Command::new(program).args(args).output() {
+        Ok(output) if !output.status.success() => println!(
+            "{program} exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Ok(_) => {}
+        Err(e) => println!("Error while trying to run {program}: {e}"),
+    }
+}
@@ -0,0 +1,137 @@
+use color_eyre::Result;
+
+use crate::{archive, config::RetentionConfig, database::DatabaseAccess, journal::JournalEntry};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Removes archived responses and journal events according to the retention settings. The
+/// latest snapshot of every instance and the newest event are always kept.
+pub fn prune(
+    db: &mut DatabaseAccess,
+    archive_dir: Option<&str>,
+    retention: &RetentionConfig,
+) -> Result<()> {
+    let now = crate::unix_now();
+
+    if let Some(archive_dir) = archive_dir {
+        let mut removed = 0;
+        for domain in archive::domains(archive_dir)? {
+            let timestamps = archive::timestamps(archive_dir, &domain)?;
+            for timestamp in expired_snapshots(&timestamps, retention, now) {
+                archive::remove(archive_dir, &domain, timestamp)?;
+                removed += 1;
+            }
+        }
+        println!("Removed {removed} archived responses");
+    }
+
+    prune_journal(db, retention, now)
+}
+
+/// Removes the journal events older than `journal_max_age_days`, except for the newest one.
+fn prune_journal(db: &mut DatabaseAccess, retention: &RetentionConfig, now: u64) -> Result<()> {
+    if let Some(max_age_days) = retention.journal_max_age_days {
+        // the newest event is always kept, databases without a journal counter take the next id
+        // from it
+        let keys = db.iter_keys::<JournalEntry>().collect::<Vec<_>>();
+        let older = keys.split_last().map_or(&[][..], |(_, older)| older);
+        let mut removed = 0;
+        for key in older {
+            let entry = db.get::<JournalEntry>(key)?.unwrap();
+            if entry.at + max_age_days * DAY < now {
+                db.remove::<JournalEntry>(key);
+                removed += 1;
+            }
+        }
+        println!("Removed {removed} journal events");
+    }
+
+    Ok(())
+}
+
+/// Gets the snapshots that should be removed, given all snapshots of an instance oldest first.
+/// The latest snapshot is always kept.
+fn expired_snapshots(timestamps: &[u64], retention: &RetentionConfig, now: u64) -> Vec<u64> {
+    let Some((_, older)) = timestamps.split_last() else {
+        return vec![];
+    };
+
+    let keep_from = retention
+        .keep_snapshots
+        .map_or(0, |keep| timestamps.len().saturating_sub(keep.max(1)));
+    older
+        .iter()
+        .enumerate()
+        .filter(|(i, timestamp)| {
+            *i < keep_from
+                || retention
+                    .archive_max_age_days
+                    .is_some_and(|days| **timestamp + days * DAY < now)
+        })
+        .map(|(_, timestamp)| *timestamp)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        database::DatabaseInstance,
+        journal::{self, Event},
+    };
+
+    #[test]
+    fn expires_snapshots() {
+        let now = 100 * DAY;
+        let timestamps = [10 * DAY, 20 * DAY, 95 * DAY, 99 * DAY];
+
+        let by_count = RetentionConfig {
+            keep_snapshots: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            expired_snapshots(&timestamps, &by_count, now),
+            vec![10 * DAY, 20 * DAY]
+        );
+
+        let by_age = RetentionConfig {
+            archive_max_age_days: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            expired_snapshots(&timestamps, &by_age, now),
+            vec![10 * DAY, 20 * DAY, 95 * DAY]
+        );
+    }
+
+    #[test]
+    fn keeps_newest_event() {
+        let mut db = DatabaseInstance::in_memory();
+        for i in 0..3 {
+            journal::record(
+                &mut db,
+                Event::InstanceAdded {
+                    instance: format!("{i}.social"),
+                    blocks: i,
+                },
+            )
+            .unwrap();
+        }
+
+        let retention = RetentionConfig {
+            journal_max_age_days: Some(0),
+            ..Default::default()
+        };
+        // every event is older than the max age by then
+        let later = crate::unix_now() + DAY;
+        prune_journal(&mut db, &retention, later).unwrap();
+
+        let ids = journal::events_since(&mut db, 0)
+            .unwrap()
+            .iter()
+            .map(|e| e.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![2]);
+        assert_eq!(journal::next_id(&mut db).unwrap(), 3);
+    }
+}
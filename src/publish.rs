@@ -0,0 +1,307 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+    path::Path,
+};
+
+use color_eyre::{eyre::Context, Result};
+use serde::Serialize;
+
+use crate::{
+    api::{DomainBlock, DomainBlockSeverity, Limitation},
+    database::{DatabaseAccess, DatabaseObject},
+    filter::EntryFilter,
+    output,
+    redact::Redaction,
+    DomainEntry, MastodonBlockList,
+};
+
+const STYLE: &str = "body{font-family:sans-serif;max-width:60em;margin:auto;padding:1em}\
+table{border-collapse:collapse;width:100%}td,th{text-align:left;padding:.2em .5em;border-bottom:1px solid #ddd}\
+.suspend{color:#b00}.silence{color:#a60}";
+
+#[derive(Serialize, Clone)]
+pub struct Blocker {
+    pub instance: String,
+    pub severity: DomainBlockSeverity,
+    /// The severity as the instance published it, if it used another term.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_severity: Option<String>,
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub limitations: BTreeSet<Limitation>,
+    pub comment: Option<String>,
+}
+
+impl Blocker {
+    pub fn severity_label(&self) -> String {
+        crate::severity::label(
+            self.severity,
+            &self.limitations,
+            self.original_severity.as_deref(),
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct PublishedDomain<'a> {
+    digest: String,
+    domain: Option<&'a str>,
+    partial_domains: Vec<&'a str>,
+    blocked_by: &'a [Blocker],
+}
+
+/// Finds which instances block each digest, in a single pass over all blocklists.
+pub fn blockers_by_digest(db: &mut DatabaseAccess) -> Result<HashMap<String, Vec<Blocker>>> {
+    let keys = db.iter_keys::<MastodonBlockList>().collect::<Vec<_>>();
+
+    let mut blockers = HashMap::<String, Vec<Blocker>>::new();
+    for key in keys {
+        let blocklist = db.get::<MastodonBlockList>(&key)?.unwrap();
+        for block in blocklist.list {
+            blockers.entry(block.digest).or_default().push(Blocker {
+                instance: blocklist.domain.clone(),
+                severity: block.severity,
+                original_severity: block.original_severity,
+                limitations: block.limitations,
+                comment: block.comment,
+            });
+        }
+    }
+
+    Ok(blockers)
+}
+
+/// Generates a static website with an index of all domains, a page per domain and json data files.
+pub fn publish(
+    db: &mut DatabaseAccess,
+    out_dir: &str,
+    redaction: &Redaction,
+    filter: &EntryFilter,
+) -> Result<()> {
+    let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
+    let mut entries = keys
+        .into_iter()
+        .map(|k| db.get::<DomainEntry>(&k).map(Option::unwrap))
+        .collect::<Result<Vec<_>>>()?;
+    entries.retain(|e| filter.matches(e));
+    let mut blockers = blockers_by_digest(db)?;
+    redaction.apply_to_blockers(&mut blockers);
+    for entry in &entries {
+        if let Some(blockers) = blockers.get_mut(entry.get_id().as_ref()) {
+            blockers.retain(|b| filter.instance_matches(entry, &b.instance));
+        }
+    }
+
+    let blocked_by = |entry: &DomainEntry| {
+        blockers
+            .get(entry.get_id().as_ref())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    };
+    entries.sort_by(|a, b| {
+        blocked_by(b)
+            .len()
+            .cmp(&blocked_by(a).len())
+            .then_with(|| a.cmp_display(b))
+    });
+
+    let out_dir = Path::new(out_dir);
+    std::fs::create_dir_all(out_dir.join("domains")).context("create output directory")?;
+    std::fs::create_dir_all(out_dir.join("data/domains")).context("create output directory")?;
+
+    let published = entries
+        .iter()
+        .map(|entry| PublishedDomain {
+            digest: entry.get_id().to_string(),
+            domain: entry.known_domain.as_deref(),
+            partial_domains: entry.partial_domains.iter().map(String::as_str).collect(),
+            blocked_by: blocked_by(entry),
+        })
+        .collect::<Vec<_>>();
+    write_if_changed(
+        &out_dir.join("data/domains.json"),
+        &serde_json::to_vec_pretty(&published)?,
+    )
+    .context("write domains data file")?;
+    for domain in &published {
+        write_if_changed(
+            &out_dir.join(format!("data/domains/{}.json", domain.digest)),
+            &serde_json::to_vec_pretty(domain)?,
+        )
+        .context("write domain data file")?;
+    }
+
+    // served from the root of the site, this lets tools built for the mastodon api read the dataset
+    let domain_blocks = published
+        .iter()
+        .map(|domain| DomainBlock {
+            domain: display_name(domain).to_string(),
+            digest: domain.digest.clone(),
+            severity: consensus_severity(domain.blocked_by.iter().map(|b| b.severity)),
+            original_severity: None,
+            limitations: Default::default(),
+            comment: Some(format!("Blocked by {} instances", domain.blocked_by.len())),
+        })
+        .collect::<Vec<_>>();
+    std::fs::create_dir_all(out_dir.join("api/v1/instance")).context("create output directory")?;
+    write_if_changed(
+        &out_dir.join("api/v1/instance/domain_blocks"),
+        &serde_json::to_vec(&domain_blocks)?,
+    )
+    .context("write domain_blocks file")?;
+
+    let mut index = String::new();
+    writeln!(index, "<h1>Blocked domains</h1>")?;
+    writeln!(
+        index,
+        "<p>{} domains, <a href=\"data/domains.json\">download as json</a></p>",
+        entries.len()
+    )?;
+    writeln!(
+        index,
+        "<table><tr><th>Domain</th><th>Blocked by</th><th>Suspended by</th></tr>"
+    )?;
+    for domain in &published {
+        let suspended = domain
+            .blocked_by
+            .iter()
+            .filter(|b| b.severity == DomainBlockSeverity::Suspend)
+            .count();
+        writeln!(
+            index,
+            "<tr><td><a href=\"domains/{}.html\">{}</a></td><td>{}</td><td>{suspended}</td></tr>",
+            domain.digest,
+            escape(display_name(domain)),
+            domain.blocked_by.len(),
+        )?;
+    }
+    writeln!(index, "</table>")?;
+    write_page(&out_dir.join("index.html"), "Blocked domains", &index)?;
+
+    for domain in &published {
+        let mut page = String::new();
+        writeln!(page, "<p><a href=\"../index.html\">All domains</a></p>")?;
+        writeln!(page, "<h1>{}</h1>", escape(display_name(domain)))?;
+        writeln!(page, "<p>SHA-256: <code>{}</code></p>", domain.digest)?;
+        if !domain.partial_domains.is_empty() {
+            writeln!(
+                page,
+                "<p>Seen as: {}</p>",
+                escape(&domain.partial_domains.join(", "))
+            )?;
+        }
+
+        writeln!(
+            page,
+            "<table><tr><th>Blocked by</th><th>Severity</th><th>Reason</th></tr>"
+        )?;
+        let groups = output::group_by(domain.blocked_by, |b| {
+            (b.severity_label(), b.comment.clone())
+        });
+        for group in groups {
+            let blocker = group[0];
+            let instances = group
+                .iter()
+                .map(|b| b.instance.as_str())
+                .collect::<Vec<_>>();
+            writeln!(
+                page,
+                "<tr><td>{}</td><td class=\"{}\">{}</td><td>{}</td></tr>",
+                escape(&instances.join(", ")),
+                blocker.severity,
+                escape(&blocker.severity_label()),
+                escape(blocker.comment.as_deref().unwrap_or_default()),
+            )?;
+        }
+        writeln!(page, "</table>")?;
+
+        write_page(
+            &out_dir.join(format!("domains/{}.html", domain.digest)),
+            display_name(domain),
+            &page,
+        )?;
+    }
+
+    println!(
+        "Published {} domains to {}",
+        entries.len(),
+        out_dir.display()
+    );
+
+    Ok(())
+}
+
+/// The severity most instances use, preferring suspend on a tie. Noop is only used when no
+/// instance limits federation.
+pub fn consensus_severity(
+    severities: impl IntoIterator<Item = DomainBlockSeverity>,
+) -> DomainBlockSeverity {
+    let severities = severities.into_iter().collect::<Vec<_>>();
+    let count =
+        |severity: DomainBlockSeverity| severities.iter().filter(|s| **s == severity).count();
+
+    let suspended = count(DomainBlockSeverity::Suspend);
+    let silenced = count(DomainBlockSeverity::Silence);
+    match (suspended, silenced) {
+        (0, 0) if count(DomainBlockSeverity::Noop) > 0 => DomainBlockSeverity::Noop,
+        (0, 0) => DomainBlockSeverity::Unknown,
+        _ if suspended >= silenced => DomainBlockSeverity::Suspend,
+        _ => DomainBlockSeverity::Silence,
+    }
+}
+
+fn display_name<'a>(domain: &PublishedDomain<'a>) -> &'a str {
+    domain
+        .domain
+        .or_else(|| domain.partial_domains.first().copied())
+        .unwrap_or_default()
+}
+
+fn write_page(path: &Path, title: &str, body: &str) -> Result<()> {
+    let html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title><style>{STYLE}</style></head>\n<body>\n{body}</body></html>\n",
+        escape(title)
+    );
+
+    write_if_changed(path, html.as_bytes()).context("write page")
+}
+
+/// Leaves files with the same content alone, so their modification time and the ETag web servers
+/// derive from it only change when the data does and clients polling them can be answered with
+/// `304 Not Modified`.
+fn write_if_changed(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    if std::fs::read(path).is_ok_and(|existing| existing == content) {
+        return Ok(());
+    }
+
+    std::fs::write(path, content)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape("<script>alert('hi & bye')</script>"),
+            "&lt;script&gt;alert(&#39;hi &amp; bye&#39;)&lt;/script&gt;"
+        );
+    }
+}
@@ -0,0 +1,478 @@
+use std::io::Write;
+
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use serde::Serialize;
+
+use crate::{
+    api::DomainBlockSeverity,
+    config::Config,
+    database::DatabaseAccess,
+    export::ExportFormat,
+    filter::{self, EntryFilter},
+    output::{Color, Table},
+    publish, score, DomainEntry,
+};
+
+/// A field of a blocked domain that a query can test.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    /// How many instances block the domain.
+    Blockers,
+    Suspends,
+    Silences,
+    Score,
+    /// The severity most blockers agree on.
+    Severity,
+    Resolved,
+    /// The domain, or its partial domains while it isn't resolved.
+    Domain,
+    Tag,
+    /// Any of the blocking instances.
+    Instance,
+    /// The language of any block comment.
+    Lang,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Number,
+    Text,
+    Bool,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "blockers" => Self::Blockers,
+            "suspends" => Self::Suspends,
+            "silences" => Self::Silences,
+            "score" => Self::Score,
+            "severity" => Self::Severity,
+            "resolved" => Self::Resolved,
+            "domain" => Self::Domain,
+            "tag" => Self::Tag,
+            "instance" => Self::Instance,
+            "lang" => Self::Lang,
+            _ => return None,
+        })
+    }
+
+    fn kind(self) -> Kind {
+        match self {
+            Self::Blockers | Self::Suspends | Self::Silences | Self::Score => Kind::Number,
+            Self::Severity | Self::Domain | Self::Tag | Self::Instance | Self::Lang => Kind::Text,
+            Self::Resolved => Kind::Bool,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    /// Matches a glob pattern such as `*.jp`.
+    Glob,
+}
+
+/// A parsed query, such as `blockers >= 5 && severity == suspend && resolved`.
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Flag(Field),
+    Number(Field, Op, f64),
+    Text(Field, Op, String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(&'static str),
+}
+
+const OPERATORS: &[&str] = &[
+    "&&", "||", "==", "!=", ">=", "<=", ">", "<", "!", "~", "(", ")",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(*op));
+            rest = &rest[op.len()..];
+        } else if let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') {
+            let end = rest[1..]
+                .find(quote)
+                .ok_or_else(|| eyre!("unterminated string: {rest}"))?;
+            tokens.push(Token::Quoted(rest[1..end + 1].to_string()));
+            rest = &rest[end + 2..];
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || "_.-*?:".contains(c)))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(eyre!("unexpected character in query: {rest}"));
+            }
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Parses a query. `&&` binds stronger than `||`, `!` negates and parentheses group.
+pub fn parse(input: &str) -> Result<Expr> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.or()?;
+    match parser.next() {
+        None => Ok(expr),
+        Some(token) => Err(eyre!("unexpected {token:?} in query")),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        let matches = matches!(self.tokens.get(self.pos), Some(Token::Op(o)) if *o == op);
+        if matches {
+            self.pos += 1;
+        }
+        matches
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.eat("||") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.eat("&&") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let expr = self.or()?;
+            if !self.eat(")") {
+                return Err(eyre!("missing ) in query"));
+            }
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let name = match self.next() {
+            Some(Token::Word(name)) => name,
+            Some(token) => return Err(eyre!("expected a field, found {token:?}")),
+            None => return Err(eyre!("query ends too early")),
+        };
+        let field = Field::parse(&name).ok_or_else(|| eyre!("unknown field {name}"))?;
+
+        let op = match self.tokens.get(self.pos) {
+            Some(Token::Op("==")) => Op::Eq,
+            Some(Token::Op("!=")) => Op::Ne,
+            Some(Token::Op(">")) => Op::Gt,
+            Some(Token::Op(">=")) => Op::Ge,
+            Some(Token::Op("<")) => Op::Lt,
+            Some(Token::Op("<=")) => Op::Le,
+            Some(Token::Op("~")) => Op::Glob,
+            _ if field.kind() == Kind::Bool => return Ok(Expr::Flag(field)),
+            _ => return Err(eyre!("{name} needs to be compared to a value")),
+        };
+        self.pos += 1;
+
+        let value = match self.next() {
+            Some(Token::Word(value) | Token::Quoted(value)) => value,
+            _ => return Err(eyre!("expected a value after {name}")),
+        };
+
+        match field.kind() {
+            Kind::Number if op != Op::Glob => {
+                let value = value
+                    .parse()
+                    .map_err(|_| eyre!("{name} is compared to a number, not {value}"))?;
+                Ok(Expr::Number(field, op, value))
+            }
+            Kind::Text if matches!(op, Op::Eq | Op::Ne | Op::Glob) => {
+                Ok(Expr::Text(field, op, value.to_lowercase()))
+            }
+            _ => Err(eyre!("{name} can't be compared with {op:?}")),
+        }
+    }
+}
+
+/// A blocked domain with the values a query tests.
+pub struct Row<'a> {
+    pub entry: &'a DomainEntry,
+    pub score: f64,
+}
+
+impl Row<'_> {
+    fn number(&self, field: Field) -> f64 {
+        let count = |severity| {
+            self.entry
+                .blocked_by
+                .values()
+                .filter(|b| b.severity == severity)
+                .count() as f64
+        };
+        match field {
+            Field::Blockers => self.entry.blocked_by.len() as f64,
+            Field::Suspends => count(DomainBlockSeverity::Suspend),
+            Field::Silences => count(DomainBlockSeverity::Silence),
+            Field::Score => self.score,
+            _ => 0.,
+        }
+    }
+
+    fn texts(&self, field: Field) -> Vec<String> {
+        let entry = self.entry;
+        match field {
+            Field::Severity => vec![self.severity().to_string()],
+            Field::Domain => match &entry.known_domain {
+                Some(domain) => vec![domain.clone()],
+                None => entry.partial_domains.iter().cloned().collect(),
+            },
+            Field::Tag => entry.annotation.tags.iter().cloned().collect(),
+            Field::Instance => entry.blocked_by.keys().cloned().collect(),
+            Field::Lang => entry
+                .blocked_by
+                .values()
+                .filter_map(|b| b.comment_language.clone())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn severity(&self) -> DomainBlockSeverity {
+        publish::consensus_severity(self.entry.blocked_by.values().map(|b| b.severity))
+    }
+
+    /// Whether this domain matches the query. Fields with several values, such as `tag`, match
+    /// `==` and `~` if any of their values does, and `!=` if none does.
+    pub fn matches(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::And(a, b) => self.matches(a) && self.matches(b),
+            Expr::Or(a, b) => self.matches(a) || self.matches(b),
+            Expr::Not(a) => !self.matches(a),
+            Expr::Flag(Field::Resolved) => self.entry.known_domain.is_some(),
+            Expr::Flag(_) => false,
+            Expr::Number(field, op, value) => {
+                let actual = self.number(*field);
+                match op {
+                    Op::Eq => actual == *value,
+                    Op::Ne => actual != *value,
+                    Op::Gt => actual > *value,
+                    Op::Ge => actual >= *value,
+                    Op::Lt => actual < *value,
+                    Op::Le => actual <= *value,
+                    Op::Glob => false,
+                }
+            }
+            Expr::Text(field, op, value) => {
+                let texts = self.texts(*field);
+                let mut texts = texts.iter().map(|t| t.to_lowercase());
+                match op {
+                    Op::Eq => texts.any(|t| t == *value),
+                    Op::Ne => texts.all(|t| t != *value),
+                    Op::Glob => texts.any(|t| filter::glob_match(value, &t)),
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct QueryResult<'a> {
+    digest: String,
+    domain: Option<&'a str>,
+    partial_domains: Vec<&'a str>,
+    score: f64,
+    blockers: usize,
+    severity: String,
+    tags: Vec<&'a str>,
+}
+
+/// Prints the domains matching the query, highest score first, as a table or in one of the
+/// export formats.
+pub fn query(
+    db: &mut DatabaseAccess,
+    config: &Config,
+    expr: &Expr,
+    format: Option<ExportFormat>,
+    filter: &EntryFilter,
+) -> Result<()> {
+    let scorer = score::Scorer::new(db, &config.scoring)?;
+    let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
+    let mut entries = vec![];
+    for key in keys {
+        let mut entry = db.get::<DomainEntry>(&key)?.unwrap();
+        if !filter.matches(&entry) {
+            continue;
+        }
+
+        // only the listed blocks count, as in `show`
+        entry.blocked_by.retain(|_, b| filter.blocker_matches(b));
+        let score = scorer.score(&entry.blocked_by);
+        let row = Row {
+            entry: &entry,
+            score,
+        };
+        if row.matches(expr) {
+            entries.push((score, entry));
+        }
+    }
+    entries.sort_by(|(score_a, a), (score_b, b)| {
+        score_b.total_cmp(score_a).then_with(|| a.cmp_display(b))
+    });
+
+    let rows = entries
+        .iter()
+        .map(|(score, entry)| Row {
+            entry,
+            score: *score,
+        })
+        .collect::<Vec<_>>();
+    let results = rows.iter().map(|row| QueryResult {
+        digest: hex::encode(row.entry.digest),
+        domain: row.entry.known_domain.as_deref(),
+        partial_domains: row
+            .entry
+            .partial_domains
+            .iter()
+            .map(|p| p.as_str())
+            .collect(),
+        score: row.score,
+        blockers: row.entry.blocked_by.len(),
+        severity: row.severity().to_string(),
+        tags: row
+            .entry
+            .annotation
+            .tags
+            .iter()
+            .map(|t| t.as_str())
+            .collect(),
+    });
+
+    let mut stdout = std::io::stdout().lock();
+    match format {
+        None => {
+            let mut table = Table::new(&["Domain", "Score", "Blockers", "Severity"]);
+            for row in &rows {
+                table.add_row(vec![
+                    (
+                        row.entry.display_domain().to_string(),
+                        row.entry.known_domain.is_none().then_some(Color::Dim),
+                    ),
+                    (format!("{:.1}", row.score), None),
+                    (row.entry.blocked_by.len().to_string(), None),
+                    (row.severity().to_string(), None),
+                ]);
+            }
+            table.print();
+            println!("{} domains", rows.len());
+        }
+        Some(ExportFormat::Json) => {
+            serde_json::to_writer_pretty(&mut stdout, &results.collect::<Vec<_>>())?;
+            writeln!(stdout)?;
+        }
+        Some(ExportFormat::Ndjson) => {
+            for result in results {
+                serde_json::to_writer(&mut stdout, &result)?;
+                writeln!(stdout)?;
+            }
+        }
+    }
+
+    stdout.flush().context("write query results")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use super::*;
+    use crate::BlockedBy;
+
+    #[test]
+    fn parses_and_evaluates() {
+        let expr = parse("blockers >= 2 && severity == suspend && resolved").unwrap();
+        assert_eq!(
+            expr,
+            Expr::And(
+                Box::new(Expr::And(
+                    Box::new(Expr::Number(Field::Blockers, Op::Ge, 2.)),
+                    Box::new(Expr::Text(Field::Severity, Op::Eq, "suspend".to_string())),
+                )),
+                Box::new(Expr::Flag(Field::Resolved)),
+            )
+        );
+
+        let block = |severity| BlockedBy {
+            severity,
+            original_severity: None,
+            limitations: Default::default(),
+            comment: None,
+            comment_language: None,
+        };
+        let entry = DomainEntry {
+            digest: crate::hash_domain("example.jp"),
+            known_domain: Some("example.jp".to_string()),
+            partial_domains: BTreeSet::from(["exa*ple.jp".to_string()]),
+            blocked_by: BTreeMap::from([
+                ("a.social".to_string(), block(DomainBlockSeverity::Suspend)),
+                ("b.social".to_string(), block(DomainBlockSeverity::Silence)),
+            ]),
+            annotation: Default::default(),
+        };
+        let row = Row {
+            entry: &entry,
+            score: 3.,
+        };
+
+        assert!(row.matches(&expr));
+        assert!(row.matches(&parse("domain ~ '*.jp' && !(score < 3)").unwrap()));
+        assert!(row.matches(&parse("instance == b.social || tag == spam").unwrap()));
+        assert!(!row.matches(&parse("suspends > 1").unwrap()));
+        assert!(!row.matches(&parse("tag == spam").unwrap()));
+
+        assert!(parse("blockers").is_err());
+        assert!(parse("severity > suspend").is_err());
+        assert!(parse("nonsense == 1").is_err());
+        assert!(parse("(resolved").is_err());
+    }
+}
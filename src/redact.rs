@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use crate::publish::Blocker;
+
+const MASK: &str = "[redacted]";
+
+/// Removes sensitive text from block comments before they are shared. Severities and domains
+/// are kept as they are.
+#[derive(Default)]
+pub struct Redaction {
+    /// Leave out comments entirely.
+    pub drop_comments: bool,
+    /// Mask these words in comments, ignoring case.
+    pub keywords: Vec<String>,
+}
+
+impl Redaction {
+    pub fn apply(&self, comment: Option<String>) -> Option<String> {
+        if self.drop_comments {
+            return None;
+        }
+
+        let mut comment = comment?;
+        for keyword in &self.keywords {
+            comment = mask(&comment, keyword);
+        }
+        Some(comment)
+    }
+
+    pub fn apply_to_blockers(&self, blockers: &mut HashMap<String, Vec<Blocker>>) {
+        for blocker in blockers.values_mut().flatten() {
+            blocker.comment = self.apply(blocker.comment.take());
+        }
+    }
+}
+
+fn mask(text: &str, keyword: &str) -> String {
+    if keyword.is_empty() {
+        return text.to_string();
+    }
+
+    // ascii lowercasing keeps byte offsets, so matches can be cut out of the original text
+    let lower = text.to_ascii_lowercase();
+    let keyword = keyword.to_ascii_lowercase();
+
+    let mut masked = String::new();
+    let mut last = 0;
+    for (start, _) in lower.match_indices(&keyword) {
+        masked += &text[last..start];
+        masked += MASK;
+        last = start + keyword.len();
+    }
+    masked += &text[last..];
+    masked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_keywords() {
+        let redaction = Redaction {
+            drop_comments: false,
+            keywords: vec!["doxx".to_string()],
+        };
+
+        assert_eq!(
+            redaction.apply(Some("Doxxing, spam and doxx".to_string())),
+            Some("[redacted]ing, spam and [redacted]".to_string())
+        );
+        assert_eq!(redaction.apply(None), None);
+
+        let redaction = Redaction {
+            drop_comments: true,
+            keywords: vec![],
+        };
+        assert_eq!(redaction.apply(Some("spam".to_string())), None);
+    }
+}
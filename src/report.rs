@@ -0,0 +1,443 @@
+use std::collections::BTreeMap;
+
+use color_eyre::{eyre::eyre, Result};
+
+use crate::{
+    api::DomainBlockSeverity,
+    archive,
+    database::{DatabaseAccess, DatabaseObject},
+    enrich::{ProbeResult, RdapInfo},
+    fetch::{self, InstanceStatus},
+    jobs::{CrackJob, CrackStats},
+    journal::{Event, JournalEntry},
+    keyspace,
+    metrics::Counters,
+    output::{Color, Table},
+    publish, translate, DomainEntry, MastodonBlockList,
+};
+
+/// Lists domains that some instances silence and others suspend, most blocked first.
+pub fn conflicts(db: &mut DatabaseAccess) -> Result<()> {
+    let blockers = publish::blockers_by_digest(db)?;
+    let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
+
+    let mut conflicts = vec![];
+    for key in keys {
+        let Some(blockers) = blockers.get(&key) else {
+            continue;
+        };
+
+        let count = |severity: DomainBlockSeverity| {
+            blockers.iter().filter(|b| b.severity == severity).count()
+        };
+        let suspended = count(DomainBlockSeverity::Suspend);
+        let silenced = count(DomainBlockSeverity::Silence);
+        if suspended > 0 && silenced > 0 {
+            let entry = db.get::<DomainEntry>(&key)?.unwrap();
+            conflicts.push((entry, suspended, silenced));
+        }
+    }
+    conflicts.sort_by(
+        |(a, a_suspended, a_silenced), (b, b_suspended, b_silenced)| {
+            (b_suspended + b_silenced)
+                .cmp(&(a_suspended + a_silenced))
+                .then_with(|| a.cmp_display(b))
+        },
+    );
+
+    println!(
+        "{} domains are silenced by some instances and suspended by others:",
+        conflicts.len()
+    );
+    let mut table = Table::new(&["Domain", "Digest", "Suspended by", "Silenced by"]);
+    for (entry, suspended, silenced) in &conflicts {
+        table.add_row(vec![
+            (entry.display_domain().to_string(), None),
+            (entry.get_id().to_string(), Some(Color::Dim)),
+            (suspended.to_string(), Some(Color::Red)),
+            (silenced.to_string(), Some(Color::Yellow)),
+        ]);
+    }
+    table.print();
+
+    Ok(())
+}
+
+/// Shows when each instance started or stopped blocking a domain and when it changed the
+/// severity, by comparing the archived responses of every instance.
+pub fn timeline(archive_dir: &str, domain: &str) -> Result<()> {
+    let digest = digest_of(domain);
+
+    let mut changes = vec![];
+    for instance in archive::domains(archive_dir)? {
+        let mut previous = None;
+        for timestamp in archive::timestamps(archive_dir, &instance)? {
+            let body = archive::load(archive_dir, &instance, timestamp)?;
+            let Ok(parsed) = fetch::parse_blocklist(&body) else {
+                continue;
+            };
+
+            let current = parsed
+                .list
+                .iter()
+                .find(|b| b.digest == digest)
+                .map(|b| b.severity);
+            let change = match (previous, current) {
+                (None, Some(severity)) => {
+                    Some((format!("started blocking ({severity})"), severity))
+                }
+                (Some(_), None) => {
+                    Some(("stopped blocking".to_string(), DomainBlockSeverity::Unknown))
+                }
+                (Some(old), Some(new)) if old != new => {
+                    Some((format!("changed severity from {old} to {new}"), new))
+                }
+                _ => None,
+            };
+            if let Some((change, severity)) = change {
+                changes.push((timestamp, instance.clone(), change, severity));
+            }
+            previous = current;
+        }
+    }
+    changes.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+
+    if changes.is_empty() {
+        println!("No archived responses block {domain}");
+        return Ok(());
+    }
+
+    let now = crate::unix_now();
+    let mut table = Table::new(&["When", "Instance", "Change"]);
+    for (timestamp, instance, change, severity) in changes {
+        table.add_row(vec![
+            (
+                format!("{} ago", crate::format_age(now.saturating_sub(timestamp))),
+                None,
+            ),
+            (instance, None),
+            (change, severity_cell(severity).1),
+        ]);
+    }
+    table.print();
+
+    Ok(())
+}
+
+/// Prints the domains only `a` blocks, only `b` blocks, and both block.
+pub fn compare(db: &mut DatabaseAccess, a: &str, b: &str) -> Result<()> {
+    let a_blocks = blocks_by_digest(db, a)?;
+    let b_blocks = blocks_by_digest(db, b)?;
+
+    let mut only_a = vec![];
+    let mut both = vec![];
+    for (digest, (name, a_severity)) in &a_blocks {
+        match b_blocks.get(digest) {
+            Some((_, b_severity)) => both.push(vec![
+                (name.clone(), None),
+                severity_cell(*a_severity),
+                severity_cell(*b_severity),
+            ]),
+            None => only_a.push(vec![(name.clone(), None), severity_cell(*a_severity)]),
+        }
+    }
+
+    let only_b = b_blocks
+        .iter()
+        .filter(|(digest, _)| !a_blocks.contains_key(*digest))
+        .map(|(_, (name, b_severity))| vec![(name.clone(), None), severity_cell(*b_severity)])
+        .collect();
+
+    for (title, table) in [
+        (
+            format!("Only blocked by {a}"),
+            sorted_table(&["Domain", "Severity"], only_a),
+        ),
+        (
+            format!("Only blocked by {b}"),
+            sorted_table(&["Domain", "Severity"], only_b),
+        ),
+        (
+            "Blocked by both".to_string(),
+            sorted_table(&["Domain", a, b], both),
+        ),
+    ] {
+        println!("{title} ({}):", table.len());
+        if !table.is_empty() {
+            table.print();
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+/// Gets the blocks of an instance by digest, with the best known name of each domain. Different
+/// domains can share a name while they are only known by a partial domain, so only the digest
+/// tells them apart.
+fn blocks_by_digest(
+    db: &mut DatabaseAccess,
+    instance: &str,
+) -> Result<BTreeMap<String, (String, DomainBlockSeverity)>> {
+    let Some(blocklist) = db.get::<MastodonBlockList>(instance)? else {
+        return Err(eyre!("No blocklist was fetched for {instance}"));
+    };
+
+    let mut blocks = BTreeMap::new();
+    for block in blocklist.list {
+        let digest = block.digest.to_lowercase();
+        let name = match db.get::<DomainEntry>(&digest)? {
+            Some(entry) => entry.display_domain().to_string(),
+            None => block.domain,
+        };
+        blocks.insert(digest, (name, block.severity));
+    }
+
+    Ok(blocks)
+}
+
+/// Builds a table of rows sorted by their first column.
+fn sorted_table(headers: &[&str], mut rows: Vec<Vec<(String, Option<Color>)>>) -> Table {
+    rows.sort_by(|a, b| a[0].0.cmp(&b[0].0));
+
+    let mut table = Table::new(headers);
+    for row in rows {
+        table.add_row(row);
+    }
+    table
+}
+
+pub fn severity_cell(severity: DomainBlockSeverity) -> (String, Option<Color>) {
+    let color = match severity {
+        DomainBlockSeverity::Suspend => Color::Red,
+        DomainBlockSeverity::Silence => Color::Yellow,
+        DomainBlockSeverity::Noop | DomainBlockSeverity::Unknown => Color::Dim,
+    };
+    (severity.to_string(), Some(color))
+}
+
+/// Gets the digest of a domain. Digests can be passed directly, for domains that aren't resolved
+/// yet.
+pub fn digest_of(domain_or_digest: &str) -> String {
+    if domain_or_digest.len() == 64 && domain_or_digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        domain_or_digest.to_lowercase()
+    } else {
+        hex::encode(crate::hash_domain(&domain_or_digest.to_lowercase()))
+    }
+}
+
+/// Lists every unresolved domain with the partial domain that is cheapest to brute-force, its
+/// keyspace and how long it would take at the hash rate measured by the last crack run.
+pub fn estimate(db: &mut DatabaseAccess) -> Result<()> {
+    let hash_rate = db
+        .get::<Counters>("global")?
+        .map(|c| c.last_hash_rate)
+        .filter(|rate| *rate > 0.);
+    if hash_rate.is_none() {
+        println!("No hash rate measured yet, run crack to measure it");
+    }
+
+    let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
+    let mut estimates = vec![];
+    for key in keys {
+        let entry = db.get::<DomainEntry>(&key)?.unwrap();
+        if entry.known_domain.is_some() {
+            continue;
+        }
+
+        let cheapest = entry
+            .partial_domains
+            .iter()
+            .map(|p| {
+                let charsets = keyspace::wildcard_charsets(p, &entry.partial_domains);
+                (keyspace::keyspace_size(&charsets), p.clone())
+            })
+            .min();
+        if let Some((size, pattern)) = cheapest {
+            estimates.push((size, pattern, key));
+        }
+    }
+    estimates.sort();
+
+    let mut table = Table::new(&["Digest", "Pattern", "Keyspace", "Estimated time", "Spent"]);
+    for (size, pattern, digest) in estimates {
+        let spent = match db.get::<CrackStats>(&digest)? {
+            Some(stats) => crate::format_age(stats.cpu_seconds() as u64),
+            None => String::new(),
+        };
+        let (time, color) = match hash_rate {
+            Some(rate) => {
+                let seconds = (size as f64 / rate).min(u64::MAX as f64) as u64;
+                let color = match seconds {
+                    0..=86_399 => Color::Green,
+                    86_400..=31_535_999 => Color::Yellow,
+                    _ => Color::Red,
+                };
+                (crate::format_age(seconds), Some(color))
+            }
+            None => ("unknown".to_string(), None),
+        };
+        table.add_row(vec![
+            (digest, None),
+            (pattern, None),
+            (size.to_string(), None),
+            (time, color),
+            (spent, None),
+        ]);
+    }
+    table.print();
+
+    Ok(())
+}
+
+/// Explains the domain whose digest starts with `prefix`, for correlating with tools that only
+/// show (shortened) hashes. An ambiguous prefix lists the digests it matches.
+pub fn lookup_digest(db: &mut DatabaseAccess, prefix: &str) -> Result<()> {
+    if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(eyre!("{prefix} is not a hex digest prefix"));
+    }
+
+    let prefix = prefix.to_lowercase();
+    let matches = db
+        .iter_keys::<DomainEntry>()
+        .filter(|k| k.starts_with(&prefix))
+        .collect::<Vec<_>>();
+
+    match matches.as_slice() {
+        [] => Err(eyre!(
+            "No blocked domain with a digest starting with {prefix}"
+        )),
+        [digest] => explain(db, digest),
+        _ => {
+            let mut table = Table::new(&["Digest", "Domain"]);
+            for digest in &matches {
+                let entry = db.get::<DomainEntry>(digest)?.unwrap();
+                table.add_row(vec![
+                    (digest.clone(), None),
+                    (entry.display_domain().to_string(), None),
+                ]);
+            }
+            table.print();
+
+            Err(eyre!(
+                "{} digests start with {prefix}, use a longer prefix",
+                matches.len()
+            ))
+        }
+    }
+}
+
+/// Prints everything known about a domain: where it was seen, how it was resolved and whether
+/// the resolution is valid.
+pub fn explain(db: &mut DatabaseAccess, domain_or_digest: &str) -> Result<()> {
+    let digest = digest_of(domain_or_digest);
+    let Some(entry) = db.get::<DomainEntry>(&digest)? else {
+        return Err(eyre!("No blocked domain with digest {digest}"));
+    };
+    let now = crate::unix_now();
+
+    println!("Digest: {digest}");
+    match &entry.known_domain {
+        Some(domain) if hex::encode(crate::hash_domain(domain)) == digest => {
+            println!("Domain: {domain} (matches digest)")
+        }
+        Some(domain) => println!("Domain: {domain} (DOES NOT match digest)"),
+        None => println!("Domain: unknown"),
+    }
+    if !entry.partial_domains.is_empty() {
+        println!("Seen as:");
+        for partial in &entry.partial_domains {
+            let job_state = db
+                .get::<CrackJob>(&format!("{digest}/{partial}"))?
+                .map(|j| format!(" (crack job {})", j.state))
+                .unwrap_or_default();
+            println!("- {partial}{job_state}");
+        }
+    }
+
+    let blockers = publish::blockers_by_digest(db)?
+        .remove(&digest)
+        .unwrap_or_default();
+    println!("Blocked by {} instances:", blockers.len());
+    for blocker in &blockers {
+        let checked = match db.get::<InstanceStatus>(&blocker.instance)? {
+            Some(status) => format!(
+                ", fetched {} ago",
+                crate::format_age(now.saturating_sub(status.checked_at))
+            ),
+            None => String::new(),
+        };
+        let comment = match &blocker.comment {
+            Some(comment) => format!(": {}", translate::with_translation(db, comment)?),
+            None => String::new(),
+        };
+        println!(
+            "- {} ({}{checked}){comment}",
+            blocker.instance,
+            blocker.severity_label()
+        );
+    }
+
+    if let Some(stats) = db.get::<CrackStats>(&digest)? {
+        println!("Crack attempts:");
+        for (source, source_stats) in &stats.by_source {
+            println!(
+                "- {source}: {} runs, {} candidates, {} of cpu time",
+                source_stats.runs,
+                source_stats.candidates,
+                crate::format_age(source_stats.cpu_seconds as u64)
+            );
+        }
+    }
+
+    if !entry.annotation.tags.is_empty() {
+        let tags = entry.annotation.tags.iter().cloned().collect::<Vec<_>>();
+        println!("Tags: {}", tags.join(", "));
+    }
+    for note in &entry.annotation.notes {
+        println!(
+            "Note from {} ago: {}",
+            crate::format_age(now.saturating_sub(note.at)),
+            note.text
+        );
+    }
+
+    let keys = db.iter_keys::<JournalEntry>().collect::<Vec<_>>();
+    for key in keys {
+        let journal_entry = db.get::<JournalEntry>(&key)?.unwrap();
+        if let Event::Resolved {
+            digest: resolved_digest,
+            domain,
+            source,
+        } = journal_entry.event
+        {
+            if resolved_digest == digest {
+                println!(
+                    "Resolved to {domain} through {source} {} ago",
+                    crate::format_age(now.saturating_sub(journal_entry.at))
+                );
+            }
+        }
+    }
+
+    if let Some(domain) = &entry.known_domain {
+        if let Some(rdap) = db.get::<RdapInfo>(domain)? {
+            match rdap.error {
+                Some(error) => println!("Registration: lookup failed: {error}"),
+                None => println!(
+                    "Registration: {} at {}, registered {}",
+                    rdap.registered_domain.unwrap_or_default(),
+                    rdap.registrar.as_deref().unwrap_or("unknown registrar"),
+                    rdap.registered_at
+                        .as_deref()
+                        .unwrap_or("at an unknown date")
+                ),
+            }
+        }
+        if let Some(probe) = db.get::<ProbeResult>(domain)? {
+            println!("Probe: {}", probe.summary(now));
+        }
+    }
+
+    Ok(())
+}
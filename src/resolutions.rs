@@ -0,0 +1,135 @@
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::DatabaseAccess,
+    journal::{self, Event},
+    DomainEntry,
+};
+
+/// A claim that a digest belongs to a domain, contributed by someone else.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Resolution {
+    pub digest: String,
+    pub domain: String,
+}
+
+#[derive(Default)]
+pub struct ImportSummary {
+    pub accepted: usize,
+    pub already_known: usize,
+    /// Resolutions of domains no fetched instance blocks, which are skipped.
+    pub not_blocked: usize,
+    pub rejected: Vec<(Resolution, &'static str)>,
+}
+
+impl ImportSummary {
+    pub fn print(&self) {
+        println!("Accepted {} resolutions", self.accepted);
+        println!("Skipped {} already known resolutions", self.already_known);
+        if self.not_blocked > 0 {
+            println!(
+                "Skipped {} resolutions of domains that aren't blocked",
+                self.not_blocked
+            );
+        }
+        println!("Rejected {} resolutions", self.rejected.len());
+        for (resolution, reason) in &self.rejected {
+            println!("- {} -> {}: {reason}", resolution.digest, resolution.domain);
+        }
+    }
+}
+
+/// Stores contributed resolutions after verifying that each domain hashes to its digest, so
+/// invalid or malicious mappings never end up in the database. `source` is recorded in the
+/// journal, such as `import`.
+pub fn apply_resolutions(
+    db: &mut DatabaseAccess,
+    resolutions: Vec<Resolution>,
+    source: &str,
+) -> Result<ImportSummary> {
+    let mut summary = ImportSummary::default();
+
+    for resolution in resolutions {
+        let digest = resolution.digest.to_lowercase();
+        let domain = resolution.domain.trim().to_lowercase();
+
+        if hex::encode(crate::hash_domain(&domain)) != digest {
+            summary
+                .rejected
+                .push((resolution, "domain does not hash to digest"));
+            continue;
+        }
+
+        let Some(mut entry) = db.get::<DomainEntry>(&digest)? else {
+            summary.rejected.push((resolution, "unknown digest"));
+            continue;
+        };
+
+        if entry.known_domain.is_some() {
+            summary.already_known += 1;
+            continue;
+        }
+
+        journal::record(
+            db,
+            Event::Resolved {
+                digest,
+                domain: domain.clone(),
+                source: source.into(),
+            },
+        )?;
+        entry.known_domain = Some(domain);
+        db.set(entry);
+        summary.accepted += 1;
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::database::DatabaseInstance;
+
+    const EXAMPLE_DIGEST: &str = "a379a6f6eeafb9a55e378c118034e2751e682fab9f2d30ab13d2125586ce1947";
+
+    #[test]
+    fn verifies_resolutions() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        db.set(DomainEntry {
+            digest: crate::hash_domain("example.com"),
+            known_domain: None,
+            partial_domains: BTreeSet::from(["exa*ple.com".to_string()]),
+            blocked_by: Default::default(),
+            annotation: Default::default(),
+        });
+
+        let summary = apply_resolutions(
+            &mut db,
+            vec![
+                Resolution {
+                    digest: EXAMPLE_DIGEST.to_string(),
+                    domain: "example.org".to_string(),
+                },
+                Resolution {
+                    digest: hex::encode(crate::hash_domain("unknown.com")),
+                    domain: "unknown.com".to_string(),
+                },
+                Resolution {
+                    digest: EXAMPLE_DIGEST.to_uppercase(),
+                    domain: "Example.com".to_string(),
+                },
+            ],
+            "import",
+        )
+        .unwrap();
+
+        assert_eq!(summary.accepted, 1);
+        assert_eq!(summary.rejected.len(), 2);
+        let entry = db.get::<DomainEntry>(EXAMPLE_DIGEST).unwrap().unwrap();
+        assert_eq!(entry.known_domain.as_deref(), Some("example.com"));
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::{BTreeSet, HashMap};
+
+use color_eyre::Result;
+
+use crate::{
+    crawl::FetchCandidate,
+    database::DatabaseAccess,
+    discover,
+    enrich::{ProbeResult, RdapInfo},
+    fetch::InstanceStatus,
+    DomainEntry, MastodonBlockList,
+};
+
+/// Checks every unresolved digest against every domain the database knows about, from any
+/// source: fetched and discovered instances, probed domains, resolved domains and their parent
+/// domains. Returns how many digests were resolved.
+pub fn resolve(db: &mut DatabaseAccess) -> Result<usize> {
+    let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
+    let mut corpus = BTreeSet::new();
+    let mut unresolved = HashMap::new();
+    for key in keys {
+        let entry = db.get::<DomainEntry>(&key)?.unwrap();
+        match entry.known_domain {
+            Some(domain) => corpus.extend(parent_domains(&domain)),
+            None => {
+                unresolved.insert(entry.digest, key);
+            }
+        }
+    }
+    if unresolved.is_empty() {
+        return Ok(0);
+    }
+
+    corpus.extend(discover::domain_corpus(db)?);
+    corpus.extend(db.iter_keys::<MastodonBlockList>());
+    corpus.extend(db.iter_keys::<InstanceStatus>());
+    corpus.extend(db.iter_keys::<FetchCandidate>());
+    corpus.extend(db.iter_keys::<ProbeResult>());
+    corpus.extend(db.iter_keys::<RdapInfo>());
+
+    let mut resolved = 0;
+    for domain in corpus {
+        let Some(key) = unresolved.remove(&crate::hash_domain(&domain)) else {
+            continue;
+        };
+
+        let entry = db.get::<DomainEntry>(&key)?.unwrap();
+        crate::store_resolution(db, entry, domain, "resolve")?;
+        resolved += 1;
+    }
+
+    Ok(resolved)
+}
+
+/// Gets the domains a domain is a subdomain of, such as `example.com` for `a.example.com`.
+/// Top level domains are left out.
+fn parent_domains(domain: &str) -> Vec<String> {
+    let mut parents = vec![];
+    let mut rest = domain;
+    while let Some((_, parent)) = rest.split_once('.') {
+        if !parent.contains('.') {
+            break;
+        }
+        parents.push(parent.to_string());
+        rest = parent;
+    }
+    parents
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::database::DatabaseInstance;
+
+    #[test]
+    fn resolves_from_any_known_domain() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        let entry = |domain: &str, known: bool| DomainEntry {
+            digest: crate::hash_domain(domain),
+            known_domain: known.then(|| domain.to_string()),
+            partial_domains: BTreeSet::from([domain.replace('e', "*")]),
+            blocked_by: Default::default(),
+            annotation: Default::default(),
+        };
+        db.set(entry("a.example.com", true));
+        db.set(entry("example.com", false));
+        db.set(entry("good.social", false));
+        db.set(entry("unknown.social", false));
+        db.set(MastodonBlockList {
+            domain: "good.social".to_string(),
+            list: vec![],
+            processed: true,
+            metadata: None,
+            removed: Default::default(),
+        });
+
+        assert_eq!(resolve(&mut db).unwrap(), 2);
+
+        let digest = hex::encode(crate::hash_domain("example.com"));
+        let resolved = db.get::<DomainEntry>(&digest).unwrap().unwrap();
+        assert_eq!(resolved.known_domain.as_deref(), Some("example.com"));
+        assert_eq!(resolve(&mut db).unwrap(), 0);
+    }
+
+    #[test]
+    fn lists_parent_domains() {
+        assert_eq!(
+            parent_domains("a.b.example.com"),
+            vec!["b.example.com", "example.com"]
+        );
+        assert!(parent_domains("example.com").is_empty());
+    }
+}
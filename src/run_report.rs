@@ -0,0 +1,166 @@
+use std::{fmt::Write, path::Path};
+
+use color_eyre::{eyre::Context, Result};
+use serde::Serialize;
+
+use crate::{
+    database::DatabaseAccess,
+    fetch::InstanceStatus,
+    journal::{self, Event, JournalEntry},
+};
+
+/// Verbs that write a report after every run when `reports_dir` is set.
+pub const REPORTED_VERBS: &[&str] = &["fetch", "process", "crack", "run"];
+
+/// What a single run did, kept as an auditable history of unattended runs.
+#[derive(Serialize)]
+struct RunReport {
+    verb: String,
+    started_at: u64,
+    finished_at: u64,
+    succeeded: bool,
+    fetched: Vec<FetchedInstance>,
+    /// Every journal event recorded during the run.
+    events: Vec<JournalEntry>,
+}
+
+#[derive(Serialize)]
+struct FetchedInstance {
+    domain: String,
+    status: String,
+    message: Option<String>,
+}
+
+/// Writes `<started_at>-<verb>.json` and `.md` to `reports_dir`, covering the events recorded
+/// since `first_event` and the instances checked since `started_at`.
+pub fn write(
+    db: &mut DatabaseAccess,
+    reports_dir: &str,
+    verb: &str,
+    started_at: u64,
+    first_event: u64,
+    succeeded: bool,
+) -> Result<()> {
+    let keys = db.iter_keys::<InstanceStatus>().collect::<Vec<_>>();
+    let mut fetched = vec![];
+    for key in keys {
+        let status = db.get::<InstanceStatus>(&key)?.unwrap();
+        if status.checked_at >= started_at {
+            fetched.push(FetchedInstance {
+                domain: status.domain,
+                status: status.status.to_string(),
+                message: status.message,
+            });
+        }
+    }
+
+    let report = RunReport {
+        verb: verb.to_string(),
+        started_at,
+        finished_at: crate::unix_now(),
+        succeeded,
+        fetched,
+        events: journal::events_since(db, first_event)?,
+    };
+
+    std::fs::create_dir_all(reports_dir).context("create reports directory")?;
+    let path = Path::new(reports_dir).join(format!("{started_at}-{verb}"));
+    std::fs::write(
+        path.with_extension("json"),
+        serde_json::to_vec_pretty(&report)?,
+    )
+    .context("write json report")?;
+    std::fs::write(path.with_extension("md"), render_markdown(&report))
+        .context("write markdown report")?;
+
+    Ok(())
+}
+
+fn render_markdown(report: &RunReport) -> String {
+    let mut md = String::new();
+    let result = if report.succeeded {
+        "succeeded"
+    } else {
+        "failed"
+    };
+    _ = writeln!(md, "# `{}` run at {}\n", report.verb, report.started_at);
+    _ = writeln!(
+        md,
+        "Took {} seconds and {result}.\n",
+        report.finished_at - report.started_at
+    );
+
+    if !report.fetched.is_empty() {
+        _ = writeln!(md, "## Fetched instances\n");
+        _ = writeln!(md, "| Instance | Status | Message |");
+        _ = writeln!(md, "| --- | --- | --- |");
+        for instance in &report.fetched {
+            _ = writeln!(
+                md,
+                "| {} | {} | {} |",
+                instance.domain,
+                instance.status,
+                instance.message.as_deref().unwrap_or_default()
+            );
+        }
+        md.push('\n');
+    }
+
+    _ = writeln!(md, "## Changes\n");
+    if report.events.is_empty() {
+        _ = writeln!(md, "Nothing changed.");
+    }
+    for entry in &report.events {
+        let line = match &entry.event {
+            Event::InstanceAdded { instance, blocks } => {
+                format!("{instance} was added with {blocks} blocks")
+            }
+            Event::NewBlock {
+                instance,
+                domain,
+                severity,
+                ..
+            } => format!("{instance} started blocking {domain} ({severity})"),
+            Event::Unblocked { instance, digest } => {
+                format!("{instance} stopped blocking {digest}")
+            }
+            Event::Resolved {
+                digest,
+                domain,
+                source,
+            } => format!("{digest} was resolved to {domain} ({source})"),
+        };
+        _ = writeln!(md, "- {line}");
+    }
+
+    md
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_markdown() {
+        let report = RunReport {
+            verb: "fetch".to_string(),
+            started_at: 100,
+            finished_at: 160,
+            succeeded: true,
+            fetched: vec![],
+            events: vec![JournalEntry {
+                id: 0,
+                at: 120,
+                event: Event::InstanceAdded {
+                    instance: "a.social".to_string(),
+                    blocks: 3,
+                },
+            }],
+        };
+
+        let md = render_markdown(&report);
+
+        assert!(md.starts_with("# `fetch` run at 100\n\nTook 60 seconds and succeeded.\n"));
+        assert!(md.contains("- a.social was added with 3 blocks\n"));
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::{BTreeMap, HashMap};
+
+use color_eyre::Result;
+
+use crate::{
+    api::DomainBlockSeverity, config::ScoringConfig, database::DatabaseAccess, enrich::ProbeResult,
+    BlockedBy,
+};
+
+/// Scores how strongly the fediverse agrees on blocking a domain: every blocking instance adds
+/// the weight of its severity, optionally scaled by the size of the instance.
+pub struct Scorer<'a> {
+    config: &'a ScoringConfig,
+    /// Monthly active users of instances, from their nodeinfo.
+    sizes: HashMap<String, u64>,
+}
+
+impl<'a> Scorer<'a> {
+    pub fn new(db: &mut DatabaseAccess, config: &'a ScoringConfig) -> Result<Self> {
+        let mut sizes = HashMap::new();
+        if config.weight_by_size {
+            let keys = db.iter_keys::<ProbeResult>().collect::<Vec<_>>();
+            for key in keys {
+                let probe = db.get::<ProbeResult>(&key)?.unwrap();
+                if let Some(active_month) = probe.active_month {
+                    sizes.insert(probe.domain, active_month);
+                }
+            }
+        }
+
+        Ok(Self { config, sizes })
+    }
+
+    pub fn score(&self, blocked_by: &BTreeMap<String, BlockedBy>) -> f64 {
+        blocked_by
+            .iter()
+            .map(|(instance, block)| {
+                self.severity_weight(block.severity) * self.size_weight(instance)
+            })
+            .sum()
+    }
+
+    fn severity_weight(&self, severity: DomainBlockSeverity) -> f64 {
+        match severity {
+            DomainBlockSeverity::Suspend => self.config.suspend,
+            DomainBlockSeverity::Silence => self.config.silence,
+            DomainBlockSeverity::Noop => self.config.noop,
+            DomainBlockSeverity::Unknown => self.config.unknown,
+        }
+    }
+
+    /// Grows with the order of magnitude of the active users, so a flagship instance counts a
+    /// few times as much as a single-user one rather than thousands of times. Instances of
+    /// unknown size count as the smallest.
+    fn size_weight(&self, instance: &str) -> f64 {
+        match self.sizes.get(instance) {
+            Some(active_month) => 1. + (1. + *active_month as f64).log10(),
+            None => 1.,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocked_by(blocks: &[(&str, DomainBlockSeverity)]) -> BTreeMap<String, BlockedBy> {
+        blocks
+            .iter()
+            .map(|(instance, severity)| {
+                (
+                    instance.to_string(),
+                    BlockedBy {
+                        severity: *severity,
+                        original_severity: None,
+                        limitations: Default::default(),
+                        comment: None,
+                        comment_language: None,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn weights_severity_and_size() {
+        let config = ScoringConfig {
+            weight_by_size: true,
+            ..Default::default()
+        };
+        let scorer = Scorer {
+            config: &config,
+            sizes: HashMap::from([("big.social".to_string(), 99_999)]),
+        };
+
+        let blocks = blocked_by(&[
+            ("a.social", DomainBlockSeverity::Suspend),
+            ("b.social", DomainBlockSeverity::Silence),
+        ]);
+        assert_eq!(scorer.score(&blocks), 3.);
+
+        let blocks = blocked_by(&[("big.social", DomainBlockSeverity::Silence)]);
+        assert_eq!(scorer.score(&blocks), 6.);
+    }
+}
@@ -0,0 +1,177 @@
+use std::{borrow::Cow, collections::BTreeMap};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{DatabaseAccess, DatabaseObject},
+    fetch::BlocklistFetcher,
+    journal::{self, Event},
+    DomainEntry,
+};
+
+/// Unobfuscated domains scraped from the moderated servers table on an instance's about page.
+#[derive(Serialize, Deserialize)]
+pub struct ScrapedBlocklist {
+    pub domain: String,
+    pub fetched_at: u64,
+    /// Maps hex digests to the domain they were verified to hash to.
+    pub resolved: BTreeMap<String, String>,
+}
+
+impl DatabaseObject for ScrapedBlocklist {
+    const KEY_NAME: &'static str = "scraped";
+
+    fn get_id(&self) -> Cow<str> {
+        (&self.domain).into()
+    }
+}
+
+pub async fn scrape_about_page(
+    db: &mut DatabaseAccess,
+    fetcher: &impl BlocklistFetcher,
+    domain: &str,
+) {
+    // mastodon 3.x lists moderated servers on /about/more, some forks still do on /about
+    let mut resolved = BTreeMap::new();
+    for path in ["about/more", "about"] {
+        let Ok(body) = fetcher.fetch_page(domain, path).await else {
+            continue;
+        };
+
+        resolved.extend(verified_domains(&String::from_utf8_lossy(&body)));
+    }
+
+    if !resolved.is_empty() {
+        println!(
+            "Scraped {} unobfuscated domains from the about page of {domain}",
+            resolved.len()
+        );
+
+        db.set(ScrapedBlocklist {
+            domain: domain.to_string(),
+            fetched_at: crate::unix_now(),
+            resolved,
+        });
+    }
+}
+
+/// Uses all scraped domains to resolve the digests of stored domain entries.
+pub fn apply_scraped(db: &mut DatabaseAccess) -> Result<usize> {
+    let keys = db.iter_keys::<ScrapedBlocklist>().collect::<Vec<_>>();
+
+    let mut applied = 0;
+    for key in keys {
+        let scraped = db.get::<ScrapedBlocklist>(&key)?.unwrap();
+        for (digest, domain) in scraped.resolved {
+            let Some(mut entry) = db.get::<DomainEntry>(&digest)? else {
+                continue;
+            };
+
+            if entry.known_domain.is_none() {
+                journal::record(
+                    db,
+                    Event::Resolved {
+                        digest,
+                        domain: domain.clone(),
+                        source: "about-page".into(),
+                    },
+                )?;
+                entry.known_domain = Some(domain);
+                db.set(entry);
+                applied += 1;
+            }
+        }
+    }
+
+    Ok(applied)
+}
+
+/// Gets the digest and domain pairs from the page where the domain is not obfuscated and its
+/// hash matches the digest.
+fn verified_domains(html: &str) -> Vec<(String, String)> {
+    parse_moderated_servers(html)
+        .into_iter()
+        .filter(|(digest, domain)| {
+            !domain.contains('*') && hex::encode(crate::hash_domain(domain)) == *digest
+        })
+        .collect()
+}
+
+/// Finds moderated servers in the page, rendered as `<span title="SHA-256: digest">domain</span>`.
+fn parse_moderated_servers(html: &str) -> Vec<(String, String)> {
+    const MARKER: &str = "title=\"SHA-256: ";
+
+    let mut found = vec![];
+    let mut rest = html;
+    while let Some(idx) = rest.find(MARKER) {
+        rest = &rest[idx + MARKER.len()..];
+        let Some(digest_end) = rest.find('"') else {
+            break;
+        };
+        let digest = rest[..digest_end].trim().to_lowercase();
+
+        rest = &rest[digest_end..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+
+        rest = &rest[tag_end + 1..];
+        let Some(text_end) = rest.find('<') else {
+            break;
+        };
+        let domain = rest[..text_end].trim().to_lowercase();
+
+        rest = &rest[text_end..];
+        found.push((digest, domain));
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"
+        <table>
+          <tr>
+            <td class="nowrap">
+              <span title="SHA-256: a379a6f6eeafb9a55e378c118034e2751e682fab9f2d30ab13d2125586ce1947">example.com</span>
+            </td>
+            <td>spam</td>
+          </tr>
+          <tr>
+            <td class="nowrap">
+              <span title="SHA-256: 0000000000000000000000000000000000000000000000000000000000000000">exa*ple.org</span>
+            </td>
+          </tr>
+          <tr>
+            <td class="nowrap">
+              <span title="SHA-256: 1111111111111111111111111111111111111111111111111111111111111111">wrong.com</span>
+            </td>
+          </tr>
+        </table>
+    "#;
+
+    #[test]
+    fn parse() {
+        let parsed = parse_moderated_servers(PAGE);
+
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[1].1, "exa*ple.org");
+    }
+
+    #[test]
+    fn verify() {
+        let verified = verified_domains(PAGE);
+
+        assert_eq!(
+            verified,
+            vec![(
+                "a379a6f6eeafb9a55e378c118034e2751e682fab9f2d30ab13d2125586ce1947".to_string(),
+                "example.com".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,58 @@
+/// Named groups of instances known to publish their blocklist, as a starting point for a crawl.
+/// Instances that stop publishing their blocklist only show up as failures in `health`, so
+/// these lists don't need to be exact.
+const PRESETS: &[(&str, &[&str])] = &[
+    (
+        "flagship",
+        &[
+            "mastodon.social",
+            "mastodon.online",
+            "mstdn.social",
+            "mas.to",
+            "mastodon.world",
+            "home.social",
+            "mastodon.cloud",
+        ],
+    ),
+    (
+        "japanese",
+        &[
+            "mstdn.jp",
+            "fedibird.com",
+            "mastodon-japan.net",
+            "best-friends.chat",
+            "mstdn.nere9.help",
+        ],
+    ),
+    (
+        "german",
+        &[
+            "chaos.social",
+            "norden.social",
+            "troet.cafe",
+            "mastodon.art",
+            "social.tchncs.de",
+        ],
+    ),
+    (
+        "tech",
+        &[
+            "fosstodon.org",
+            "hachyderm.io",
+            "infosec.exchange",
+            "floss.social",
+            "techhub.social",
+        ],
+    ),
+];
+
+pub fn preset(name: &str) -> Option<&'static [&'static str]> {
+    PRESETS
+        .iter()
+        .find(|(preset, _)| *preset == name)
+        .map(|(_, domains)| *domains)
+}
+
+pub fn preset_names() -> Vec<&'static str> {
+    PRESETS.iter().map(|(name, _)| *name).collect()
+}
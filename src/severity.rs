@@ -0,0 +1,143 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::OnceLock,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::api::{DomainBlockSeverity, Limitation};
+
+/// Terms other fediverse software uses for its block levels, and the mastodon severity they
+/// come closest to. Entries in `severity_map` in `config.json` override these.
+const DEFAULT_MAPPING: &[(&str, DomainBlockSeverity)] = &[
+    ("suspend", DomainBlockSeverity::Suspend),
+    ("silence", DomainBlockSeverity::Silence),
+    ("limit", DomainBlockSeverity::Silence),
+    // pleroma and akkoma mrf_simple
+    ("reject", DomainBlockSeverity::Suspend),
+    ("federated_timeline_removal", DomainBlockSeverity::Silence),
+    ("followers_only", DomainBlockSeverity::Silence),
+    ("media_removal", DomainBlockSeverity::Noop),
+    ("media_nsfw", DomainBlockSeverity::Noop),
+    ("report_removal", DomainBlockSeverity::Noop),
+    ("noop", DomainBlockSeverity::Noop),
+];
+
+/// Terms that stand for a limitation rather than a level of federation.
+const LIMITATIONS: &[(&str, Limitation)] = &[
+    ("media_removal", Limitation::RejectMedia),
+    ("reject_media", Limitation::RejectMedia),
+    ("media_nsfw", Limitation::MarkMediaSensitive),
+    ("report_removal", Limitation::RejectReports),
+    ("reject_reports", Limitation::RejectReports),
+];
+
+static MAPPING: OnceLock<HashMap<String, DomainBlockSeverity>> = OnceLock::new();
+
+/// Sets the configured severity mapping for this run. Until this is called only the default
+/// mapping is used, as in tests.
+pub fn init(overrides: &HashMap<String, DomainBlockSeverity>) {
+    let overrides = overrides
+        .iter()
+        .map(|(raw, severity)| (raw.to_lowercase(), *severity))
+        .collect();
+    _ = MAPPING.set(overrides);
+}
+
+/// Identifies the mapping in use, so a stored blocklist that was parsed with another mapping is
+/// parsed again even when the response didn't change.
+pub fn fingerprint() -> String {
+    let mut overrides = MAPPING.get().into_iter().flatten().collect::<Vec<_>>();
+    overrides.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mapping = format!("{DEFAULT_MAPPING:?} {LIMITATIONS:?} {overrides:?}");
+    hex::encode(Sha256::digest(mapping))
+}
+
+/// Maps a severity as published by an instance to the common severity.
+pub fn normalize(raw: &str) -> DomainBlockSeverity {
+    let raw = raw.trim().to_lowercase();
+    if let Some(severity) = MAPPING.get().and_then(|m| m.get(&raw)) {
+        return *severity;
+    }
+
+    DEFAULT_MAPPING
+        .iter()
+        .find(|(name, _)| *name == raw)
+        .map(|(_, severity)| *severity)
+        .unwrap_or_default()
+}
+
+/// The limitation a published severity stands for, if any.
+pub fn limitation(raw: &str) -> Option<Limitation> {
+    let raw = raw.trim().to_lowercase();
+    LIMITATIONS
+        .iter()
+        .find(|(name, _)| *name == raw)
+        .map(|(_, limitation)| *limitation)
+}
+
+/// The published severity, if it is not simply the name of the severity it maps to. This is
+/// kept next to the normalized severity so it can be shown as the instance published it.
+pub fn original(raw: &str, severity: DomainBlockSeverity) -> Option<String> {
+    (raw != severity.to_string()).then(|| raw.to_string())
+}
+
+/// The severity and its limitations, followed by the published term when it differs, such as
+/// `noop, reject media (media_removal)`.
+pub fn label(
+    severity: DomainBlockSeverity,
+    limitations: &BTreeSet<Limitation>,
+    original: Option<&str>,
+) -> String {
+    let mut label = severity.to_string();
+    for limitation in limitations {
+        label += &format!(", {limitation}");
+    }
+    if let Some(original) = original {
+        label += &format!(" ({original})");
+    }
+    label
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_other_software() {
+        assert_eq!(normalize("suspend"), DomainBlockSeverity::Suspend);
+        assert_eq!(normalize("Reject"), DomainBlockSeverity::Suspend);
+        assert_eq!(
+            normalize("federated_timeline_removal"),
+            DomainBlockSeverity::Silence
+        );
+        assert_eq!(normalize("something"), DomainBlockSeverity::Unknown);
+
+        assert_eq!(original("suspend", DomainBlockSeverity::Suspend), None);
+        assert_eq!(
+            original("reject", DomainBlockSeverity::Suspend).as_deref(),
+            Some("reject")
+        );
+        assert_eq!(normalize("media_removal"), DomainBlockSeverity::Noop);
+        assert_eq!(limitation("media_removal"), Some(Limitation::RejectMedia));
+        assert_eq!(limitation("reject"), None);
+
+        assert_eq!(
+            label(
+                DomainBlockSeverity::Suspend,
+                &BTreeSet::new(),
+                Some("reject")
+            ),
+            "suspend (reject)"
+        );
+        assert_eq!(
+            label(
+                DomainBlockSeverity::Noop,
+                &BTreeSet::from([Limitation::RejectMedia]),
+                Some("media_removal")
+            ),
+            "noop, reject media (media_removal)"
+        );
+    }
+}
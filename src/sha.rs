@@ -0,0 +1,98 @@
+use sha2::{compress256, digest::generic_array::GenericArray};
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Checks candidates of a pattern against a digest by running the SHA-256 compression function
+/// on prepared blocks. The message is padded once per pattern, and the blocks before the first
+/// wildcard are compressed once into a midstate, so only the blocks that change get compressed
+/// for every candidate. sha2 uses the SHA-NI instructions for this when the cpu has them.
+pub struct PatternMatcher {
+    midstate: [u32; 8],
+    /// The padded blocks from the first block with a wildcard onwards.
+    tail: Vec<u8>,
+    tail_offset: usize,
+    expected_state: [u32; 8],
+}
+
+impl PatternMatcher {
+    pub fn new(pattern: &[u8], expected_digest: [u8; 32]) -> Self {
+        let mut padded = pattern.to_vec();
+        padded.push(0x80);
+        while padded.len() % 64 != 56 {
+            padded.push(0);
+        }
+        padded.extend_from_slice(&(pattern.len() as u64 * 8).to_be_bytes());
+
+        let first_wildcard = pattern
+            .iter()
+            .position(|b| *b == b'*')
+            .unwrap_or(pattern.len());
+        let tail_offset = first_wildcard / 64 * 64;
+
+        let mut midstate = INITIAL_STATE;
+        compress(&mut midstate, &padded[..tail_offset]);
+
+        let mut expected_state = [0u32; 8];
+        for (word, bytes) in expected_state
+            .iter_mut()
+            .zip(expected_digest.chunks_exact(4))
+        {
+            *word = u32::from_be_bytes(bytes.try_into().unwrap());
+        }
+
+        Self {
+            midstate,
+            tail: padded[tail_offset..].to_vec(),
+            tail_offset,
+            expected_state,
+        }
+    }
+
+    /// Gets a copy of the changing blocks for a candidate to be written into. Byte `i` of the
+    /// pattern is at `i - tail_offset()` in it.
+    pub fn template(&self) -> Vec<u8> {
+        self.tail.clone()
+    }
+
+    pub fn tail_offset(&self) -> usize {
+        self.tail_offset
+    }
+
+    pub fn matches(&self, tail: &[u8]) -> bool {
+        let mut state = self.midstate;
+        compress(&mut state, tail);
+        state == self.expected_state
+    }
+}
+
+fn compress(state: &mut [u32; 8], blocks: &[u8]) {
+    for block in blocks.chunks_exact(64) {
+        compress256(state, std::slice::from_ref(GenericArray::from_slice(block)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, domain: &str, candidate: &str) -> bool {
+        let matcher = PatternMatcher::new(pattern.as_bytes(), crate::hash_domain(domain));
+        let mut tail = matcher.template();
+        let written = &candidate.as_bytes()[matcher.tail_offset()..];
+        tail[..written.len()].copy_from_slice(written);
+        matcher.matches(&tail)
+    }
+
+    #[test]
+    fn matches_sha256() {
+        assert!(matches("exa*ple.com", "example.com", "example.com"));
+        assert!(!matches("exa*ple.com", "example.com", "exaqple.com"));
+
+        // long enough for the prefix to be compressed into a midstate
+        let prefix = "a".repeat(70);
+        let domain = format!("{prefix}.example.com");
+        assert!(matches(&format!("{prefix}.exa*ple.com"), &domain, &domain));
+    }
+}
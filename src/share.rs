@@ -0,0 +1,193 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{DatabaseAccess, DatabaseObject},
+    export,
+    filter::EntryFilter,
+    resolutions::{self, ImportSummary, Resolution},
+    DomainEntry,
+};
+
+/// Version of the share bundle format, increased when a field is removed or changes meaning.
+pub const SHARE_VERSION: u32 = 1;
+
+/// Crack results that can be published without republishing anyone's moderation decisions: it
+/// has no comments, severities or blocking instances, and no resolution sources since those can
+/// name an instance.
+#[derive(Serialize, Deserialize)]
+pub struct ShareBundle {
+    pub version: u32,
+    pub generated_at: u64,
+    pub domains: Vec<SharedDomain>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SharedDomain {
+    pub digest: String,
+    #[serde(default)]
+    pub partial_domains: Vec<String>,
+    /// The domain, if it is known.
+    #[serde(default)]
+    pub domain: Option<String>,
+}
+
+/// Collects the digests, partial domains and resolutions of the blocked domains.
+pub fn bundle(db: &mut DatabaseAccess, filter: &EntryFilter) -> Result<ShareBundle> {
+    let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
+    let mut domains = vec![];
+    for key in keys {
+        let entry = db.get::<DomainEntry>(&key)?.unwrap();
+        if !filter.matches(&entry) {
+            continue;
+        }
+        domains.push(SharedDomain {
+            digest: entry.get_id().to_string(),
+            partial_domains: entry.partial_domains.into_iter().collect(),
+            domain: entry.known_domain,
+        });
+    }
+
+    Ok(ShareBundle {
+        version: SHARE_VERSION,
+        generated_at: export::generated_at(),
+        domains,
+    })
+}
+
+/// Writes the share bundle as json, to a file or stdout.
+pub fn share(db: &mut DatabaseAccess, output: Option<&str>, filter: &EntryFilter) -> Result<()> {
+    let bundle = bundle(db, filter)?;
+
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).context("create share bundle")?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    serde_json::to_writer_pretty(&mut writer, &bundle)?;
+    writeln!(writer)?;
+
+    writer.flush().context("write share bundle")?;
+    Ok(())
+}
+
+/// Merges the resolutions of someone else's share bundle, recording `source` as how they were
+/// resolved. Every domain is hashed again before it is stored, so a bundle can't map a digest to
+/// another domain. Digests that aren't blocked by any fetched instance are skipped.
+pub fn import(db: &mut DatabaseAccess, bundle: ShareBundle, source: &str) -> Result<ImportSummary> {
+    if bundle.version > SHARE_VERSION {
+        return Err(eyre!(
+            "Share bundle version {} is newer than the supported version {SHARE_VERSION}",
+            bundle.version
+        ));
+    }
+
+    let mut resolutions = vec![];
+    let mut not_blocked = 0;
+    for shared in bundle.domains {
+        let Some(domain) = shared.domain else {
+            continue;
+        };
+        if db
+            .get::<DomainEntry>(&shared.digest.to_lowercase())?
+            .is_none()
+        {
+            not_blocked += 1;
+            continue;
+        }
+        resolutions.push(Resolution {
+            digest: shared.digest,
+            domain,
+        });
+    }
+
+    let mut summary = resolutions::apply_resolutions(db, resolutions, source)?;
+    summary.not_blocked = not_blocked;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use super::*;
+    use crate::{api::DomainBlockSeverity, database::DatabaseInstance, BlockedBy};
+
+    #[test]
+    fn leaves_out_moderation_data() {
+        let mut db = DatabaseInstance::default().use_namespace("test_db");
+        db.set(DomainEntry {
+            digest: crate::hash_domain("example.com"),
+            known_domain: Some("example.com".to_string()),
+            partial_domains: BTreeSet::from(["exa*ple.com".to_string()]),
+            blocked_by: BTreeMap::from([(
+                "a.social".to_string(),
+                BlockedBy {
+                    severity: DomainBlockSeverity::Suspend,
+                    original_severity: None,
+                    limitations: Default::default(),
+                    comment: Some("spam".to_string()),
+                    comment_language: Some("en".to_string()),
+                },
+            )]),
+            annotation: Default::default(),
+        });
+
+        let bundle = bundle(&mut db, &EntryFilter::default()).unwrap();
+        let json = serde_json::to_string(&bundle).unwrap();
+
+        assert_eq!(bundle.domains.len(), 1);
+        assert_eq!(bundle.domains[0].domain.as_deref(), Some("example.com"));
+        assert!(!json.contains("a.social"));
+        assert!(!json.contains("spam"));
+        assert!(!json.contains("suspend"));
+    }
+
+    #[test]
+    fn imports_verified_resolutions() {
+        let mut db = DatabaseInstance::in_memory();
+        for domain in ["example.com", "example.org"] {
+            db.set(DomainEntry {
+                digest: crate::hash_domain(domain),
+                known_domain: None,
+                partial_domains: BTreeSet::from(["exa*ple.*".to_string()]),
+                blocked_by: Default::default(),
+                annotation: Default::default(),
+            });
+        }
+
+        let shared = |digest_of: &str, domain: &str| SharedDomain {
+            digest: hex::encode(crate::hash_domain(digest_of)),
+            partial_domains: vec![],
+            domain: Some(domain.to_string()),
+        };
+        let bundle = ShareBundle {
+            version: SHARE_VERSION,
+            generated_at: 0,
+            domains: vec![
+                shared("example.com", "example.com"),
+                // a malicious mapping of a blocked digest to another domain
+                shared("example.org", "innocent.org"),
+                shared("elsewhere.net", "elsewhere.net"),
+            ],
+        };
+
+        let summary = import(&mut db, bundle, "share").unwrap();
+
+        assert_eq!(summary.accepted, 1);
+        assert_eq!(summary.rejected.len(), 1);
+        assert_eq!(summary.not_blocked, 1);
+        let digest = hex::encode(crate::hash_domain("example.org"));
+        let entry = db.get::<DomainEntry>(&digest).unwrap().unwrap();
+        assert_eq!(entry.known_domain, None);
+    }
+}
@@ -0,0 +1,132 @@
+use std::{fs::File, io::Write};
+
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+/// A detached ed25519 signature of an exported dataset, stored next to it as `<file>.sig`.
+#[derive(Serialize, Deserialize)]
+pub struct DatasetSignature {
+    /// The hex-encoded public key of the signer.
+    pub public_key: String,
+    /// The hex-encoded signature of the whole file.
+    pub signature: String,
+}
+
+pub fn signature_path(path: &str) -> String {
+    format!("{path}.sig")
+}
+
+/// Creates a new signing key in `path`, which must not exist yet. Returns its public key.
+pub fn generate_key(path: &str) -> Result<String> {
+    let key = SigningKey::generate(&mut rand::rngs::OsRng);
+
+    let mut file = File::create_new(path).context("create key file")?;
+    writeln!(file, "{}", hex::encode(key.to_bytes())).context("write key file")?;
+
+    Ok(hex::encode(key.verifying_key().to_bytes()))
+}
+
+/// Loads a signing key written by [`generate_key`].
+pub fn load_key(path: &str) -> Result<SigningKey> {
+    let content = std::fs::read_to_string(path).context("read key file")?;
+    let bytes = decode::<32>(content.trim()).ok_or_else(|| eyre!("{path} is not a signing key"))?;
+
+    Ok(SigningKey::from_bytes(&bytes))
+}
+
+/// Signs the file at `path`, writing the signature to `<path>.sig`.
+pub fn sign_file(key: &SigningKey, path: &str) -> Result<()> {
+    let content = std::fs::read(path).context("read file to sign")?;
+    let signature = sign(key, &content);
+
+    let json = serde_json::to_vec_pretty(&signature)?;
+    std::fs::write(signature_path(path), json).context("write signature")
+}
+
+/// Checks the signature next to the file at `path`, if there is one. When `trusted_keys` isn't
+/// empty, the file has to be signed by one of them.
+pub fn check_file(path: &str, content: &[u8], trusted_keys: &[String]) -> Result<()> {
+    let signature = match std::fs::read(signature_path(path)) {
+        Ok(json) => Some(serde_json::from_slice(&json).context("deserialize signature")?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).context("read signature"),
+    };
+
+    match check(signature.as_ref(), content, trusted_keys)? {
+        Some(signer) => println!("{path} is signed by {signer}"),
+        None => println!("{path} is not signed"),
+    }
+    Ok(())
+}
+
+fn sign(key: &SigningKey, content: &[u8]) -> DatasetSignature {
+    DatasetSignature {
+        public_key: hex::encode(key.verifying_key().to_bytes()),
+        signature: hex::encode(key.sign(content).to_bytes()),
+    }
+}
+
+/// Verifies a signature of `content`, returning the public key of the signer if it is signed.
+fn check<'a>(
+    signature: Option<&'a DatasetSignature>,
+    content: &[u8],
+    trusted_keys: &[String],
+) -> Result<Option<&'a str>> {
+    let Some(signature) = signature else {
+        if !trusted_keys.is_empty() {
+            return Err(eyre!(
+                "The dataset is not signed, but trusted_signers is set"
+            ));
+        }
+        return Ok(None);
+    };
+
+    let public_key = decode::<32>(&signature.public_key)
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+        .ok_or_else(|| eyre!("Invalid public key {}", signature.public_key))?;
+    let bytes = decode::<64>(&signature.signature).ok_or_else(|| eyre!("Invalid signature"))?;
+    public_key
+        .verify(content, &Signature::from_bytes(&bytes))
+        .map_err(|_| eyre!("The signature doesn't match the dataset"))?;
+
+    if !trusted_keys.is_empty()
+        && !trusted_keys
+            .iter()
+            .any(|k| k.eq_ignore_ascii_case(&signature.public_key))
+    {
+        return Err(eyre!(
+            "The dataset is signed by {}, which is not in trusted_signers",
+            signature.public_key
+        ));
+    }
+
+    Ok(Some(&signature.public_key))
+}
+
+fn decode<const N: usize>(value: &str) -> Option<[u8; N]> {
+    hex::decode(value).ok()?.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_signatures() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let other = SigningKey::from_bytes(&[8; 32]);
+        let trusted = vec![hex::encode(key.verifying_key().to_bytes())];
+        let signature = sign(&key, b"dataset");
+
+        assert!(check(Some(&signature), b"dataset", &[]).unwrap().is_some());
+        assert!(check(Some(&signature), b"dataset", &trusted).is_ok());
+        assert!(check(Some(&signature), b"tampered", &[]).is_err());
+        assert!(check(Some(&sign(&other, b"dataset")), b"dataset", &trusted).is_err());
+        assert!(check(None, b"dataset", &[]).unwrap().is_none());
+        assert!(check(None, b"dataset", &trusted).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use color_eyre::Result;
+
+use crate::{
+    database::DatabaseAccess,
+    fetch::{self, ParsedBlocklist},
+    MastodonBlockList,
+};
+
+/// A kind of blocklist an instance may publish. Support for other fediverse software or static
+/// lists is added by implementing this for the new format and adding it to [`SOURCES`], rather
+/// than by special-casing it in the fetch pipeline.
+pub trait BlockSource: Sync {
+    /// Stored with every fetched blocklist, so archived responses are parsed the same way.
+    fn name(&self) -> &'static str;
+
+    /// Path of the blocklist on an instance.
+    fn path(&self) -> &'static str;
+
+    /// Turns a raw response into normalized blocks.
+    fn parse(&self, body: &[u8]) -> Result<ParsedBlocklist>;
+}
+
+/// The public domain blocks api of mastodon and its forks.
+pub struct MastodonApi;
+
+impl BlockSource for MastodonApi {
+    fn name(&self) -> &'static str {
+        "mastodon"
+    }
+
+    fn path(&self) -> &'static str {
+        "api/v1/instance/domain_blocks"
+    }
+
+    fn parse(&self, body: &[u8]) -> Result<ParsedBlocklist> {
+        fetch::parse_blocklist(body)
+    }
+}
+
+/// Every known source, in the order they are tried when fetching an instance.
+pub const SOURCES: &[&dyn BlockSource] = &[&MastodonApi];
+
+pub fn by_name(name: &str) -> Option<&'static dyn BlockSource> {
+    SOURCES.iter().copied().find(|s| s.name() == name)
+}
+
+/// Gets the source the stored blocklist of an instance came from. Blocklists stored before
+/// sources were recorded came from the mastodon api.
+pub fn stored_source(db: &DatabaseAccess, domain: &str) -> Result<&'static dyn BlockSource> {
+    let name = db
+        .get::<MastodonBlockList>(domain)?
+        .and_then(|b| b.metadata)
+        .and_then(|m| m.source);
+
+    Ok(name.as_deref().and_then(by_name).unwrap_or(&MastodonApi))
+}
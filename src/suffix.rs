@@ -0,0 +1,47 @@
+/// Domains that host many instances as subdomains. A censored pattern of the right shape is
+/// likely a subdomain of one of these, which only leaves the subdomain to brute-force.
+const HOSTING_SUFFIXES: &[&str] = &["masto.host", "hostdon.ne.jp", "mastodon.cloud"];
+
+/// Proposes patterns with the censored characters of a hosting suffix filled in, for every
+/// hosting suffix that fits the end of `pattern`. Patterns whose suffix is fully visible already
+/// gain nothing and are left out.
+pub fn hypotheses(pattern: &str) -> Vec<String> {
+    let mut hypotheses = vec![];
+    for suffix in HOSTING_SUFFIXES {
+        let suffix = format!(".{suffix}");
+        // there has to be room for a subdomain before the suffix
+        let Some(split) = pattern.len().checked_sub(suffix.len()).filter(|s| *s > 0) else {
+            continue;
+        };
+        let Some(tail) = pattern.get(split..) else {
+            continue;
+        };
+
+        let fits = tail
+            .bytes()
+            .zip(suffix.bytes())
+            .all(|(p, s)| p == b'*' || p == s);
+        if fits && tail.contains('*') {
+            hypotheses.push(format!("{}{suffix}", &pattern[..split]));
+        }
+    }
+
+    hypotheses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proposes_fitting_suffixes() {
+        assert_eq!(hypotheses("f*o.m*st*.h**t"), vec!["f*o.masto.host"]);
+        // fully visible suffixes are already searched as they are
+        assert!(hypotheses("f*o.masto.host").is_empty());
+        // a different length can't be the same suffix
+        assert!(hypotheses("f*o.m*st*.h***t").is_empty());
+        // no room for a subdomain
+        assert!(hypotheses("m*st*.h**t").is_empty());
+        assert!(hypotheses("*****.****").is_empty());
+    }
+}
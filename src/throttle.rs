@@ -0,0 +1,131 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use reqwest::{header::HeaderMap, RequestBuilder, Response, StatusCode};
+
+// used when a 429 doesn't say how long to wait
+const DEFAULT_PAUSE: Duration = Duration::from_secs(60);
+const MAX_PAUSE: Duration = Duration::from_secs(10 * 60);
+const MAX_RETRIES: u32 = 3;
+
+#[derive(Default)]
+struct State {
+    /// Minimum time between any two requests, from `--max-rps`.
+    min_interval: Option<Duration>,
+    next_slot: Option<Instant>,
+    /// Minimum time between two requests to the same host, from `host_interval_secs`.
+    host_interval: Option<Duration>,
+    next_host_slot: HashMap<String, Instant>,
+    /// Hosts that answered with a 429, and until when they are left alone.
+    paused: HashMap<String, Instant>,
+}
+
+static STATE: LazyLock<Mutex<State>> = LazyLock::new(Mutex::default);
+
+fn state() -> MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Limits all outgoing requests to `max_rps` requests per second, and those to a single host to
+/// one per `host_interval`.
+pub fn init(max_rps: Option<f64>, host_interval: Option<Duration>) {
+    let mut state = state();
+    state.min_interval = max_rps.map(|rps| Duration::from_secs_f64(1. / rps));
+    state.host_interval = host_interval;
+}
+
+/// Sends a request, waiting for the global rate limit and for any pause of its host first.
+/// A 429 pauses every request to that host for as long as its `Retry-After` asks, after which
+/// the request is retried. `request` builds the request again for every attempt.
+pub async fn send(url: &str, request: impl Fn() -> RequestBuilder) -> reqwest::Result<Response> {
+    let host = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+        .unwrap_or_default();
+
+    let mut retries = 0;
+    loop {
+        wait(&host).await;
+
+        let response = request().send().await?;
+        if response.status() != StatusCode::TOO_MANY_REQUESTS || retries == MAX_RETRIES {
+            return Ok(response);
+        }
+
+        let pause = retry_after(response.headers()).unwrap_or(DEFAULT_PAUSE);
+        println!("{host} is rate limiting, pausing requests to it for {pause:?}");
+        state().paused.insert(host.clone(), Instant::now() + pause);
+        retries += 1;
+    }
+}
+
+async fn wait(host: &str) {
+    let paused_until = state().paused.get(host).copied();
+    if let Some(until) = paused_until {
+        tokio::time::sleep(until.saturating_duration_since(Instant::now())).await;
+    }
+
+    let host_delay = {
+        let mut state = state();
+        state.host_interval.map(|interval| {
+            let now = Instant::now();
+            let slot = state
+                .next_host_slot
+                .get(host)
+                .map_or(now, |s| (*s).max(now));
+            state
+                .next_host_slot
+                .insert(host.to_string(), slot + interval);
+            slot - now
+        })
+    };
+    if let Some(delay) = host_delay {
+        tokio::time::sleep(delay).await;
+    }
+
+    let delay = {
+        let mut state = state();
+        state.min_interval.map(|interval| {
+            let now = Instant::now();
+            let slot = state.next_slot.map_or(now, |s| s.max(now));
+            state.next_slot = Some(slot + interval);
+            slot - now
+        })
+    };
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Reads a `Retry-After` header given in seconds, capped so a single host can't stall a run.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get("retry-after")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+
+    Some(Duration::from_secs(seconds).min(MAX_PAUSE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert("retry-after", "120".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+
+        headers.insert("retry-after", "86400".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(MAX_PAUSE));
+    }
+}
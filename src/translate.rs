@@ -0,0 +1,157 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use color_eyre::{
+    eyre::{eyre, Context},
+    Result,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    autosave::Autosave,
+    config::Config,
+    database::{DatabaseAccess, DatabaseObject},
+    interrupt, DomainEntry,
+};
+
+/// A block comment translated by `translate_command`. Stored apart from the blocks, keyed by the
+/// comment, so `process` keeps it and instances copying a reason share its translation.
+#[derive(Serialize, Deserialize)]
+pub struct Translation {
+    /// The hex SHA-256 digest of the original comment.
+    pub digest: String,
+    /// The language the comment was translated to.
+    pub language: String,
+    pub text: String,
+    pub translated_at: u64,
+}
+
+impl DatabaseObject for Translation {
+    const KEY_NAME: &'static str = "translation";
+
+    fn get_id(&self) -> Cow<str> {
+        (&self.digest).into()
+    }
+}
+
+fn comment_digest(comment: &str) -> String {
+    hex::encode(Sha256::digest(comment.as_bytes()))
+}
+
+/// The stored translation of a comment, if there is one.
+pub fn get(db: &mut DatabaseAccess, comment: &str) -> Result<Option<String>> {
+    Ok(db
+        .get::<Translation>(&comment_digest(comment))?
+        .map(|t| t.text))
+}
+
+/// A comment followed by its translation, if one is stored.
+pub fn with_translation(db: &mut DatabaseAccess, comment: &str) -> Result<String> {
+    Ok(match get(db, comment)? {
+        Some(translated) => format!("{comment} [{translated}]"),
+        None => comment.to_string(),
+    })
+}
+
+/// Translates the comments written in another language than `translate_language` with
+/// `command`. Comments that already have a translation to that language are skipped unless
+/// `refresh` is set.
+pub fn translate(
+    db: &mut DatabaseAccess,
+    config: &Config,
+    command: &str,
+    refresh: bool,
+) -> Result<()> {
+    let target = &config.translate_language;
+
+    // by comment, with the detected language
+    let mut comments = BTreeMap::new();
+    let keys = db.iter_keys::<DomainEntry>().collect::<Vec<_>>();
+    for key in keys {
+        let entry = db.get::<DomainEntry>(&key)?.unwrap();
+        for blocked_by in entry.blocked_by.into_values() {
+            if let (Some(comment), Some(language)) =
+                (blocked_by.comment, blocked_by.comment_language)
+            {
+                if &language != target {
+                    comments.insert(comment, language);
+                }
+            }
+        }
+    }
+
+    let mut pending = vec![];
+    for (comment, language) in comments {
+        let digest = comment_digest(&comment);
+        let existing = db.get::<Translation>(&digest)?;
+        if refresh || existing.is_none_or(|t| &t.language != target) {
+            pending.push((digest, comment, language));
+        }
+    }
+    println!("Translating {} comments to {target}", pending.len());
+
+    let mut autosave = Autosave::new(config, crate::DATABASE_FILE);
+    let mut failed = 0;
+    for (digest, comment, language) in pending {
+        match run(command, &comment, &language, target) {
+            Ok(text) => {
+                db.set(Translation {
+                    digest,
+                    language: target.clone(),
+                    text,
+                    translated_at: crate::unix_now(),
+                });
+                autosave.changed(db)?;
+            }
+            Err(e) => {
+                println!("Error while translating {comment:?}: {e:#}");
+                failed += 1;
+            }
+        }
+        if interrupt::is_interrupted() {
+            break;
+        }
+    }
+
+    if failed > 0 {
+        println!("{failed} comments could not be translated");
+    }
+    Ok(())
+}
+
+/// Runs the translate command through `sh` with the comment on stdin and the languages in
+/// `SOURCE_LANGUAGE` and `TARGET_LANGUAGE`. Its output is the translation.
+fn run(command: &str, comment: &str, source: &str, target: &str) -> Result<String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("SOURCE_LANGUAGE", source)
+        .env("TARGET_LANGUAGE", target)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("run translate command")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        _ = stdin.write_all(comment.as_bytes());
+    }
+
+    let output = child
+        .wait_with_output()
+        .context("wait for translate command")?;
+    if !output.status.success() {
+        return Err(eyre!("translate command exited with {}", output.status));
+    }
+
+    let text = String::from_utf8(output.stdout).context("read translation")?;
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(eyre!("translate command printed nothing"));
+    }
+    Ok(text.to_string())
+}